@@ -0,0 +1,451 @@
+#ifndef MONERO_WALLET_API_H
+#define MONERO_WALLET_API_H
+
+/* generated by cbindgen from rust/src, do not edit: make -C tests/c header */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// the outputs of one call. every output (and the error) is followed by a NUL byte that
+// its length doesn't count, so json outputs can be used as C strings
+typedef struct MwaResult MwaResult;
+
+typedef void (*MwaInputCallback)(uint8_t*, size_t);
+
+typedef void (*MwaOutputCallback)(const uint8_t*, size_t);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// like wallet_init, the handle is written to handle (0 if the keys are invalid or no handles
+// are left)
+MwaResult *mwa_wallet_init(const uint8_t *primary_address,
+                           size_t primary_address_len,
+                           const uint8_t *secret_view_key,
+                           size_t secret_view_key_len,
+                           uint32_t last_subaddress_index,
+                           uint32_t *handle);
+
+bool mwa_wallet_free(uint32_t handle);
+
+// entropy has to be 32 bytes
+MwaResult *mwa_vk_from_entropy(const uint8_t *entropy, size_t entropy_len);
+
+// a new random secret spend key as hex
+MwaResult *mwa_make_spendkey(void);
+
+// the secret spend key of a 64 byte hex seed
+MwaResult *mwa_make_spendkey_from_seed(const uint8_t *seed, size_t seed_len);
+
+// the view key and primary addresses of a hex secret spend key
+MwaResult *mwa_make_viewkey(const uint8_t *spend_key, size_t spend_key_len);
+
+// see make_input, getouts_response is a get_outs.bin response
+MwaResult *mwa_make_input(const uint8_t *output_json,
+                          size_t output_json_len,
+                          const uint8_t *getouts_response,
+                          size_t getouts_response_len);
+
+MwaResult *mwa_sample_decoys(const uint8_t *json_params, size_t json_params_len);
+
+// parses an address of any network
+MwaResult *mwa_parse_address(const uint8_t *address, size_t address_len);
+
+MwaResult *mwa_estimate_fee(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_sign_transaction(const uint8_t *signable_transaction,
+                                size_t signable_transaction_len,
+                                const uint8_t *secret_spend_key,
+                                size_t secret_spend_key_len);
+
+// getouts_response may be empty
+MwaResult *mwa_decode_transaction(const uint8_t *transaction,
+                                  size_t transaction_len,
+                                  const uint8_t *getouts_response,
+                                  size_t getouts_response_len);
+
+MwaResult *mwa_get_tx_key(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_check_tx_key(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_get_out_proof(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_check_out_proof(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_check_in_proof(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_parse_reserve_proof(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_check_reserve_proof(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_get_spend_proof(const uint8_t *json_params,
+                               size_t json_params_len,
+                               const uint8_t *secret_spend_key,
+                               size_t secret_spend_key_len,
+                               const uint8_t *getouts_response,
+                               size_t getouts_response_len);
+
+MwaResult *mwa_check_spend_proof(const uint8_t *json_params,
+                                 size_t json_params_len,
+                                 const uint8_t *getouts_response,
+                                 size_t getouts_response_len);
+
+MwaResult *mwa_verify_message(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_parse_payment_uri(const uint8_t *uri, size_t uri_len);
+
+MwaResult *mwa_make_payment_uri(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_openalias_query_name(const uint8_t *alias, size_t alias_len);
+
+MwaResult *mwa_parse_openalias(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_parse_amount(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_format_amount(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_compute_key_image(const uint8_t *output,
+                                 size_t output_len,
+                                 const uint8_t *sender_spend_key,
+                                 size_t sender_spend_key_len);
+
+// outputs the binary request body
+MwaResult *mwa_build_getblocksbin_request(const uint8_t *json_params, size_t json_params_len);
+
+// outputs the binary request body
+MwaResult *mwa_build_getoutsbin_request(const uint8_t *outputs_array, size_t outputs_array_len);
+
+MwaResult *mwa_build_send_raw_transaction_request(const uint8_t *json_params,
+                                                  size_t json_params_len);
+
+MwaResult *mwa_parse_send_raw_transaction_response(const uint8_t *response, size_t response_len);
+
+MwaResult *mwa_convert_get_outs_bin_response_to_json(const uint8_t *response, size_t response_len);
+
+MwaResult *mwa_compute_balances(const uint8_t *json_params, size_t json_params_len);
+
+MwaResult *mwa_wallet_make_integrated_address(uint32_t handle, uint64_t payment_id);
+
+MwaResult *mwa_wallet_make_subaddress(uint32_t handle, uint32_t major, uint32_t minor);
+
+// parses an address, other networks than the wallet's are an error
+MwaResult *mwa_wallet_parse_address(uint32_t handle, const uint8_t *address, size_t address_len);
+
+MwaResult *mwa_wallet_make_transaction(uint32_t handle,
+                                       const uint8_t *json_params,
+                                       size_t json_params_len);
+
+MwaResult *mwa_wallet_make_external_sweep_transaction(uint32_t handle,
+                                                      const uint8_t *json_params,
+                                                      size_t json_params_len);
+
+MwaResult *mwa_wallet_select_inputs(uint32_t handle,
+                                    const uint8_t *json_params,
+                                    size_t json_params_len);
+
+MwaResult *mwa_wallet_make_sweep_all_transactions(uint32_t handle,
+                                                  const uint8_t *json_params,
+                                                  size_t json_params_len);
+
+MwaResult *mwa_wallet_make_batch_payout(uint32_t handle,
+                                        const uint8_t *json_params,
+                                        size_t json_params_len);
+
+MwaResult *mwa_wallet_inspect_signable_transaction(uint32_t handle,
+                                                   const uint8_t *json_params,
+                                                   size_t json_params_len);
+
+MwaResult *mwa_wallet_get_in_proof(uint32_t handle,
+                                   const uint8_t *json_params,
+                                   size_t json_params_len);
+
+MwaResult *mwa_wallet_get_reserve_proof(uint32_t handle,
+                                        const uint8_t *json_params,
+                                        size_t json_params_len);
+
+MwaResult *mwa_wallet_sign_message(uint32_t handle,
+                                   const uint8_t *json_params,
+                                   size_t json_params_len);
+
+MwaResult *mwa_wallet_check_tx_key(uint32_t handle,
+                                   const uint8_t *json_params,
+                                   size_t json_params_len);
+
+MwaResult *mwa_wallet_get_out_proof(uint32_t handle,
+                                    const uint8_t *json_params,
+                                    size_t json_params_len);
+
+MwaResult *mwa_wallet_check_out_proof(uint32_t handle,
+                                      const uint8_t *json_params,
+                                      size_t json_params_len);
+
+MwaResult *mwa_wallet_check_in_proof(uint32_t handle,
+                                     const uint8_t *json_params,
+                                     size_t json_params_len);
+
+MwaResult *mwa_wallet_check_reserve_proof(uint32_t handle,
+                                          const uint8_t *json_params,
+                                          size_t json_params_len);
+
+MwaResult *mwa_wallet_verify_message(uint32_t handle,
+                                     const uint8_t *json_params,
+                                     size_t json_params_len);
+
+MwaResult *mwa_wallet_parse_payment_uri(uint32_t handle, const uint8_t *uri, size_t uri_len);
+
+MwaResult *mwa_wallet_make_payment_uri(uint32_t handle,
+                                       const uint8_t *json_params,
+                                       size_t json_params_len);
+
+MwaResult *mwa_wallet_parse_openalias(uint32_t handle,
+                                      const uint8_t *json_params,
+                                      size_t json_params_len);
+
+MwaResult *mwa_wallet_address_ownership(uint32_t handle,
+                                        const uint8_t *address,
+                                        size_t address_len);
+
+// outputs the block meta, then the scan result
+MwaResult *mwa_wallet_scan_blocks_with_get_blocks_bin(uint32_t handle,
+                                                      const uint8_t *response,
+                                                      size_t response_len);
+
+MwaResult *mwa_wallet_load_get_blocks_bin_response(uint32_t handle,
+                                                   const uint8_t *response,
+                                                   size_t response_len);
+
+MwaResult *mwa_wallet_get_blocks_bin_scan_one_block(uint32_t handle, uint32_t block_index);
+
+// outputs the new cache file contents, then the changed outputs
+MwaResult *mwa_wallet_scan_cache_append(uint32_t handle,
+                                        const uint8_t *cache,
+                                        size_t cache_len,
+                                        const uint8_t *scan_result,
+                                        size_t scan_result_len,
+                                        const uint8_t *params_json,
+                                        size_t params_json_len);
+
+MwaResult *mwa_wallet_scan_cache_compact(uint32_t handle, const uint8_t *cache, size_t cache_len);
+
+// false if the call failed, mwa_result_error has the {code, message, field} json then
+bool mwa_result_ok(const MwaResult *result);
+
+// most calls output one buffer, the scans and scan_cache_append two
+size_t mwa_result_output_count(const MwaResult *result);
+
+// the index-th output, NULL if there is none. it lives until mwa_result_free
+const uint8_t *mwa_result_output(const MwaResult *result, size_t index, size_t *length);
+
+// the {code, message, field} json of a failed call, NULL if it succeeded
+const uint8_t *mwa_result_error(const MwaResult *result, size_t *length);
+
+// copies the index-th output into a buffer of the caller and returns its length (0 if there
+// is none). nothing is copied if it is longer than capacity, call again with a larger buffer
+size_t mwa_result_copy_output(const MwaResult *result,
+                              size_t index,
+                              uint8_t *buffer,
+                              size_t capacity);
+
+void mwa_result_free(MwaResult *result);
+
+// for hosts that want the callbacks of the wasm ABI natively: the exports without the
+// mwa_ prefix use them. NULL unsets one, its output is dropped then
+void mwa_set_io_callbacks(MwaInputCallback input,
+                          MwaOutputCallback output,
+                          MwaOutputCallback output_error);
+
+// WASM / C ABI
+void make_spendkey(void);
+
+void make_spendkey_from_seed(size_t seed_string_len);
+
+void make_viewkey(size_t spend_key_string_len);
+
+void vk_from_entropy(void);
+
+void init_viewpair(size_t primary_address_string_len,
+                   size_t secret_view_key_string_len,
+                   uint32_t last_subaddress_index);
+
+// handle based ABI: like init_viewpair, but the wallet is kept next to any other
+// and the returned handle is passed to the wallet_* exports. 0 if the keys are invalid
+// or no handles are left
+uint32_t wallet_init(size_t primary_address_string_len,
+                     size_t secret_view_key_string_len,
+                     uint32_t last_subaddress_index);
+
+// drops the wallet of a handle, false if the handle was unknown
+bool wallet_free(uint32_t handle);
+
+void make_integrated_address(uint64_t payment_id);
+
+void wallet_make_integrated_address(uint32_t handle, uint64_t payment_id);
+
+void make_subaddress(uint32_t major, uint32_t minor);
+
+void wallet_make_subaddress(uint32_t handle, uint32_t major, uint32_t minor);
+
+void sample_decoys(size_t sample_json_str_len);
+
+// turns output into and input with decoys, so a new transaction can be built
+void make_input(size_t output_json_len, size_t getouts_response_len);
+
+void parse_address(size_t address_string_len);
+
+void wallet_parse_address(uint32_t handle, size_t address_string_len);
+
+void make_transaction(size_t json_params_len);
+
+void wallet_make_transaction(uint32_t handle, size_t json_params_len);
+
+void make_external_sweep_transaction(size_t json_params_len);
+
+void wallet_make_external_sweep_transaction(uint32_t handle, size_t json_params_len);
+
+// estimated weight and fee of a transaction with the given shape, no inputs or decoys needed
+void estimate_fee(size_t json_params_len);
+
+// picks inputs from the spendable outputs, see transaction_building::coin_selection for the strategies
+void select_inputs(size_t json_params_len);
+
+void wallet_select_inputs(uint32_t handle, size_t json_params_len);
+
+// sweeps every input (or every input below a threshold) to one address,
+// split into as many transactions as the weight limit requires
+void make_sweep_all_transactions(size_t json_params_len);
+
+void wallet_make_sweep_all_transactions(uint32_t handle, size_t json_params_len);
+
+// splits a long payout list into transactions that stay under the output limit,
+// every transaction gets its own inputs
+void make_batch_payout(size_t json_params_len);
+
+void wallet_make_batch_payout(uint32_t handle, size_t json_params_len);
+
+// decodes a signable transaction so an offline signer can show what it is about to sign
+void inspect_signable_transaction(size_t json_params_len);
+
+void wallet_inspect_signable_transaction(uint32_t handle, size_t json_params_len);
+
+void sign_transaction(size_t tx_len, size_t secret_spend_key_len);
+
+// decodes a signed transaction before it is relayed.
+// pass getouts_response_len = 0 to skip the proof verification
+void decode_transaction(size_t tx_len, size_t getouts_response_len);
+
+// the tx key(s) of a transaction we built, derived from its outgoing view key
+void get_tx_key(size_t json_params_len);
+
+// how much a transaction sent to an address, given the tx key
+void check_tx_key(size_t json_params_len);
+
+// OutProofV2: proves we paid an address in a transaction
+void get_out_proof(size_t json_params_len);
+
+void check_out_proof(size_t json_params_len);
+
+void get_in_proof(size_t json_params_len);
+
+void wallet_get_in_proof(uint32_t handle, size_t json_params_len);
+
+void check_in_proof(size_t json_params_len);
+
+// ReserveProofV2: proves the wallet owns (and had not spent) the given outputs
+void get_reserve_proof(size_t json_params_len);
+
+void wallet_get_reserve_proof(uint32_t handle, size_t json_params_len);
+
+// lists the transactions and key images check_reserve_proof needs
+void parse_reserve_proof(size_t json_params_len);
+
+void check_reserve_proof(size_t json_params_len);
+
+// SpendProofV1: proves we sent a transaction, ring members as for decode_transaction
+void get_spend_proof(size_t json_params_len,
+                     size_t secret_spend_key_len,
+                     size_t getouts_response_len);
+
+void check_spend_proof(size_t json_params_len, size_t getouts_response_len);
+
+// SigV2 message signature with the spend or view key, as wallet2's sign_message
+void sign_message(size_t json_params_len);
+
+void wallet_sign_message(uint32_t handle, size_t json_params_len);
+
+void verify_message(size_t json_params_len);
+
+// parses a monero: payment uri, the addresses have to be on the network of the wallet
+void parse_payment_uri(size_t uri_len);
+
+void wallet_parse_payment_uri(uint32_t handle, size_t uri_len);
+
+void make_payment_uri(size_t json_params_len);
+
+void wallet_make_payment_uri(uint32_t handle, size_t json_params_len);
+
+// the dns name whose TXT records the host has to resolve for an OpenAlias
+void openalias_query_name(size_t alias_len);
+
+void parse_openalias(size_t json_params_len);
+
+// is this address ours: primary, integrated (with payment id), subaddress (with index) or not ours
+void address_ownership(size_t address_len);
+
+void wallet_address_ownership(uint32_t handle, size_t address_len);
+
+// exact XMR (or millinero, micronero) decimal string to piconero
+void parse_amount(size_t json_params_len);
+
+void format_amount(size_t json_params_len);
+
+void compute_key_image(size_t output_hex_string_len, size_t sender_spend_key_len);
+
+void build_getblocksbin_request(size_t json_params_len);
+
+void build_getoutsbin_request(size_t outputs_array_len);
+
+// outputs the json body for POST /send_raw_transaction
+void build_send_raw_transaction_request(size_t json_params_len);
+
+// outputs {"relayed", "untrusted"} or an error with a stable code (see relay::RelayError)
+void parse_send_raw_transaction_response(size_t response_len);
+
+void convert_get_outs_bin_response_to_json(size_t response_len);
+
+void scan_blocks_with_get_blocks_bin(size_t response_len);
+
+void wallet_scan_blocks_with_get_blocks_bin(uint32_t handle, size_t response_len);
+
+void load_get_blocks_bin_response(size_t response_len);
+
+void wallet_load_get_blocks_bin_response(uint32_t handle, size_t response_len);
+
+void get_blocks_bin_scan_one_block(uint32_t block_index);
+
+void wallet_get_blocks_bin_scan_one_block(uint32_t handle, uint32_t block_index);
+
+// merges a scan result into the _cache.json contents, the host only reads and writes the file
+// outputs the new cache file contents, then the changed outputs as json
+void scan_cache_append(size_t cache_len, size_t scan_result_len, size_t params_json_len);
+
+void wallet_scan_cache_append(uint32_t handle,
+                              size_t cache_len,
+                              size_t scan_result_len,
+                              size_t params_json_len);
+
+void scan_cache_compact(size_t cache_len);
+
+void wallet_scan_cache_compact(uint32_t handle, size_t cache_len);
+
+// locked and unlocked balances per account and subaddress, computed from the found outputs
+void compute_balances(size_t json_params_len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MONERO_WALLET_API_H */
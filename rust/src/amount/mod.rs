@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+
+use crate::block_parsing::amount_to_string;
+use crate::error::AbiError;
+
+/// the denominations amounts can be given in, by their number of decimals in piconero
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+  #[default]
+  Xmr,
+  Millinero,
+  Micronero,
+  Piconero,
+}
+impl Unit {
+  pub fn decimals(self) -> usize {
+    match self {
+      Unit::Xmr => 12,
+      Unit::Millinero => 9,
+      Unit::Micronero => 6,
+      Unit::Piconero => 0,
+    }
+  }
+  fn piconero(self) -> u64 {
+    10u64.pow(self.decimals() as u32)
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct ParseAmountParams {
+  amount: String,
+  #[serde(default)]
+  unit: Unit,
+  /// e.g. ',' for "1,000.5", without it any separator is rejected
+  thousands_separator: Option<char>,
+}
+#[derive(Debug, Deserialize)]
+struct FormatAmountParams {
+  piconero: String,
+  #[serde(default)]
+  unit: Unit,
+  thousands_separator: Option<char>,
+}
+#[derive(Debug, Serialize)]
+pub struct ParsedAmount {
+  #[serde(serialize_with = "amount_to_string")]
+  piconero: u64,
+}
+#[derive(Debug, Serialize)]
+pub struct FormattedAmount {
+  amount: String,
+}
+
+/// removes the thousands separators of the integer part, they have to group it by three
+fn strip_separators(whole: &str, separator: Option<char>) -> Result<String, String> {
+  let Some(separator) = separator.filter(|separator| whole.contains(*separator)) else {
+    return Ok(whole.to_string());
+  };
+  let groups: Vec<&str> = whole.split(separator).collect();
+  let first_ok = (1..=3).contains(&groups[0].len());
+  if !first_ok || groups[1..].iter().any(|group| group.len() != 3) {
+    return Err(format!("misplaced thousands separator in '{}'", whole));
+  }
+  Ok(groups.concat())
+}
+
+/// exact decimal to piconero: no rounding, more decimals than the unit has is an error
+pub fn parse_amount(amount: &str, unit: Unit, separator: Option<char>) -> Result<u64, String> {
+  let invalid = || format!("invalid amount '{}'", amount);
+  if separator.is_some_and(|separator| separator == '.' || separator.is_ascii_digit()) {
+    return Err("the thousands separator can't be '.' or a digit".to_string());
+  }
+  let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+  let whole = strip_separators(whole, separator)?;
+  if whole.is_empty() && fraction.is_empty()
+    || !whole.bytes().all(|b| b.is_ascii_digit())
+    || !fraction.bytes().all(|b| b.is_ascii_digit())
+  {
+    return Err(invalid());
+  }
+  if fraction.len() > unit.decimals() {
+    return Err(format!(
+      "amount '{}' has more than {} decimals, the smallest unit is 1 piconero",
+      amount,
+      unit.decimals()
+    ));
+  }
+  let whole: u64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| invalid())? };
+  let fraction: u64 = if fraction.is_empty() {
+    0
+  } else {
+    // at most 12 digits, checked above
+    format!("{:0<width$}", fraction, width = unit.decimals()).parse().unwrap()
+  };
+  whole
+    .checked_mul(unit.piconero())
+    .and_then(|whole| whole.checked_add(fraction))
+    .ok_or_else(|| format!("amount '{}' is too large", amount))
+}
+
+/// piconero to the shortest exact decimal in the given unit
+pub fn format_amount(piconero: u64, unit: Unit, separator: Option<char>) -> String {
+  let whole = (piconero / unit.piconero()).to_string();
+  let whole = match separator {
+    Some(separator) => {
+      let mut grouped = String::with_capacity(whole.len() * 4 / 3);
+      for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+          grouped.push(separator);
+        }
+        grouped.push(digit);
+      }
+      grouped
+    }
+    None => whole,
+  };
+  let fraction = piconero % unit.piconero();
+  if fraction == 0 {
+    return whole;
+  }
+  let fraction = format!("{:0width$}", fraction, width = unit.decimals());
+  format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+pub fn parse_amount_json(json_params: &str) -> Result<ParsedAmount, AbiError> {
+  let params: ParseAmountParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input("json_params", format!("failed to parse amount params json: {:?}", e))
+  })?;
+  Ok(ParsedAmount {
+    piconero: parse_amount(&params.amount, params.unit, params.thousands_separator)
+      .map_err(|message| AbiError::invalid_input("amount", message))?,
+  })
+}
+pub fn format_amount_json(json_params: &str) -> Result<FormattedAmount, AbiError> {
+  let params: FormatAmountParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse format amount params json: {:?}", e),
+    )
+  })?;
+  let piconero = params.piconero.parse::<u64>().map_err(|e| {
+    AbiError::invalid_input(
+      "piconero",
+      format!("failed to parse piconero amount '{}' : {:?}", params.piconero, e),
+    )
+  })?;
+  Ok(FormattedAmount { amount: format_amount(piconero, params.unit, params.thousands_separator) })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const XMR: u64 = 1_000_000_000_000;
+
+  #[test]
+  fn parses_exact_decimals_in_every_unit() {
+    assert_eq!(parse_amount("1.5", Unit::Xmr, None), Ok(XMR + XMR / 2));
+    assert_eq!(parse_amount("0.000000000001", Unit::Xmr, None), Ok(1));
+    assert_eq!(parse_amount("1.5", Unit::Millinero, None), Ok(1_500_000_000));
+    assert_eq!(parse_amount("0.000000001", Unit::Millinero, None), Ok(1));
+    assert_eq!(parse_amount("1.5", Unit::Micronero, None), Ok(1_500_000));
+    assert_eq!(parse_amount("0.000001", Unit::Micronero, None), Ok(1));
+    assert_eq!(parse_amount("42", Unit::Piconero, None), Ok(42));
+  }
+
+  #[test]
+  fn rejects_excess_precision_per_unit() {
+    assert!(parse_amount("0.0000000000001", Unit::Xmr, None).is_err());
+    assert!(parse_amount("0.0000000001", Unit::Millinero, None).is_err());
+    assert!(parse_amount("0.0000001", Unit::Micronero, None).is_err());
+    assert!(parse_amount("1.0", Unit::Piconero, None).is_err());
+    // trailing zeros are precision too
+    assert!(parse_amount("1.0000000000000", Unit::Xmr, None).is_err());
+  }
+
+  #[test]
+  fn a_missing_whole_or_fraction_part_is_zero() {
+    assert_eq!(parse_amount(".5", Unit::Xmr, None), Ok(XMR / 2));
+    assert_eq!(parse_amount("5.", Unit::Xmr, None), Ok(5 * XMR));
+    for invalid in [".", "", "1.2.3", "-1", "+1", " 1", "1e3", "0x10", "1,5"] {
+      assert!(parse_amount(invalid, Unit::Xmr, None).is_err(), "{}", invalid);
+    }
+  }
+
+  #[test]
+  fn thousands_separators_group_by_three() {
+    assert_eq!(parse_amount("1,000,000.5", Unit::Xmr, Some(',')), Ok(1_000_000 * XMR + XMR / 2));
+    assert_eq!(parse_amount("1'000", Unit::Xmr, Some('\'')), Ok(1_000 * XMR));
+    assert_eq!(parse_amount("1000", Unit::Xmr, Some(',')), Ok(1_000 * XMR));
+    for misplaced in ["1,00,000", "1000,000", ",100", "100,", "1,,000"] {
+      assert!(parse_amount(misplaced, Unit::Xmr, Some(',')).is_err(), "{}", misplaced);
+    }
+    // separators only in the integer part, and only the one that was asked for
+    assert!(parse_amount("1.000,5", Unit::Xmr, Some(',')).is_err());
+    assert!(parse_amount("1 000", Unit::Xmr, Some(',')).is_err());
+    assert!(parse_amount("1.000", Unit::Xmr, Some('.')).is_err());
+  }
+
+  #[test]
+  fn rejects_amounts_above_u64() {
+    assert_eq!(parse_amount("18446744073709551615", Unit::Piconero, None), Ok(u64::MAX));
+    assert!(parse_amount("18446744073709551616", Unit::Piconero, None).is_err());
+    assert_eq!(parse_amount("18446744.073709551615", Unit::Xmr, None), Ok(u64::MAX));
+    assert!(parse_amount("18446744.073709551616", Unit::Xmr, None).is_err());
+    assert!(parse_amount("18446745", Unit::Xmr, None).is_err());
+    assert!(parse_amount("18446744073709552", Unit::Millinero, None).is_err());
+  }
+
+  #[test]
+  fn formats_the_shortest_exact_decimal() {
+    assert_eq!(format_amount(XMR + XMR / 2, Unit::Xmr, None), "1.5");
+    assert_eq!(format_amount(5 * XMR, Unit::Xmr, None), "5");
+    assert_eq!(format_amount(1, Unit::Xmr, None), "0.000000000001");
+    assert_eq!(format_amount(1_234_567 * XMR, Unit::Xmr, Some(',')), "1,234,567");
+    assert_eq!(format_amount(123 * XMR, Unit::Xmr, Some(',')), "123");
+    assert_eq!(format_amount(1_500_000, Unit::Micronero, None), "1.5");
+    assert_eq!(format_amount(u64::MAX, Unit::Xmr, None), "18446744.073709551615");
+  }
+
+  #[test]
+  fn format_then_parse_round_trips() {
+    let units = [Unit::Xmr, Unit::Millinero, Unit::Micronero, Unit::Piconero];
+    let amounts = [0, 1, 999, 1_000, XMR / 2, XMR, 1_234_567_890_123_456, u64::MAX];
+    for unit in units {
+      for separator in [None, Some(','), Some('\'')] {
+        for piconero in amounts {
+          let formatted = format_amount(piconero, unit, separator);
+          assert_eq!(parse_amount(&formatted, unit, separator), Ok(piconero), "{}", formatted);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn json_errors_name_their_field() {
+    let parsed = parse_amount_json(r#"{"amount":"1.5","unit":"millinero"}"#).unwrap();
+    assert_eq!(parsed.piconero, 1_500_000_000);
+    let error = parse_amount_json(r#"{"amount":"0.0000001","unit":"micronero"}"#).unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("amount"));
+    let error = parse_amount_json(r#"{"amount":"1","unit":"nanonero"}"#).unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("json_params"));
+    let error = format_amount_json(r#"{"piconero":"1.5"}"#).unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("piconero"));
+  }
+}
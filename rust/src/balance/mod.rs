@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::block_parsing::amount_to_string;
+use crate::error::AbiError;
+use crate::transaction_building::inputs::read_output_from_string;
+
+/// CRYPTONOTE_DEFAULT_TX_SPENDABLE_AGE
+pub const SPENDABLE_AGE: u64 = 10;
+/// CRYPTONOTE_MINED_MONEY_UNLOCK_WINDOW
+pub const MINED_MONEY_UNLOCK_WINDOW: u64 = 60;
+
+/// the fields of a scanned output (see wallet_output_to_json) the balance engine needs
+#[derive(Deserialize, Debug)]
+struct FoundOutput {
+  amount: String,
+  tx_hash: String,
+  index_on_blockchain: u64,
+  block_height: u64,
+  is_miner_tx: bool,
+  serialized: String,
+  spent_in_tx_hash: Option<String>,
+  burned: Option<u64>,
+}
+#[derive(Deserialize, Debug)]
+struct BalanceParams {
+  outputs: Vec<FoundOutput>,
+  /// key image hex -> index_on_blockchain.to_string(), as in _cache.json
+  #[serde(default)]
+  own_key_images: HashMap<String, String>,
+  /// key images seen on chain (or in our own pending transactions)
+  #[serde(default)]
+  spent_key_images: Vec<String>,
+  current_height: u64,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Amounts {
+  #[serde(serialize_with = "amount_to_string")]
+  unlocked: u64,
+  #[serde(serialize_with = "amount_to_string")]
+  locked: u64,
+  /// received from others, change is not counted here
+  #[serde(serialize_with = "amount_to_string")]
+  received: u64,
+  #[serde(serialize_with = "amount_to_string")]
+  spent: u64,
+  #[serde(serialize_with = "amount_to_string")]
+  change: u64,
+}
+#[derive(Serialize, Debug)]
+pub struct SubaddressBalance {
+  minor: u32,
+  #[serde(flatten)]
+  amounts: Amounts,
+}
+#[derive(Serialize, Debug)]
+pub struct AccountBalance {
+  major: u32,
+  #[serde(flatten)]
+  amounts: Amounts,
+  subaddresses: Vec<SubaddressBalance>,
+}
+#[derive(Serialize, Debug)]
+pub struct Balances {
+  current_height: u64,
+  #[serde(flatten)]
+  total: Amounts,
+  accounts: Vec<AccountBalance>,
+}
+
+/// no sum of real outputs overflows, a malformed cache could
+fn add_amount(sum: &mut u64, amount: u64) -> Result<(), AbiError> {
+  *sum = sum
+    .checked_add(amount)
+    .ok_or_else(|| AbiError::invalid_input("outputs", "balance overflows u64"))?;
+  Ok(())
+}
+impl Amounts {
+  fn add(&mut self, other: &Amounts) -> Result<(), AbiError> {
+    add_amount(&mut self.unlocked, other.unlocked)?;
+    add_amount(&mut self.locked, other.locked)?;
+    add_amount(&mut self.received, other.received)?;
+    add_amount(&mut self.spent, other.spent)?;
+    add_amount(&mut self.change, other.change)
+  }
+}
+
+/// the block an output was included in counts as its first confirmation
+/// (current_height is the number of blocks in the chain, not the height of the top block)
+pub fn confirmations(block_height: u64, current_height: u64) -> u64 {
+  current_height.saturating_sub(block_height)
+}
+pub fn is_unlocked(block_height: u64, is_miner_tx: bool, current_height: u64) -> bool {
+  let required = if is_miner_tx { MINED_MONEY_UNLOCK_WINDOW } else { SPENDABLE_AGE };
+  confirmations(block_height, current_height) >= required
+}
+
+pub fn compute_balances(json_params: &str) -> Result<Balances, AbiError> {
+  let params: BalanceParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input("json_params", format!("failed to parse balance params json: {}", e))
+  })?;
+
+  let spent_key_images: HashSet<&String> = params.spent_key_images.iter().collect();
+  let spent_by_key_image: HashSet<&String> = params
+    .own_key_images
+    .iter()
+    .filter(|(key_image, _)| spent_key_images.contains(key_image))
+    .map(|(_, global_id)| global_id)
+    .collect();
+  // transactions that spent one of our outputs, our outputs in them are change
+  let own_spends: HashSet<&String> =
+    params.outputs.iter().filter_map(|output| output.spent_in_tx_hash.as_ref()).collect();
+
+  let mut by_index: BTreeMap<(u32, u32), Amounts> = BTreeMap::new();
+  for (i, output) in params.outputs.iter().enumerate() {
+    if output.burned.is_some() {
+      continue;
+    }
+    let amount = output.amount.parse::<u64>().map_err(|e| {
+      AbiError::invalid_input(
+        &format!("outputs[{}].amount", i),
+        format!("failed to parse output amount '{}' : {:?}", output.amount, e),
+      )
+    })?;
+    let index = read_output_from_string(&output.serialized, &format!("outputs[{}].serialized", i))?
+      .subaddress()
+      .map(|subaddress| (subaddress.account(), subaddress.address()))
+      .unwrap_or((0, 0));
+
+    let amounts = by_index.entry(index).or_default();
+    if own_spends.contains(&output.tx_hash) {
+      add_amount(&mut amounts.change, amount)?;
+    } else {
+      add_amount(&mut amounts.received, amount)?;
+    }
+    let global_id = output.index_on_blockchain.to_string();
+    if output.spent_in_tx_hash.is_some() || spent_by_key_image.contains(&global_id) {
+      add_amount(&mut amounts.spent, amount)?;
+    } else if is_unlocked(output.block_height, output.is_miner_tx, params.current_height) {
+      add_amount(&mut amounts.unlocked, amount)?;
+    } else {
+      add_amount(&mut amounts.locked, amount)?;
+    }
+  }
+
+  let mut total = Amounts::default();
+  let mut accounts: Vec<AccountBalance> = vec![];
+  for ((major, minor), amounts) in by_index {
+    total.add(&amounts)?;
+    if accounts.last().map(|account| account.major) != Some(major) {
+      accounts.push(AccountBalance { major, amounts: Amounts::default(), subaddresses: vec![] });
+    }
+    let account = accounts.last_mut().unwrap(); // pushed above
+    account.amounts.add(&amounts)?;
+    account.subaddresses.push(SubaddressBalance { minor, amounts });
+  }
+  Ok(Balances { current_height: params.current_height, total, accounts })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{keys, output_json};
+  use serde_json::{Value, json};
+
+  fn balances(outputs: &[Value], current_height: u64) -> Value {
+    let params = json!({ "outputs": outputs, "current_height": current_height });
+    serde_json::to_value(compute_balances(&params.to_string()).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn outputs_unlock_after_10_confirmations() {
+    assert!(!is_unlocked(100, false, 109));
+    assert!(is_unlocked(100, false, 110));
+    let output = output_json(&keys(7), 1000, 10, 100, false);
+    assert_eq!(balances(&[output.clone()], 109)["locked"], "1000");
+    assert_eq!(balances(&[output], 110)["unlocked"], "1000");
+  }
+
+  #[test]
+  fn coinbase_outputs_unlock_after_60_confirmations() {
+    assert!(!is_unlocked(100, true, 159));
+    assert!(is_unlocked(100, true, 160));
+    let output = output_json(&keys(7), 1000, 10, 100, true);
+    assert_eq!(balances(&[output.clone()], 159)["locked"], "1000");
+    assert_eq!(balances(&[output], 160)["unlocked"], "1000");
+  }
+
+  #[test]
+  fn outputs_of_our_own_spends_are_change() {
+    let wallet = keys(7);
+    let mut spent = output_json(&wallet, 5000, 10, 100, false);
+    spent["spent_in_tx_hash"] = json!("aa");
+    let mut change = output_json(&wallet, 1500, 11, 120, false);
+    change["tx_hash"] = json!("aa");
+    let burned = {
+      let mut burned = change.clone();
+      burned["index_on_blockchain"] = json!(12);
+      burned["burned"] = json!(11);
+      burned
+    };
+
+    let total = balances(&[spent, change, burned], 200);
+    assert_eq!(total["received"], "5000");
+    assert_eq!(total["change"], "1500");
+    assert_eq!(total["spent"], "5000");
+    assert_eq!(total["unlocked"], "1500");
+    assert_eq!(total["accounts"][0]["subaddresses"][0]["minor"], 0);
+  }
+
+  #[test]
+  fn overflowing_amounts_are_an_error() {
+    let wallet = keys(7);
+    let mut first = output_json(&wallet, 1000, 10, 100, false);
+    first["amount"] = json!(u64::MAX.to_string());
+    let second = output_json(&wallet, 1000, 11, 100, false);
+    let params = json!({ "outputs": [first, second], "current_height": 200 });
+    assert!(compute_balances(&params.to_string()).is_err());
+  }
+}
@@ -1,10 +1,11 @@
+pub mod view_tags;
 use cuprate_rpc_types::{bin::GetBlocksResponse, misc::Status};
 use cuprate_types::TransactionBlobs;
+use curve25519_dalek::Scalar;
 use monero_wallet::{
   NotTimelocked, Scanner, WalletOutput,
   block::Block,
   extra::PaymentId,
-  rpc::ScannableBlock,
   transaction::{Pruned, Transaction},
 };
 use serde::Serialize;
@@ -26,6 +27,10 @@ where
     }
   }
 }
+/// amounts are strings in json, js numbers can't hold every u64
+pub fn amount_to_string<S: serde::Serializer>(amount: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_str(&amount.to_string())
+}
 #[derive(serde::Serialize)]
 pub struct BlockInfo {
   block_height: u64,
@@ -38,6 +43,28 @@ pub struct GetBlocksResult {
   daemon_height: u64,
   status: Status,
   block_infos: Vec<BlockInfo>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  scan_stats: Option<ScanStats>,
+}
+impl GetBlocksResult {
+  pub fn with_scan_stats(mut self, scan_stats: ScanStats) -> Self {
+    self.scan_stats = Some(scan_stats);
+    self
+  }
+}
+/// counters to measure how much work the view tag prefilter saved
+#[derive(serde::Serialize, Debug, Default, Clone, Copy)]
+pub struct ScanStats {
+  txs_seen: u64,
+  view_tag_matches: u64,
+  found_outputs: u64,
+}
+impl ScanStats {
+  fn add(&mut self, other: &ScanStats) {
+    self.txs_seen += other.txs_seen;
+    self.view_tag_matches += other.view_tag_matches;
+    self.found_outputs += other.found_outputs;
+  }
 }
 pub fn get_blocks_bin_response_meta(
   get_blocks_bin: &GetBlocksResponse,
@@ -48,10 +75,8 @@ pub fn get_blocks_bin_response_meta(
   for (index, block_entry) in get_blocks_bin.blocks.iter().enumerate() {
     let block = match Block::read::<&[u8]>(&mut block_entry.block.as_ref()) {
       Ok(block) => block,
-      Err(_) => {
-        println!("Error reading block");
-        continue;
-      }
+      // scan_block reports it
+      Err(_) => continue,
     };
     block_infos.push(BlockInfo {
       block_timestamp: block.header.timestamp,
@@ -64,6 +89,7 @@ pub fn get_blocks_bin_response_meta(
     daemon_height,
     status: get_blocks_bin.base.response_base.status.clone(),
     block_infos,
+    scan_stats: None,
   }
 }
 #[derive(serde::Serialize, Debug)]
@@ -77,34 +103,39 @@ pub(crate) struct InputImage {
 }
 pub fn scan_blocks(
   scanner: Scanner,
+  view_key: &Scalar,
   primary_address: &str,
   get_blocks_bin: GetBlocksResponse,
-) -> String {
+) -> (Result<String, String>, ScanStats) {
   let mut output_jsons = Vec::new();
   let mut input_images_jsons: Vec<InputImage> = Vec::new();
+  let mut scan_stats = ScanStats::default();
 
   for index in 0..get_blocks_bin.blocks.len() {
-    match scan_block(&scanner, primary_address, &get_blocks_bin, index) {
-      Ok((outputs, inputs)) => {
+    match scan_block(&scanner, view_key, primary_address, &get_blocks_bin, index) {
+      Ok((outputs, inputs, block_stats)) => {
         output_jsons.extend(outputs);
         input_images_jsons.extend(inputs);
+        scan_stats.add(&block_stats);
       }
-      Err(error_json) => return error_json,
+      Err(error) => return (Err(error), scan_stats),
     }
   }
   let final_output_json: serde_json::Value =
     json!({"outputs":output_jsons, "all_key_images": input_images_jsons});
-  return final_output_json.to_string();
+  return (Ok(final_output_json.to_string()), scan_stats);
 }
 
 pub(crate) fn scan_block(
   scanner: &Scanner,
+  view_key: &Scalar,
   primary_address: &str,
   get_blocks_bin: &GetBlocksResponse,
   index: usize,
-) -> Result<(Vec<serde_json::Value>, Vec<InputImage>), String> {
+) -> Result<(Vec<serde_json::Value>, Vec<InputImage>, ScanStats), String> {
   let mut output_jsons = Vec::new();
   let mut input_images_jsons: Vec<InputImage> = Vec::new();
+  let mut scan_stats = ScanStats::default();
 
   let block_entry = &get_blocks_bin.blocks[index];
 
@@ -117,75 +148,29 @@ pub(crate) fn scan_block(
 
   let block = match Block::read::<&[u8]>(&mut block_entry.block.as_ref()) {
     Ok(block) => block,
-    Err(_) => {
-      println!("Error reading block");
-      return Ok((output_jsons, input_images_jsons));
+    Err(error) => {
+      let height = get_blocks_bin.start_height + (index as u64);
+      return Err(format!("failed to parse block at height {}: {}", height, error));
     }
   };
   let block_timestamp = block.header.timestamp;
-
-  let mut transactions = Vec::new();
-
-  match &block_entry.txs {
-    TransactionBlobs::Normal(_) => {
-      // we don't handle non pruned transactions
-    }
-    TransactionBlobs::Pruned(pruned_txs) => {
-      for entry in pruned_txs {
-        match Transaction::<Pruned>::read::<&[u8]>(&mut entry.blob.as_ref()) {
-          Ok(tx) => {
-            transactions.push(tx);
-          }
-          Err(_) => {
-            println!("Error reading pruned transaction");
-          }
-        }
-      }
-    }
-    TransactionBlobs::None => {
-      // No transactions in this block
-    }
-  }
-
-  let mut txs_with_hashes = vec![(
-    block.miner_transaction().hash(),
-    Transaction::<Pruned>::from(block.miner_transaction().clone()),
-  )];
-  for (hash, tx) in block.transactions.iter().zip(transactions.clone()) {
-    txs_with_hashes.push((*hash, tx));
-  }
-  let input_images = txs_with_hashes.iter().fold(Vec::new(), |mut acc, (hash_bytes, tx)| {
-    let tx_hash = hex::encode(hash_bytes); // hash_bytes is [u8; 32]
-    tx.prefix().inputs.iter().enumerate().for_each(|(i, input)| match input {
-      monero_wallet::transaction::Input::Gen(_) => {}
-      monero_oxide::transaction::Input::ToKey { amount: _, key_offsets: _, key_image } => {
-        let key_image_hex = hex::encode(key_image.to_bytes());
-        acc.push(InputImage {
-          key_image_hex,
-          relative_index: i,
-          tx_hash: tx_hash.clone(),
-          block_timestamp,
-          block_height: get_blocks_bin.start_height + (index as u64),
-          block_hash: hex::encode(block.hash()),
-        });
-      }
-    });
-    acc
-  });
-  input_images_jsons.extend(input_images);
+  let block_height = get_blocks_bin.start_height + (index as u64);
+  let block_hash = hex::encode(block.hash());
 
   // Scan the miner transaction
+  let miner_transaction = Transaction::<Pruned>::from(block.miner_transaction().clone());
+  scan_stats.txs_seen += 1;
   match scanner.scan_transaction(
     output_index_for_first_ringct_output,
     block.miner_transaction().hash(),
-    &Transaction::<Pruned>::from(block.miner_transaction().clone()),
+    &miner_transaction,
   ) {
     Ok(res) => {
-      let block_height = get_blocks_bin.start_height + (index as u64);
       if let NotTimelocked::OnChain(unlocked) =
         &res.additional_timelock_satisfied_by((block_height + 60) as usize, u64::MAX)
       {
         for wallet_output in unlocked {
+          scan_stats.found_outputs += 1;
           output_jsons.push(wallet_output_to_json(
             wallet_output,
             block_height,
@@ -196,46 +181,87 @@ pub(crate) fn scan_block(
         }
       }
     }
-    Err(error) => {
-      let error_message = format!("Error scanning miner transaction: {}", error);
-      let error_json = json!({
-          "error": error_message
-      })
-      .to_string();
-      return Err(error_json);
-    }
+    Err(error) => return Err(format!("error scanning miner transaction: {}", error)),
   };
+  // the ringct output index of the first output of the next transaction
+  let mut next_ringct_output_index = output_index_for_first_ringct_output;
+  if matches!(miner_transaction, Transaction::V2 { .. }) {
+    next_ringct_output_index = next_ringct_output_index
+      .map(|value| value + miner_transaction.prefix().outputs.len() as u64);
+  }
 
-  let scan_block = ScannableBlock { block, transactions, output_index_for_first_ringct_output };
-  match scanner.scan(scan_block) {
-    Ok(res) => {
-      let unlocked = res.not_additionally_locked();
-      let block_height = get_blocks_bin.start_height + (index as u64);
+  let pruned_txs = match &block_entry.txs {
+    TransactionBlobs::Normal(_) => {
+      // we don't handle non pruned transactions
+      return Ok((output_jsons, input_images_jsons, scan_stats));
+    }
+    TransactionBlobs::Pruned(pruned_txs) => pruned_txs,
+    TransactionBlobs::None => {
+      // No transactions in this block
+      return Ok((output_jsons, input_images_jsons, scan_stats));
+    }
+  };
 
-      for wallet_output in unlocked {
-        output_jsons.push(wallet_output_to_json(
-          &wallet_output,
-          block_height,
-          block_timestamp,
-          primary_address,
-          false,
-        ));
+  for (hash, entry) in block.transactions.iter().zip(pruned_txs) {
+    scan_stats.txs_seen += 1;
+    // fast path: only look at the prefix, fully parse the transaction if a view tag matches
+    let header = match view_tags::read_tx_header(entry.blob.as_ref()) {
+      Ok(header) => header,
+      Err(error) => {
+        return Err(format!("failed to parse pruned transaction {}: {}", hex::encode(hash), error));
       }
+    };
+    let tx_hash = hex::encode(hash);
+    for (relative_index, key_image) in header.key_images.iter().enumerate() {
+      input_images_jsons.push(InputImage {
+        key_image_hex: hex::encode(key_image),
+        relative_index,
+        tx_hash: tx_hash.clone(),
+        block_timestamp,
+        block_height,
+        block_hash: block_hash.clone(),
+      });
     }
-    Err(error) => {
-      let error_message = format!("Error scanning block: {}", error);
-      let error_json = json!({
-          "error": error_message
-      })
-      .to_string();
-      return Err(error_json);
+
+    let output_index_for_this_tx = next_ringct_output_index;
+    if header.version == 2 {
+      next_ringct_output_index =
+        next_ringct_output_index.map(|value| value + header.view_tags.len() as u64);
+    }
+
+    match view_tags::matching_view_tags(&header, view_key) {
+      Some(0) => continue,
+      Some(matches) => scan_stats.view_tag_matches += matches,
+      None => {} // untagged outputs, scan the full transaction
+    }
+
+    let tx = match Transaction::<Pruned>::read::<&[u8]>(&mut entry.blob.as_ref()) {
+      Ok(tx) => tx,
+      Err(error) => {
+        return Err(format!("failed to parse pruned transaction {}: {}", hex::encode(hash), error));
+      }
+    };
+    match scanner.scan_transaction(output_index_for_this_tx, *hash, &tx) {
+      Ok(res) => {
+        for wallet_output in res.not_additionally_locked() {
+          scan_stats.found_outputs += 1;
+          output_jsons.push(wallet_output_to_json(
+            &wallet_output,
+            block_height,
+            block_timestamp,
+            primary_address,
+            false,
+          ));
+        }
+      }
+      Err(error) => return Err(format!("error scanning block: {}", error)),
     }
   }
 
-  Ok((output_jsons, input_images_jsons))
+  Ok((output_jsons, input_images_jsons, scan_stats))
 }
 
-fn wallet_output_to_json(
+pub(crate) fn wallet_output_to_json(
   wallet_output: &WalletOutput,
   block_height: u64,
   block_timestamp: u64,
@@ -0,0 +1,216 @@
+use curve25519_dalek::Scalar;
+use monero_primitives::keccak256;
+use monero_wallet::extra::Extra;
+
+/// the parts of a serialized transaction prefix we need to decide
+/// whether a full deserialization is worth it
+pub(crate) struct TxHeader {
+  pub(crate) version: u64,
+  pub(crate) key_images: Vec<[u8; 32]>,
+  pub(crate) view_tags: Vec<Option<u8>>,
+  pub(crate) extra: Vec<u8>,
+}
+
+pub(crate) fn read_byte(reader: &mut &[u8]) -> Result<u8, String> {
+  let (byte, rest) = reader.split_first().ok_or("unexpected end of transaction prefix")?;
+  *reader = rest;
+  Ok(*byte)
+}
+pub(crate) fn read_bytes<const N: usize>(reader: &mut &[u8]) -> Result<[u8; N], String> {
+  if reader.len() < N {
+    return Err("unexpected end of transaction prefix".to_string());
+  }
+  let (bytes, rest) = reader.split_at(N);
+  *reader = rest;
+  Ok(bytes.try_into().unwrap()) // length checked above
+}
+pub(crate) fn read_varint(reader: &mut &[u8]) -> Result<u64, String> {
+  let mut value: u64 = 0;
+  for i in 0..10 {
+    let byte = read_byte(reader)?;
+    if i == 9 && byte > 1 {
+      return Err("varint overflows u64".to_string());
+    }
+    value |= u64::from(byte & 0x7f) << (7 * i);
+    if byte & 0x80 == 0 {
+      if i != 0 && byte == 0 {
+        return Err("varint is not canonically encoded".to_string());
+      }
+      return Ok(value);
+    }
+  }
+  Err("varint overflows u64".to_string())
+}
+pub(crate) fn write_varint(mut value: u64, buf: &mut Vec<u8>) {
+  while value >= 0x80 {
+    buf.push((value as u8 & 0x7f) | 0x80);
+    value >>= 7;
+  }
+  buf.push(value as u8);
+}
+
+/// reads the transaction prefix without decompressing any points
+/// (pruned blobs start with the prefix, the rct base follows it)
+pub(crate) fn read_tx_header(blob: &[u8]) -> Result<TxHeader, String> {
+  let mut reader = blob;
+  let version = read_varint(&mut reader)?;
+  let _unlock_time = read_varint(&mut reader)?;
+
+  let mut key_images = Vec::new();
+  let input_count = read_varint(&mut reader)?;
+  for _ in 0..input_count {
+    match read_byte(&mut reader)? {
+      0xff => {
+        let _height = read_varint(&mut reader)?;
+      }
+      0x02 => {
+        let _amount = read_varint(&mut reader)?;
+        let offsets = read_varint(&mut reader)?;
+        for _ in 0..offsets {
+          read_varint(&mut reader)?;
+        }
+        key_images.push(read_bytes::<32>(&mut reader)?);
+      }
+      tag => return Err(format!("unknown input type {}", tag)),
+    }
+  }
+
+  let mut view_tags = Vec::new();
+  let output_count = read_varint(&mut reader)?;
+  for _ in 0..output_count {
+    let _amount = read_varint(&mut reader)?;
+    match read_byte(&mut reader)? {
+      0x02 => {
+        read_bytes::<32>(&mut reader)?;
+        view_tags.push(None);
+      }
+      0x03 => {
+        read_bytes::<32>(&mut reader)?;
+        view_tags.push(Some(read_byte(&mut reader)?));
+      }
+      tag => return Err(format!("unknown output type {}", tag)),
+    }
+  }
+
+  let extra_len = usize::try_from(read_varint(&mut reader)?).map_err(|e| e.to_string())?;
+  if reader.len() < extra_len {
+    return Err("unexpected end of transaction prefix".to_string());
+  }
+  let extra = reader[..extra_len].to_vec();
+
+  Ok(TxHeader { version, key_images, view_tags, extra })
+}
+
+pub(crate) fn view_tag(derivation: &[u8; 32], output_index: usize) -> u8 {
+  let mut preimage = b"view_tag".to_vec();
+  preimage.extend_from_slice(derivation);
+  write_varint(output_index as u64, &mut preimage);
+  keccak256(preimage)[0]
+}
+
+/// Some(n) is the number of outputs whose view tag matches our view key,
+/// None means the transaction can not be prefiltered (untagged outputs or unreadable extra)
+/// and has to be fully scanned
+pub(crate) fn matching_view_tags(header: &TxHeader, view_key: &Scalar) -> Option<u64> {
+  if header.view_tags.iter().any(|tag| tag.is_none()) {
+    return None;
+  }
+  let extra = Extra::read::<&[u8]>(&mut header.extra.as_ref()).ok()?;
+  let (main_keys, additional_keys) = extra.keys()?;
+
+  let main_derivations: Vec<[u8; 32]> =
+    main_keys.iter().map(|key| (view_key * key).mul_by_cofactor().compress().to_bytes()).collect();
+
+  let mut matches = 0;
+  for (output_index, tag) in header.view_tags.iter().enumerate() {
+    let tag = (*tag)?;
+    let additional_derivation = additional_keys
+      .as_ref()
+      .and_then(|keys| keys.get(output_index))
+      .map(|key| (view_key * key).mul_by_cofactor().compress().to_bytes());
+    if main_derivations
+      .iter()
+      .chain(additional_derivation.iter())
+      .any(|derivation| view_tag(derivation, output_index) == tag)
+    {
+      matches += 1;
+    }
+  }
+  Some(matches)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{coinbase_blob, keys, scan};
+
+  #[test]
+  fn varints_round_trip_and_reject_non_canonical_encodings() {
+    for value in [0, 1, 0x7f, 0x80, 300, u64::from(u32::MAX), u64::MAX] {
+      let mut buf = Vec::new();
+      write_varint(value, &mut buf);
+      assert_eq!(read_varint(&mut buf.as_slice()), Ok(value));
+    }
+    assert!(read_varint(&mut [0x80, 0x00].as_slice()).is_err());
+    assert!(read_varint(&mut [0xff; 10].as_slice()).is_err());
+    assert!(read_varint(&mut [0x80].as_slice()).is_err());
+  }
+
+  #[test]
+  fn header_has_the_key_images_and_view_tags() {
+    let mut blob = Vec::new();
+    write_varint(2, &mut blob);
+    write_varint(0, &mut blob);
+    write_varint(2, &mut blob);
+    for key_image in [[1; 32], [2; 32]] {
+      blob.push(0x02);
+      write_varint(0, &mut blob); // amount
+      write_varint(2, &mut blob);
+      write_varint(1000, &mut blob);
+      write_varint(5, &mut blob);
+      blob.extend(key_image);
+    }
+    write_varint(2, &mut blob);
+    for tag in [7, 9] {
+      write_varint(0, &mut blob);
+      blob.push(0x03);
+      blob.extend([3; 32]);
+      blob.push(tag);
+    }
+    write_varint(2, &mut blob);
+    blob.extend([0x02, 0x00]);
+
+    let header = read_tx_header(&blob).unwrap();
+    assert_eq!(header.version, 2);
+    assert_eq!(header.key_images, vec![[1; 32], [2; 32]]);
+    assert_eq!(header.view_tags, vec![Some(7), Some(9)]);
+    assert_eq!(header.extra, vec![0x02, 0x00]);
+    assert!(read_tx_header(&blob[..blob.len() - 1]).is_err());
+  }
+
+  #[test]
+  fn prefilter_finds_the_outputs_the_full_scan_finds() {
+    let (ours, theirs) = (keys(11), keys(23));
+    let tx_key = Scalar::from(5u64);
+    for recipients in
+      [vec![&ours], vec![&theirs, &ours, &theirs], vec![&theirs], vec![&ours, &ours]]
+    {
+      let blob = coinbase_blob(&recipients, 1000, tx_key, true);
+      let header = read_tx_header(&blob).unwrap();
+      let found = scan(&ours, &blob, 0).len();
+      let expected = recipients.iter().filter(|keys| keys.spend == ours.spend).count();
+      assert_eq!(found, expected);
+      assert_eq!(matching_view_tags(&header, &ours.view_key), Some(found as u64));
+    }
+  }
+
+  #[test]
+  fn untagged_outputs_are_not_prefiltered() {
+    let ours = keys(11);
+    let blob = coinbase_blob(&[&ours], 1000, Scalar::from(5u64), false);
+    let header = read_tx_header(&blob).unwrap();
+    assert_eq!(header.view_tags, vec![None]);
+    assert_eq!(matching_view_tags(&header, &ours.view_key), None);
+    assert_eq!(scan(&ours, &blob, 0).len(), 1);
+  }
+}
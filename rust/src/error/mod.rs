@@ -0,0 +1,74 @@
+use serde::Serialize;
+
+use crate::relay::RelayError;
+
+/// an input could not be parsed: bad hex, json, key, address, ...
+pub const INVALID_INPUT: &str = "invalid_input";
+/// an address of another network than the wallet's
+pub const NETWORK_MISMATCH: &str = "network_mismatch";
+/// init_viewpair was not called yet
+pub const NOT_INITIALIZED: &str = "not_initialized";
+/// the wallet handle is unknown or was freed
+pub const UNKNOWN_HANDLE: &str = "unknown_handle";
+/// every wallet handle was given out already
+pub const NO_HANDLES_LEFT: &str = "no_handles_left";
+/// the inputs don't cover the payments and the fee
+pub const NOT_ENOUGH_FUNDS: &str = "not_enough_funds";
+/// the input was fine, but the operation failed (proof mismatch, ...)
+pub const FAILED: &str = "failed";
+
+/// what every export passes to output_error: a stable code to match on, a message for
+/// humans and the input the error is about (if it is about one). field is the name of the
+/// export's input ("json_params", "secret_spend_key", ...) or the key inside json_params
+/// ("tx_hash", "payments[2].address", ...)
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AbiError {
+  pub code: String,
+  pub message: String,
+  pub field: Option<String>,
+}
+impl AbiError {
+  pub fn new(code: &str, message: impl Into<String>) -> AbiError {
+    AbiError { code: code.to_string(), message: message.into(), field: None }
+  }
+  pub fn invalid_input(field: &str, message: impl Into<String>) -> AbiError {
+    AbiError::new(INVALID_INPUT, message).with_field(field)
+  }
+  pub fn with_field(mut self, field: &str) -> AbiError {
+    self.field = Some(field.to_string());
+    self
+  }
+  pub fn to_json(&self) -> String {
+    serde_json::to_string(self).unwrap_or_else(|_| format!("{{\"code\":\"{}\"}}", self.code))
+  }
+}
+impl std::fmt::Display for AbiError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.message)
+  }
+}
+
+/// the errors the modules don't classify: the input was fine as far as they can tell, the
+/// operation failed. bad inputs are reported with AbiError::invalid_input where they are parsed
+impl From<String> for AbiError {
+  fn from(message: String) -> AbiError {
+    AbiError::new(FAILED, message)
+  }
+}
+impl From<&str> for AbiError {
+  fn from(message: &str) -> AbiError {
+    AbiError::new(FAILED, message)
+  }
+}
+/// the relay codes are stable already, they are kept as they are
+impl From<RelayError> for AbiError {
+  fn from(error: RelayError) -> AbiError {
+    let code = serde_json::to_value(&error)
+      .ok()
+      .and_then(|json| json.get("code")?.as_str().map(str::to_string))
+      .unwrap_or_else(|| FAILED.to_string());
+    // the one relay error about the input of parse_send_raw_transaction_response
+    let field = matches!(error, RelayError::InvalidResponse { .. }).then(|| "response".to_string());
+    AbiError { code, message: error.message(), field }
+  }
+}
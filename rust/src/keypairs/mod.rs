@@ -1,16 +1,30 @@
+pub mod ownership;
+
 use monero_primitives::{keccak256_to_scalar};
 use monero_wallet::{ViewPair, address::Network};
 use curve25519_dalek::{Scalar, EdwardsPoint};
 
+use hex::FromHex;
 use rand_core::OsRng;
 use zeroize::Zeroizing;
 
+use crate::error::AbiError;
+
 pub fn make_spendkey() -> Scalar {
   Scalar::random(&mut OsRng)
 }
 pub fn make_spendkey_from_seed(seed: [u8; 64]) -> Scalar {
   keccak256_to_scalar(seed)
 }
+/// a secret key from hex, the error names the key (as field) but never echoes it
+pub fn parse_secret_key(name: &str, hex_str: &str) -> Result<Zeroizing<Scalar>, AbiError> {
+  let invalid =
+    |reason: &str| AbiError::invalid_input(name, format!("invalid {}: {}", name, reason));
+  let bytes = <[u8; 32]>::from_hex(hex_str).map_err(|_| invalid("not 32 bytes of hex"))?;
+  Option::<Scalar>::from(Scalar::from_canonical_bytes(bytes))
+    .map(Zeroizing::new)
+    .ok_or_else(|| invalid("not a canonical scalar"))
+}
 pub fn make_viewkey(spend_key: [u8; 32]) -> Scalar {
   keccak256_to_scalar(spend_key)
 }
@@ -22,11 +36,13 @@ pub struct ViewPairJson {
   testnet_primary: String,
 }
 
-pub fn viewpair_from_spendkey(spend_key: [u8; 32]) -> Result<ViewPairJson, String> {
-  let spend_scalar = Scalar::from_canonical_bytes(spend_key).unwrap(); // okay to panic if spend_key is invalid
+pub fn viewpair_from_spendkey(spend_key: [u8; 32]) -> Result<ViewPairJson, AbiError> {
+  let invalid = |message: String| AbiError::invalid_input("spend_key", message);
+  let spend_scalar = Option::<Scalar>::from(Scalar::from_canonical_bytes(spend_key))
+    .ok_or_else(|| invalid("invalid spend_key: not a canonical scalar".to_string()))?;
   let view_scalar = Zeroizing::new(make_viewkey(spend_key));
   let viewpair = ViewPair::new(EdwardsPoint::mul_base(&spend_scalar), view_scalar.clone())
-    .map_err(|e| format!("failed to parse new Viewpair {}", e))?;
+    .map_err(|e| invalid(format!("failed to parse new Viewpair {}", e)))?;
 
   let mainnet_primary = viewpair.legacy_address(Network::Mainnet).to_string();
   let stagenet_primary = viewpair.legacy_address(Network::Stagenet).to_string();
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use curve25519_dalek::{EdwardsPoint, Scalar};
+use monero_wallet::{
+  ViewPair,
+  address::{Network, SubaddressIndex},
+};
+use serde::Serialize;
+
+use crate::error::AbiError;
+use crate::transaction_building::transaction::parse_address_for_network;
+
+/// compressed spend key of every subaddress registered with the scanner -> (major, minor)
+pub type SubaddressTable = HashMap<[u8; 32], (u32, u32)>;
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Ownership {
+  NotOurs,
+  Primary,
+  /// payment id as make_integrated_address takes it
+  Integrated {
+    payment_id: String,
+  },
+  /// only registered subaddresses (make_subaddress or init_viewpair's last_subaddress_index),
+  /// outputs to the others are not scanned
+  Subaddress {
+    major: u32,
+    minor: u32,
+  },
+}
+
+/// remembers the spend key of a subaddress so it can be looked up by address later
+pub fn register(table: &mut SubaddressTable, viewpair: &ViewPair, index: SubaddressIndex) {
+  let spend = viewpair.subaddress(Network::Mainnet, index).spend(); // same on every network
+  table.insert(spend.compress().to_bytes(), (index.account(), index.address()));
+}
+
+/// which of our addresses this is, addresses of another network are an error
+pub fn address_ownership(
+  address: &str,
+  viewpair: &ViewPair,
+  view_key: &Scalar,
+  network: Network,
+  subaddresses: &SubaddressTable,
+) -> Result<Ownership, AbiError> {
+  let address = parse_address_for_network(address, network)?;
+
+  if address.is_subaddress() {
+    // anyone can pick a D and make C = a * D from our public view key, only the spend keys
+    // we derived ourselves (the registered ones) are ours
+    let registered = subaddresses.get(&address.spend().compress().to_bytes());
+    return Ok(match registered {
+      Some((major, minor)) if address.view() == view_key * address.spend() => {
+        Ownership::Subaddress { major: *major, minor: *minor }
+      }
+      _ => Ownership::NotOurs,
+    });
+  }
+  if address.spend() != viewpair.spend() || address.view() != EdwardsPoint::mul_base(view_key) {
+    return Ok(Ownership::NotOurs);
+  }
+  Ok(match address.payment_id() {
+    Some(payment_id) => {
+      Ownership::Integrated { payment_id: u64::from_le_bytes(payment_id).to_string() }
+    }
+    None => Ownership::Primary,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::keys;
+  use monero_wallet::address::{AddressType, MoneroAddress};
+
+  /// the ownership of address for keys(7) with subaddress 1/2 registered
+  fn ownership(address: &MoneroAddress) -> Ownership {
+    let wallet = keys(7);
+    let mut table = SubaddressTable::new();
+    register(&mut table, &wallet.viewpair(), SubaddressIndex::new(1, 2).unwrap());
+    let address = address.to_string();
+    address_ownership(&address, &wallet.viewpair(), &wallet.view_key, Network::Mainnet, &table)
+      .unwrap()
+  }
+
+  #[test]
+  fn our_addresses() {
+    let viewpair = keys(7).viewpair();
+    assert_eq!(ownership(&viewpair.legacy_address(Network::Mainnet)), Ownership::Primary);
+    let integrated = viewpair.legacy_integrated_address(Network::Mainnet, 5u64.to_le_bytes());
+    assert_eq!(ownership(&integrated), Ownership::Integrated { payment_id: "5".to_string() });
+    let subaddress = viewpair.subaddress(Network::Mainnet, SubaddressIndex::new(1, 2).unwrap());
+    assert_eq!(ownership(&subaddress), Ownership::Subaddress { major: 1, minor: 2 });
+  }
+
+  #[test]
+  fn unregistered_subaddresses_are_not_ours() {
+    let viewpair = keys(7).viewpair();
+    let subaddress = viewpair.subaddress(Network::Mainnet, SubaddressIndex::new(1, 3).unwrap());
+    assert_eq!(ownership(&subaddress), Ownership::NotOurs);
+  }
+
+  #[test]
+  fn subaddresses_forged_from_our_view_key_are_not_ours() {
+    let wallet = keys(7);
+    // D = d * G and C = d * A pass C == a * D without the spend key
+    let d = Scalar::from(1234u64);
+    let spend = EdwardsPoint::mul_base(&d);
+    let view = d * EdwardsPoint::mul_base(&wallet.view_key);
+    let forged = MoneroAddress::new(Network::Mainnet, AddressType::Subaddress, spend, view);
+    assert_eq!(forged.view(), wallet.view_key * forged.spend());
+    assert_eq!(ownership(&forged), Ownership::NotOurs);
+
+    // a registered D with another C
+    let registered =
+      wallet.viewpair().subaddress(Network::Mainnet, SubaddressIndex::new(1, 2).unwrap());
+    let other_view = MoneroAddress::new(
+      Network::Mainnet,
+      AddressType::Subaddress,
+      registered.spend(),
+      EdwardsPoint::mul_base(&d),
+    );
+    assert_eq!(ownership(&other_view), Ownership::NotOurs);
+  }
+
+  #[test]
+  fn addresses_of_other_wallets_are_not_ours() {
+    let other = keys(9).viewpair();
+    assert_eq!(ownership(&other.legacy_address(Network::Mainnet)), Ownership::NotOurs);
+    let subaddress = other.subaddress(Network::Mainnet, SubaddressIndex::new(1, 2).unwrap());
+    assert_eq!(ownership(&subaddress), Ownership::NotOurs);
+    // our spend key with another view key
+    let mixed = MoneroAddress::new(
+      Network::Mainnet,
+      AddressType::Legacy,
+      keys(7).spend,
+      other.legacy_address(Network::Mainnet).view(),
+    );
+    assert_eq!(ownership(&mixed), Ownership::NotOurs);
+  }
+
+  #[test]
+  fn addresses_of_another_network_are_an_error() {
+    let wallet = keys(7);
+    let stagenet = wallet.viewpair().legacy_address(Network::Stagenet).to_string();
+    let table = SubaddressTable::new();
+    let error =
+      address_ownership(&stagenet, &wallet.viewpair(), &wallet.view_key, Network::Mainnet, &table)
+        .unwrap_err();
+    assert_eq!(error.code, crate::error::NETWORK_MISMATCH);
+  }
+}
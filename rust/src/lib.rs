@@ -1,40 +1,44 @@
+pub mod amount;
+pub mod balance;
 pub mod block_parsing;
+pub mod error;
 pub mod transaction_building;
 pub mod keypairs;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod native;
+pub mod openalias;
+pub mod proofs;
+pub mod relay;
+pub mod scan_cache;
+#[cfg(test)]
+mod test_util;
+pub mod uri;
+pub mod wallet;
 use block_parsing::convert_to_json;
 use block_parsing::get_blocks_bin_response_meta;
 use block_parsing::scan_block;
 use block_parsing::scan_blocks;
 use cuprate_epee_encoding::{from_bytes, to_bytes};
-use cuprate_rpc_types::bin::{GetBlocksRequest, GetBlocksResponse, GetOutsRequest, GetOutsResponse};
+use cuprate_rpc_types::bin::{GetBlocksRequest, GetOutsRequest, GetOutsResponse};
 use cuprate_rpc_types::misc::GetOutputsOut;
 use cuprate_fixed_bytes::ByteArrayVec;
 
-use curve25519_dalek::scalar::Scalar;
 use hex::FromHex;
 use monero_wallet::address::{Network, SubaddressIndex};
 use serde::Deserialize;
 use serde_json::json;
 
-use monero_wallet::{Scanner, ViewPair};
-use core::panic;
-use std::cell::RefCell;
+use std::sync::Arc;
 use your_program::{input, input_string, output, output_string, output_error_string};
-use zeroize::Zeroizing;
 
+use crate::error::{self, AbiError};
 use crate::transaction_building::transaction::address_to_json;
-
-thread_local! {
-  static GLOBAL_VIEWPAIR: RefCell<ViewPair> = panic!("GLOBAL_VIEWPAIR is not initialized, call init_viewpair first");
-  static GLOBAL_SCANNER: RefCell<Scanner> = panic!("GLOBAL_SCANNER is not initialized, call init_viewpair first");
-  static GLOBAL_NETWORK: RefCell<Network> = panic!("GLOBAL_NETWORK is not initialized, call init_viewpair first");
-  static GLOBAL_PRIMARY_ADDRESS: RefCell<String> = panic!("GLOBAL_PRIMARY_ADDRESS is not initialized, call init_viewpair first");
-  static GLOBAL_GET_BLOCKS_BIN_RESPONSE: RefCell<Option<GetBlocksResponse>> = RefCell::new(None);
-}
+use crate::wallet::{DEFAULT_WALLET, Wallet};
 
 mod your_program {
   /// implement input & output in your program to share arrays with the monero-wallet-api
   /// rust will take care of allocation and deallocation
+  #[cfg(target_arch = "wasm32")]
   mod yours {
     extern "C" {
       pub fn input(ptr: *const u8, length: usize);
@@ -46,12 +50,17 @@ mod your_program {
       pub fn output_error(ptr: *const u8, length: usize);
     }
   }
+  /// native builds have no imports to link against, see native for the mwa_* functions
+  /// and mwa_set_io_callbacks
+  #[cfg(not(target_arch = "wasm32"))]
+  mod yours {
+    pub(super) use crate::native::{input, output, output_error};
+  }
   /// internal wrappers to handle input and output of strings
   pub fn input(length: usize) -> Vec<u8> {
-    let mut vec = Vec::with_capacity(length);
+    let mut vec = vec![0; length];
 
     unsafe {
-      vec.set_len(length);
       yours::input(vec.as_mut_ptr(), length);
     }
 
@@ -60,14 +69,9 @@ mod your_program {
   pub fn output(value: &Vec<u8>) {
     unsafe { yours::output(value.as_ptr(), value.len()) };
   }
+  /// invalid utf-8 is replaced, so it fails in the parsing of the export instead of being UB
   pub fn input_string(length: usize) -> String {
-    let mut vec = Vec::with_capacity(length);
-
-    unsafe {
-      vec.set_len(length);
-      yours::input(vec.as_ptr(), length);
-      return String::from_utf8_unchecked(vec);
-    }
+    String::from_utf8_lossy(&input(length)).into_owned()
   }
   pub fn output_string(value: &str) {
     unsafe { yours::output(value.as_ptr(), value.len()) };
@@ -76,6 +80,43 @@ mod your_program {
     unsafe { yours::output_error(value.as_ptr(), value.len()) };
   }
 }
+/// every export reports its failures this way: {"code", "message", "field"}, see error::AbiError
+fn output_failure(error: impl Into<AbiError>) {
+  output_error_string(&error.into().to_json());
+}
+fn getouts_response_error(error: impl std::fmt::Display) -> AbiError {
+  let message = format!("failed to parse getouts.bin response: {}", error);
+  AbiError::invalid_input("getouts_response", message)
+}
+fn getblocks_response_error(error: impl std::fmt::Display) -> AbiError {
+  let message = format!("failed to parse getblocks.bin response: {}", error);
+  AbiError::invalid_input("response", message)
+}
+/// the network user supplied addresses are checked against, before init_viewpair any is fine
+fn default_network() -> Option<Network> {
+  wallet::with_wallet(DEFAULT_WALLET, |wallet| wallet.network).ok()
+}
+/// the wallet_ variants of the exports that also work without a wallet: f gets the network
+/// of the wallet and its result is the output
+fn output_for_network<T: serde::Serialize>(
+  handle: u32,
+  f: impl FnOnce(Option<Network>) -> Result<T, AbiError>,
+) {
+  let network = match wallet::with_wallet(handle, |wallet| wallet.network) {
+    Ok(network) => network,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
+  match f(Some(network)) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
 /// WASM / C ABI
 #[no_mangle]
 pub extern "C" fn make_spendkey() {
@@ -84,26 +125,28 @@ pub extern "C" fn make_spendkey() {
 #[no_mangle]
 pub extern "C" fn make_spendkey_from_seed(seed_string_len: usize) {
   let seed_string = input_string(seed_string_len);
-  output_string(
-    hex::encode(
-      keypairs::make_spendkey_from_seed(<[u8; 64]>::from_hex(seed_string.as_str()).unwrap())
-        .to_bytes(),
-    )
-    .as_str(),
-  );
+  let Ok(seed) = <[u8; 64]>::from_hex(seed_string.as_str()) else {
+    output_failure(AbiError::invalid_input("seed", "seed must be 64 bytes of hex"));
+    return;
+  };
+  output_string(hex::encode(keypairs::make_spendkey_from_seed(seed).to_bytes()).as_str());
 }
 #[no_mangle]
 pub extern "C" fn make_viewkey(spend_key_string_len: usize) {
   let spend_key_string = input_string(spend_key_string_len);
-  output_string(&convert_to_json(
-    &keypairs::viewpair_from_spendkey(<[u8; 32]>::from_hex(spend_key_string.as_str()).unwrap())
-      .unwrap(),
-  ));
+  let Ok(spend_key) = <[u8; 32]>::from_hex(spend_key_string.as_str()) else {
+    output_failure(AbiError::invalid_input("spend_key", "spend_key must be 32 bytes of hex"));
+    return;
+  };
+  match keypairs::viewpair_from_spendkey(spend_key) {
+    Ok(viewpair) => output_string(&convert_to_json(&viewpair)),
+    Err(e) => output_failure(e),
+  }
 }
 #[no_mangle]
 pub extern "C" fn vk_from_entropy() {
-  let viewkey_vec = input(32);
-  let viewkey: [u8; 32] = viewkey_vec.try_into().unwrap();
+  let mut viewkey = [0; 32];
+  viewkey.copy_from_slice(&input(32));
   output_string(hex::encode(keypairs::make_viewkey(viewkey).to_bytes()).as_str());
 }
 #[no_mangle]
@@ -114,59 +157,90 @@ pub extern "C" fn init_viewpair(
 ) {
   let primary_address = input_string(primary_address_string_len);
   let secret_view_key = input_string(secret_view_key_string_len);
-  let address = if let Some(val) =
-    monero_wallet::address::MoneroAddress::from_str_with_unchecked_network(primary_address.as_str())
-      .ok()
-  {
-    val
-  } else {
-    let error_json = json!({
-        "error": "primary-address-not-valid"
-    })
-    .to_string();
-    output_error_string(&error_json);
-    return;
-  };
-  let viewpair = init_viewpair_from_viewpk_primary(&address, secret_view_key.as_str());
-  match address.network() {
-    Network::Mainnet => output_string(&json!({"network": "mainnet"}).to_string()),
-    Network::Stagenet => output_string(&json!({"network": "stagenet"}).to_string()),
-    Network::Testnet => output_string(&json!({"network": "testnet"}).to_string()),
+  match Wallet::new(&primary_address, &secret_view_key, last_subaddress_index) {
+    Ok(wallet) => {
+      output_string(&wallet.network_json());
+      wallet::set_default(wallet);
+    }
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
   }
-
-  let mut scanner = Scanner::new(viewpair.clone());
-  let mut minor = 1;
-  while minor <= last_subaddress_index {
-    // we set minor to 1 so this will never return None
-    let subaddress = SubaddressIndex::new(0, minor).unwrap();
-    scanner.register_subaddress(subaddress);
-    minor += 1;
+}
+/// handle based ABI: like init_viewpair, but the wallet is kept next to any other
+/// and the returned handle is passed to the wallet_* exports. 0 if the keys are invalid
+/// or no handles are left
+#[no_mangle]
+pub extern "C" fn wallet_init(
+  primary_address_string_len: usize,
+  secret_view_key_string_len: usize,
+  last_subaddress_index: u32,
+) -> u32 {
+  let primary_address = input_string(primary_address_string_len);
+  let secret_view_key = input_string(secret_view_key_string_len);
+  let wallet = match Wallet::new(&primary_address, &secret_view_key, last_subaddress_index) {
+    Ok(wallet) => wallet,
+    Err(e) => {
+      output_failure(e);
+      return 0;
+    }
+  };
+  let network_json = wallet.network_json();
+  match wallet::insert(wallet) {
+    Ok(handle) => {
+      output_string(&network_json);
+      handle
+    }
+    Err(e) => {
+      output_failure(e);
+      0
+    }
   }
-
-  GLOBAL_SCANNER.set(scanner);
-  GLOBAL_NETWORK.set(address.network());
-  GLOBAL_VIEWPAIR.set(viewpair);
-  GLOBAL_PRIMARY_ADDRESS.set(primary_address);
+}
+/// drops the wallet of a handle, false if the handle was unknown
+#[no_mangle]
+pub extern "C" fn wallet_free(handle: u32) -> bool {
+  wallet::free(handle)
 }
 #[no_mangle]
 pub extern "C" fn make_integrated_address(payment_id: u64) {
-  let viewpair = GLOBAL_VIEWPAIR.with_borrow(|viewpair| viewpair.clone());
-  let network = GLOBAL_NETWORK.with_borrow(|network| *network);
+  wallet_make_integrated_address(DEFAULT_WALLET, payment_id)
+}
+#[no_mangle]
+pub extern "C" fn wallet_make_integrated_address(handle: u32, payment_id: u64) {
+  let (viewpair, _, network) = match wallet::keys(handle) {
+    Ok(keys) => keys,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
 
   let bytes_back: [u8; 8] = payment_id.to_le_bytes();
   output_string(&viewpair.legacy_integrated_address(network, bytes_back).to_string());
 }
 #[no_mangle]
 pub extern "C" fn make_subaddress(major: u32, minor: u32) {
-  let viewpair = GLOBAL_VIEWPAIR.with_borrow(|viewpair| viewpair.clone());
-  let network = GLOBAL_NETWORK.with_borrow(|network| *network);
-  GLOBAL_SCANNER.with_borrow_mut(|scanner| {
-    let subaddress_index = SubaddressIndex::new(major, minor).expect("Invalid indices");
-    let subaddress = viewpair.subaddress(network, subaddress_index);
-    scanner.register_subaddress(subaddress_index.clone());
-
-    output_string(&subaddress.to_string());
-  });
+  wallet_make_subaddress(DEFAULT_WALLET, major, minor)
+}
+#[no_mangle]
+pub extern "C" fn wallet_make_subaddress(handle: u32, major: u32, minor: u32) {
+  let Some(subaddress_index) = SubaddressIndex::new(major, minor) else {
+    let message = "0/0 is the primary address, not a subaddress";
+    output_failure(AbiError::invalid_input("minor", message));
+    return;
+  };
+  match wallet::with_wallet(handle, |wallet| {
+    wallet.register_subaddress(subaddress_index);
+    wallet.viewpair().subaddress(wallet.network, subaddress_index)
+  }) {
+    Ok(subaddress) => output_string(&subaddress.to_string()),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
 }
 #[no_mangle]
 pub extern "C" fn sample_decoys(sample_json_str_len: usize) {
@@ -177,8 +251,8 @@ pub extern "C" fn sample_decoys(sample_json_str_len: usize) {
       output_string(&candidates_json.to_string());
     }
     Err(e) => {
-      println!("Error sampling decoys: {}", e);
-      return; // error handling becomes easier on the ts side if we just return nothing print the error
+      output_failure(e);
+      return;
     }
   }
 }
@@ -197,27 +271,47 @@ pub extern "C" fn make_input(output_json_len: usize, getouts_response_len: usize
           output_string(&inputs_json.to_string());
         }
         Err(e) => {
-          println!("Error making input: {}", e);
-          return; // error handling becomes easier on the ts side if we just return nothing print the error
+          output_failure(e);
+          return;
         }
       }
     }
     Err(e) => {
-      println!("Error parsing getBlocksBin response: IO error: {}", e);
-      return; // error handling becomes easier on the ts side if we just return nothing print the error
+      output_failure(getouts_response_error(e));
+      return;
     }
   }
 }
 #[no_mangle]
 pub extern "C" fn parse_address(address_string_len: usize) {
   let address_string = input_string(address_string_len);
-  match transaction_building::transaction::parse_address(&address_string) {
+  output_parsed_address(&address_string, default_network());
+}
+#[no_mangle]
+pub extern "C" fn wallet_parse_address(handle: u32, address_string_len: usize) {
+  let address_string = input_string(address_string_len);
+  match wallet::with_wallet(handle, |wallet| wallet.network) {
+    Ok(network) => output_parsed_address(&address_string, Some(network)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+fn output_parsed_address(address_string: &str, network: Option<Network>) {
+  match transaction_building::transaction::parse_address(address_string) {
     Ok(address) => {
+      if let Some(Err(e)) =
+        network.map(|network| transaction_building::transaction::check_network(&address, network))
+      {
+        output_failure(e);
+        return;
+      }
       let address_json = address_to_json(&address);
       output_string(&address_json.to_string());
     }
     Err(e) => {
-      output_error_string(json!({"error":e.to_string()}).to_string().as_str());
+      output_failure(e);
       return;
     }
   }
@@ -225,16 +319,27 @@ pub extern "C" fn parse_address(address_string_len: usize) {
 
 #[no_mangle]
 pub extern "C" fn make_transaction(json_params_len: usize) {
+  wallet_make_transaction(DEFAULT_WALLET, json_params_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_make_transaction(handle: u32, json_params_len: usize) {
   let json_params = input_string(json_params_len);
-  let viewpair = GLOBAL_VIEWPAIR.with_borrow(|viewpair| viewpair.clone());
+  let (viewpair, _, network) = match wallet::keys(handle) {
+    Ok(keys) => keys,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
 
-  match transaction_building::transaction::make_transaction(&json_params, viewpair.clone()) {
+  match transaction_building::transaction::make_transaction(&json_params, viewpair.clone(), network)
+  {
     Ok(signable_tx) => {
       let tx_json = json!({ "signable_transaction": hex::encode(signable_tx.serialize()) });
-      output_string(&tx_json.to_string());
+      output_with_change_subaddress(handle, &json_params, tx_json);
     }
     Err(e) => {
-      output_error_string(&e);
+      output_failure(e);
       return;
     }
   }
@@ -242,19 +347,146 @@ pub extern "C" fn make_transaction(json_params_len: usize) {
 
 #[no_mangle]
 pub extern "C" fn make_external_sweep_transaction(json_params_len: usize) {
+  wallet_make_external_sweep_transaction(DEFAULT_WALLET, json_params_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_make_external_sweep_transaction(handle: u32, json_params_len: usize) {
   let json_params = input_string(json_params_len);
-  let viewpair = GLOBAL_VIEWPAIR.with_borrow(|viewpair| viewpair.clone());
+  let (viewpair, _, network) = match wallet::keys(handle) {
+    Ok(keys) => keys,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
 
   match transaction_building::transaction::make_external_sweep_transaction(
     &json_params,
     viewpair.clone(),
+    network,
   ) {
     Ok(signable_tx) => {
       let tx_json = json!({ "signable_transaction": hex::encode(signable_tx.serialize()) });
-      output_string(&tx_json.to_string());
+      output_with_change_subaddress(handle, &json_params, tx_json);
     }
     Err(e) => {
-      output_error_string(&e);
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// estimated weight and fee of a transaction with the given shape, no inputs or decoys needed
+#[no_mangle]
+pub extern "C" fn estimate_fee(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match transaction_building::fee_estimation::estimate_fee(&json_params) {
+    Ok(estimate) => output_string(&convert_to_json(&estimate)),
+    Err(e) => output_failure(e),
+  }
+}
+/// picks inputs from the spendable outputs, see transaction_building::coin_selection for the strategies
+#[no_mangle]
+pub extern "C" fn select_inputs(json_params_len: usize) {
+  wallet_select_inputs(DEFAULT_WALLET, json_params_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_select_inputs(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  let (viewpair, _, network) = match wallet::keys(handle) {
+    Ok(keys) => keys,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
+
+  match transaction_building::coin_selection::select_inputs(&json_params, viewpair, network) {
+    Ok(selection) => output_string(&convert_to_json(&selection)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// sweeps every input (or every input below a threshold) to one address,
+/// split into as many transactions as the weight limit requires
+#[no_mangle]
+pub extern "C" fn make_sweep_all_transactions(json_params_len: usize) {
+  wallet_make_sweep_all_transactions(DEFAULT_WALLET, json_params_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_make_sweep_all_transactions(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  let (viewpair, _, network) = match wallet::keys(handle) {
+    Ok(keys) => keys,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
+
+  match transaction_building::sweep::make_sweep_all_transactions(&json_params, viewpair, network) {
+    Ok(plan) => {
+      output_with_change_subaddress(handle, &json_params, json!(plan));
+    }
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// splits a long payout list into transactions that stay under the output limit,
+/// every transaction gets its own inputs
+#[no_mangle]
+pub extern "C" fn make_batch_payout(json_params_len: usize) {
+  wallet_make_batch_payout(DEFAULT_WALLET, json_params_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_make_batch_payout(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  let (viewpair, _, network) = match wallet::keys(handle) {
+    Ok(keys) => keys,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
+
+  match transaction_building::batch::make_batch_payout(&json_params, viewpair, network) {
+    Ok(plan) => {
+      output_with_change_subaddress(handle, &json_params, json!(plan));
+    }
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// decodes a signable transaction so an offline signer can show what it is about to sign
+#[no_mangle]
+pub extern "C" fn inspect_signable_transaction(json_params_len: usize) {
+  wallet_inspect_signable_transaction(DEFAULT_WALLET, json_params_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_inspect_signable_transaction(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  let (viewpair, view_key, network) = match wallet::keys(handle) {
+    Ok(keys) => keys,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
+
+  match transaction_building::inspect::inspect_signable_transaction(
+    &json_params,
+    &viewpair,
+    &view_key,
+    network,
+  ) {
+    Ok(inspection) => output_string(&convert_to_json(&inspection)),
+    Err(e) => {
+      output_failure(e);
       return;
     }
   }
@@ -269,12 +501,395 @@ pub extern "C" fn sign_transaction(tx_len: usize, secret_spend_key_len: usize) {
       output_string(&tx_json.to_string());
     }
     Err(e) => {
-      println!("Error signing transaction: {}", e);
+      output_failure(e);
       return;
     }
   }
 }
 
+/// decodes a signed transaction before it is relayed.
+/// pass getouts_response_len = 0 to skip the proof verification
+#[no_mangle]
+pub extern "C" fn decode_transaction(tx_len: usize, getouts_response_len: usize) {
+  let tx_string = input_string(tx_len);
+  let ring_members = if getouts_response_len == 0 {
+    None
+  } else {
+    let getouts_response = input(getouts_response_len);
+    match from_bytes::<GetOutsResponse, _>(&mut getouts_response.as_slice()) {
+      Ok(response) => Some(response),
+      Err(e) => {
+        output_failure(getouts_response_error(e));
+        return;
+      }
+    }
+  };
+  match transaction_building::decode::decode_transaction(&tx_string, ring_members) {
+    Ok(decoded) => output_string(&convert_to_json(&decoded)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// the tx key(s) of a transaction we built, derived from its outgoing view key
+#[no_mangle]
+pub extern "C" fn get_tx_key(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match proofs::tx_key::get_tx_key(&json_params) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// how much a transaction sent to an address, given the tx key
+#[no_mangle]
+pub extern "C" fn check_tx_key(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match proofs::tx_key::check_tx_key(&json_params, default_network()) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn wallet_check_tx_key(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  output_for_network(handle, |network| proofs::tx_key::check_tx_key(&json_params, network));
+}
+/// OutProofV2: proves we paid an address in a transaction
+#[no_mangle]
+pub extern "C" fn get_out_proof(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match proofs::out_proof::get_out_proof(&json_params, default_network()) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn wallet_get_out_proof(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  output_for_network(handle, |network| proofs::out_proof::get_out_proof(&json_params, network));
+}
+#[no_mangle]
+pub extern "C" fn check_out_proof(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match proofs::out_proof::check_out_proof(&json_params, default_network()) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn wallet_check_out_proof(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  output_for_network(handle, |network| proofs::out_proof::check_out_proof(&json_params, network));
+}
+#[no_mangle]
+pub extern "C" fn get_in_proof(json_params_len: usize) {
+  wallet_get_in_proof(DEFAULT_WALLET, json_params_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_get_in_proof(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  let (_, view_key, network) = match wallet::keys(handle) {
+    Ok(keys) => keys,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
+  match proofs::in_proof::get_in_proof(&json_params, &view_key, network) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn check_in_proof(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match proofs::in_proof::check_in_proof(&json_params, default_network()) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn wallet_check_in_proof(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  output_for_network(handle, |network| proofs::in_proof::check_in_proof(&json_params, network));
+}
+/// ReserveProofV2: proves the wallet owns (and had not spent) the given outputs
+#[no_mangle]
+pub extern "C" fn get_reserve_proof(json_params_len: usize) {
+  wallet_get_reserve_proof(DEFAULT_WALLET, json_params_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_get_reserve_proof(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  let (viewpair, view_key, _) = match wallet::keys(handle) {
+    Ok(keys) => keys,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
+  match proofs::reserve_proof::get_reserve_proof(&json_params, &viewpair, &view_key) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// lists the transactions and key images check_reserve_proof needs
+#[no_mangle]
+pub extern "C" fn parse_reserve_proof(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match proofs::reserve_proof::parse_reserve_proof(&json_params) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn check_reserve_proof(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match proofs::reserve_proof::check_reserve_proof(&json_params, default_network()) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn wallet_check_reserve_proof(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  output_for_network(handle, |network| {
+    proofs::reserve_proof::check_reserve_proof(&json_params, network)
+  });
+}
+/// SpendProofV1: proves we sent a transaction, ring members as for decode_transaction
+#[no_mangle]
+pub extern "C" fn get_spend_proof(
+  json_params_len: usize,
+  secret_spend_key_len: usize,
+  getouts_response_len: usize,
+) {
+  let json_params = input_string(json_params_len);
+  let secret_spend_key_string = input_string(secret_spend_key_len);
+  let getouts_response = input(getouts_response_len);
+  let ring_members = match from_bytes::<GetOutsResponse, _>(&mut getouts_response.as_slice()) {
+    Ok(response) => response,
+    Err(e) => {
+      output_failure(getouts_response_error(e));
+      return;
+    }
+  };
+  match proofs::spend_proof::get_spend_proof(&json_params, &secret_spend_key_string, ring_members)
+  {
+    Ok(spend_proof) => output_string(&json!({ "spend_proof": spend_proof }).to_string()),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn check_spend_proof(json_params_len: usize, getouts_response_len: usize) {
+  let json_params = input_string(json_params_len);
+  let getouts_response = input(getouts_response_len);
+  let ring_members = match from_bytes::<GetOutsResponse, _>(&mut getouts_response.as_slice()) {
+    Ok(response) => response,
+    Err(e) => {
+      output_failure(getouts_response_error(e));
+      return;
+    }
+  };
+  match proofs::spend_proof::check_spend_proof(&json_params, ring_members) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// SigV2 message signature with the spend or view key, as wallet2's sign_message
+#[no_mangle]
+pub extern "C" fn sign_message(json_params_len: usize) {
+  wallet_sign_message(DEFAULT_WALLET, json_params_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_sign_message(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  let (viewpair, view_key, network) = match wallet::keys(handle) {
+    Ok(keys) => keys,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
+  match proofs::message::sign_message(&json_params, &viewpair, &view_key, network) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn verify_message(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match proofs::message::verify_message(&json_params, default_network()) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn wallet_verify_message(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  output_for_network(handle, |network| proofs::message::verify_message(&json_params, network));
+}
+/// parses a monero: payment uri, the addresses have to be on the network of the wallet
+#[no_mangle]
+pub extern "C" fn parse_payment_uri(uri_len: usize) {
+  wallet_parse_payment_uri(DEFAULT_WALLET, uri_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_parse_payment_uri(handle: u32, uri_len: usize) {
+  let uri = input_string(uri_len);
+  let network = match wallet::with_wallet(handle, |wallet| wallet.network) {
+    Ok(network) => network,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
+  match uri::parse_uri(&uri, network) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn make_payment_uri(json_params_len: usize) {
+  wallet_make_payment_uri(DEFAULT_WALLET, json_params_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_make_payment_uri(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  let network = match wallet::with_wallet(handle, |wallet| wallet.network) {
+    Ok(network) => network,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
+  match uri::make_uri(&json_params, network) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// the dns name whose TXT records the host has to resolve for an OpenAlias
+#[no_mangle]
+pub extern "C" fn openalias_query_name(alias_len: usize) {
+  let alias = input_string(alias_len);
+  match openalias::openalias_query_name(&alias) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn parse_openalias(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match openalias::parse_openalias(&json_params, default_network()) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn wallet_parse_openalias(handle: u32, json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  output_for_network(handle, |network| openalias::parse_openalias(&json_params, network));
+}
+/// is this address ours: primary, integrated (with payment id), subaddress (with index) or not ours
+#[no_mangle]
+pub extern "C" fn address_ownership(address_len: usize) {
+  wallet_address_ownership(DEFAULT_WALLET, address_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_address_ownership(handle: u32, address_len: usize) {
+  let address = input_string(address_len);
+  let ownership = wallet::with_wallet(handle, |wallet| {
+    keypairs::ownership::address_ownership(
+      &address,
+      &wallet.viewpair(),
+      &wallet.view_key,
+      wallet.network,
+      &wallet.subaddresses,
+    )
+  });
+  match ownership.and_then(|ownership| ownership) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// exact XMR (or millinero, micronero) decimal string to piconero
+#[no_mangle]
+pub extern "C" fn parse_amount(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match amount::parse_amount_json(&json_params) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+#[no_mangle]
+pub extern "C" fn format_amount(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match amount::format_amount_json(&json_params) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
 #[no_mangle]
 pub extern "C" fn compute_key_image(output_hex_string_len: usize, sender_spend_key_len: usize) {
   let output_hex_string = input_string(output_hex_string_len);
@@ -285,7 +900,7 @@ pub extern "C" fn compute_key_image(output_hex_string_len: usize, sender_spend_k
       output_string(&key_image_json.to_string());
     }
     Err(e) => {
-      println!("Error computing key image: {}", e);
+      output_failure(e);
       return;
     }
   }
@@ -307,11 +922,10 @@ pub extern "C" fn build_getblocksbin_request(json_params_len: usize) {
   let params: GetBlocksBinParams = match serde_json::from_str(&json_params) {
     Ok(p) => p,
     Err(e) => {
-      output_error_string(
-        json!({"message":"failed to parse getblocksbinrequest params","error":e.to_string()})
-          .to_string()
-          .as_str(),
-      );
+      output_failure(AbiError::invalid_input(
+        "json_params",
+        format!("failed to parse getblocksbinrequest params: {}", e),
+      ));
       return;
     }
   };
@@ -333,13 +947,18 @@ pub extern "C" fn build_getblocksbin_request(json_params_len: usize) {
     req_params.pool_info_since = val;
   }
   if let Some(val) = params.block_ids {
-    let ids: Vec<[u8; 32]> = val
-      .iter()
-      .map(|hex| hex::decode(hex).ok().and_then(|bytes| bytes.try_into().ok()).unwrap())
-      .collect();
+    let ids: Option<Vec<[u8; 32]>> =
+      val.iter().map(|hex| <[u8; 32]>::from_hex(hex).ok()).collect();
+    let Some(ids) = ids else {
+      output_failure(AbiError::invalid_input("block_ids", "block ids must be 32 bytes of hex"));
+      return;
+    };
     req_params.block_ids = ByteArrayVec::from(ids);
   }
-  output(to_bytes(req_params).unwrap().to_vec().as_ref());
+  match to_bytes(req_params) {
+    Ok(request) => output(request.to_vec().as_ref()),
+    Err(e) => output_failure(AbiError::new(error::FAILED, e.to_string())),
+  }
 }
 
 #[no_mangle]
@@ -348,11 +967,11 @@ pub extern "C" fn build_getoutsbin_request(outputs_array_len: usize) {
   let output_indices: Vec<u64> = match serde_json::from_str(&output_indices_array) {
     Ok(v) => v,
     Err(e) => {
-      println!(
-        "build_getoutsbin_request: Failed to parse outputs array as JSON array of u64: {}",
-        e
-      );
-      return; // we don't output anything in this error case
+      output_failure(AbiError::invalid_input(
+        "outputs_array",
+        format!("failed to parse outputs array as JSON array of u64: {}", e),
+      ));
+      return;
     }
   };
 
@@ -361,7 +980,35 @@ pub extern "C" fn build_getoutsbin_request(outputs_array_len: usize) {
     output_indices.into_iter().map(|idx| GetOutputsOut { amount: 0, index: idx }).collect();
   req_params.outputs = outputs_vec;
   req_params.get_txid = true;
-  output(to_bytes(req_params).unwrap().to_vec().as_ref()); // in the success case we output the request
+  match to_bytes(req_params) {
+    Ok(request) => output(request.to_vec().as_ref()),
+    Err(e) => output_failure(AbiError::new(error::FAILED, e.to_string())),
+  }
+}
+
+/// outputs the json body for POST /send_raw_transaction
+#[no_mangle]
+pub extern "C" fn build_send_raw_transaction_request(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match relay::build_send_raw_transaction_request(&json_params) {
+    Ok(request) => output_string(&request),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+}
+/// outputs {"relayed", "untrusted"} or an error with a stable code (see relay::RelayError)
+#[no_mangle]
+pub extern "C" fn parse_send_raw_transaction_response(response_len: usize) {
+  let response = input_string(response_len);
+  match relay::parse_send_raw_transaction_response(&response) {
+    Ok(result) => output_string(&convert_to_json(&result)),
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
 }
 
 #[no_mangle]
@@ -372,101 +1019,195 @@ pub extern "C" fn convert_get_outs_bin_response_to_json(response_len: usize) {
     Ok(outs_response) => {
       output_string(&convert_to_json(&outs_response));
     }
-    Err(error) => {
-      let error_message = format!("Error parsing getouts.bin response: {}", error);
-      let error_json = json!({
-          "error": error_message
-      })
-      .to_string();
-      output_string(&error_json);
-    }
+    Err(error) => output_failure(getouts_response_error(error).with_field("response")),
   }
 }
 
 #[no_mangle]
 pub extern "C" fn scan_blocks_with_get_blocks_bin(response_len: usize) {
+  wallet_scan_blocks_with_get_blocks_bin(DEFAULT_WALLET, response_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_scan_blocks_with_get_blocks_bin(handle: u32, response_len: usize) {
   let response = input(response_len);
+  let wallet = wallet::with_wallet(handle, |wallet| {
+    (wallet.scanner.clone(), wallet.view_key.clone(), wallet.primary_address.clone())
+  });
+  let (scanner, view_key, primary_address) = match wallet {
+    Ok(wallet) => wallet,
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  };
 
   match from_bytes(&mut response.as_slice()) {
-    Ok(blocks_response) => GLOBAL_SCANNER.with_borrow(|scanner| {
-      let primary_address =
-        GLOBAL_PRIMARY_ADDRESS.with_borrow(|primary_address| primary_address.clone());
-      output_string(&convert_to_json(&get_blocks_bin_response_meta(&blocks_response)));
-      output_string(&scan_blocks(scanner.clone(), &primary_address, blocks_response));
-    }),
-    Err(error) => {
-      let error_message = format!("Error parsing getBlocksBin response: {}", error);
-      let error_json = json!({
-          "error": error_message
-      })
-      .to_string();
-      output_string(&error_json);
+    Ok(blocks_response) => {
+      let meta = get_blocks_bin_response_meta(&blocks_response);
+      // scan first so the meta can report the scan stats, the meta is still output first
+      let (scan_result, scan_stats) =
+        scan_blocks(scanner, &view_key, &primary_address, blocks_response);
+      match scan_result {
+        Ok(scan_result) => {
+          output_string(&convert_to_json(&meta.with_scan_stats(scan_stats)));
+          output_string(&scan_result);
+        }
+        Err(e) => output_failure(AbiError::invalid_input("response", e)),
+      }
     }
+    Err(error) => output_failure(getblocks_response_error(error)),
   }
 }
 #[no_mangle]
 pub extern "C" fn load_get_blocks_bin_response(response_len: usize) {
+  wallet_load_get_blocks_bin_response(DEFAULT_WALLET, response_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_load_get_blocks_bin_response(handle: u32, response_len: usize) {
   let response = input(response_len);
 
   match from_bytes(&mut response.as_slice()) {
     Ok(blocks_response) => {
       let meta = get_blocks_bin_response_meta(&blocks_response);
-      GLOBAL_GET_BLOCKS_BIN_RESPONSE.set(Some(blocks_response));
+      let blocks_response = Some(Arc::new(blocks_response));
+      if let Err(e) =
+        wallet::with_wallet(handle, |wallet| wallet.get_blocks_bin_response = blocks_response)
+      {
+        output_failure(e);
+        return;
+      }
       output_string(&convert_to_json(&meta));
     }
-    Err(error) => {
-      let error_message = format!("Error parsing getBlocksBin response: {}", error);
-      let error_json = json!({
-          "error": error_message
-      })
-      .to_string();
-      output_string(&error_json);
-    }
+    Err(error) => output_failure(getblocks_response_error(error)),
   }
 }
 #[no_mangle]
 pub extern "C" fn get_blocks_bin_scan_one_block(block_index: u32) {
-  let scanner = GLOBAL_SCANNER.with_borrow(|scanner| scanner.clone());
-  let primary_address =
-    GLOBAL_PRIMARY_ADDRESS.with_borrow(|primary_address| primary_address.clone());
-  let result = GLOBAL_GET_BLOCKS_BIN_RESPONSE.with_borrow(|response| match response {
+  wallet_get_blocks_bin_scan_one_block(DEFAULT_WALLET, block_index)
+}
+#[no_mangle]
+pub extern "C" fn wallet_get_blocks_bin_scan_one_block(handle: u32, block_index: u32) {
+  let wallet = wallet::with_wallet(handle, |wallet| {
+    (
+      wallet.scanner.clone(),
+      wallet.view_key.clone(),
+      wallet.primary_address.clone(),
+      wallet.get_blocks_bin_response.clone(),
+    )
+  });
+  let result = wallet.and_then(|(scanner, view_key, primary_address, response)| match response {
     Some(ref get_blocks_bin) => {
       if (block_index as usize) >= get_blocks_bin.blocks.len() {
-        return Err(format!(
-          "Block index {} out of bounds (total blocks: {})",
+        let message = format!(
+          "block index {} out of bounds (total blocks: {})",
           block_index,
           get_blocks_bin.blocks.len()
-        ));
+        );
+        return Err(AbiError::invalid_input("block_index", message));
       }
-      match scan_block(&scanner, &primary_address, get_blocks_bin, block_index as usize) {
-        Ok((output_jsons, input_images_jsons)) => {
-          let result_json = json!({"outputs": output_jsons, "all_key_images": input_images_jsons});
+      match scan_block(&scanner, &view_key, &primary_address, get_blocks_bin, block_index as usize)
+      {
+        Ok((output_jsons, input_images_jsons, scan_stats)) => {
+          let result_json = json!({"outputs": output_jsons, "all_key_images": input_images_jsons, "scan_stats": scan_stats});
           Ok(convert_to_json(&result_json))
         }
-        Err(error_string) => Err(error_string),
+        Err(error_string) => Err(AbiError::from(error_string)),
       }
     }
-    None => {
-      Err("No getBlocks.bin response loaded. Call loadGetBlocksBinResponse first.".to_string())
-    }
+    None => Err(AbiError::new(
+      error::FAILED,
+      "no getblocks.bin response loaded, call load_get_blocks_bin_response first",
+    )),
   });
   match result {
     Ok(json) => output_string(&json),
-    Err(error_msg) => {
-      let error_json = json!({"error": error_msg}).to_string();
-      output_string(&error_json);
-    }
-  }
-}
-///rust API
-pub fn init_viewpair_from_viewpk_primary(
-  address: &monero_wallet::address::MoneroAddress,
-  secret_view_key: &str,
-) -> ViewPair {
-  let view_key_bytes = <[u8; 32]>::from_hex(secret_view_key).unwrap();
-  monero_wallet::ViewPair::new(
-    address.spend(),
-    Zeroizing::new(Scalar::from_canonical_bytes(view_key_bytes).unwrap()),
-  )
-  .unwrap()
+    Err(e) => output_failure(e),
+  }
+}
+/// merges a scan result into the _cache.json contents, the host only reads and writes the file
+/// outputs the new cache file contents, then the changed outputs as json
+#[no_mangle]
+pub extern "C" fn scan_cache_append(
+  cache_len: usize,
+  scan_result_len: usize,
+  params_json_len: usize,
+) {
+  wallet_scan_cache_append(DEFAULT_WALLET, cache_len, scan_result_len, params_json_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_scan_cache_append(
+  handle: u32,
+  cache_len: usize,
+  scan_result_len: usize,
+  params_json_len: usize,
+) {
+  let cache_bytes = input(cache_len);
+  let scan_result = input(scan_result_len);
+  let params_json = input_string(params_json_len);
+
+  let result = (|| {
+    let primary_address = wallet::with_wallet(handle, |wallet| wallet.primary_address.clone())?;
+    let params: scan_cache::AppendParams = if params_json.is_empty() {
+      Default::default()
+    } else {
+      serde_json::from_str(&params_json).map_err(|e| {
+        let message = format!("failed to parse scan cache append params: {}", e);
+        AbiError::invalid_input("params_json", message)
+      })?
+    };
+    let mut cache = scan_cache::read_scan_cache(&cache_bytes, &primary_address)?;
+    let changed_outputs = cache.append(&scan_result, &params)?;
+    Ok::<_, AbiError>((scan_cache::write_scan_cache(&cache)?, changed_outputs))
+  })();
+  match result {
+    Ok((cache_bytes, changed_outputs)) => {
+      output(&cache_bytes);
+      output_string(&json!({ "changed_outputs": changed_outputs }).to_string());
+    }
+    Err(e) => output_failure(e),
+  }
+}
+#[no_mangle]
+pub extern "C" fn scan_cache_compact(cache_len: usize) {
+  wallet_scan_cache_compact(DEFAULT_WALLET, cache_len)
+}
+#[no_mangle]
+pub extern "C" fn wallet_scan_cache_compact(handle: u32, cache_len: usize) {
+  let cache_bytes = input(cache_len);
+
+  match wallet::with_wallet(handle, |wallet| wallet.primary_address.clone())
+    .and_then(|primary_address| {
+      let mut cache = scan_cache::read_scan_cache(&cache_bytes, &primary_address)?;
+      cache.compact();
+      Ok(scan_cache::write_scan_cache(&cache)?)
+    }) {
+    Ok(cache_bytes) => output(&cache_bytes),
+    Err(e) => output_failure(e),
+  }
+}
+/// locked and unlocked balances per account and subaddress, computed from the found outputs
+#[no_mangle]
+pub extern "C" fn compute_balances(json_params_len: usize) {
+  let json_params = input_string(json_params_len);
+  match balance::compute_balances(&json_params) {
+    Ok(balances) => output_string(&convert_to_json(&balances)),
+    Err(e) => output_failure(e),
+  }
+}
+/// change sent to a subaddress has to be found by the scanner: it is registered with the wallet
+/// and returned as change_subaddress_index, so the host can store it and register it again with
+/// make_subaddress after the next init
+fn output_with_change_subaddress(handle: u32, json_params: &str, mut result: serde_json::Value) {
+  match wallet::with_wallet(handle, |wallet| wallet.register_change_subaddress(json_params)) {
+    Ok(Some(index)) => {
+      let index = json!({ "major": index.account(), "minor": index.address() });
+      result["change_subaddress_index"] = index;
+    }
+    Ok(None) => {}
+    Err(e) => {
+      output_failure(e);
+      return;
+    }
+  }
+  output_string(&result.to_string());
 }
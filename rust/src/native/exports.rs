@@ -0,0 +1,699 @@
+//! one mwa_* function per export, see the export of the same name in lib.rs for the json
+//! params and outputs. the wallet_* exports work on handles of mwa_wallet_init, the
+//! exports of the default wallet of init_viewpair have no native counterpart
+#![allow(clippy::missing_safety_doc)] // see the safety section of native
+
+use super::{call, MwaResult};
+use crate::error::AbiError;
+
+/// like wallet_init, the handle is written to handle (0 if the keys are invalid or no handles
+/// are left)
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_init(
+  primary_address: *const u8,
+  primary_address_len: usize,
+  secret_view_key: *const u8,
+  secret_view_key_len: usize,
+  last_subaddress_index: u32,
+  handle: *mut u32,
+) -> *mut MwaResult {
+  let inputs = [
+    ("primary_address", primary_address, primary_address_len),
+    ("secret_view_key", secret_view_key, secret_view_key_len),
+  ];
+  unsafe {
+    call(&inputs, |len| {
+      let new_handle = crate::wallet_init(len[0], len[1], last_subaddress_index);
+      if let Some(handle) = handle.as_mut() {
+        *handle = new_handle;
+      }
+    })
+  }
+}
+#[no_mangle]
+pub extern "C" fn mwa_wallet_free(handle: u32) -> bool {
+  crate::wallet_free(handle)
+}
+/// entropy has to be 32 bytes
+#[no_mangle]
+pub unsafe extern "C" fn mwa_vk_from_entropy(
+  entropy: *const u8,
+  entropy_len: usize,
+) -> *mut MwaResult {
+  if entropy_len != 32 {
+    let error = AbiError::invalid_input("entropy", "entropy must be 32 bytes");
+    return Box::into_raw(Box::new(MwaResult::failure(error)));
+  }
+  unsafe { call(&[("entropy", entropy, entropy_len)], |_| crate::vk_from_entropy()) }
+}
+/// a new random secret spend key as hex
+#[no_mangle]
+pub unsafe extern "C" fn mwa_make_spendkey() -> *mut MwaResult {
+  unsafe { call(&[], |_| crate::make_spendkey()) }
+}
+/// the secret spend key of a 64 byte hex seed
+#[no_mangle]
+pub unsafe extern "C" fn mwa_make_spendkey_from_seed(
+  seed: *const u8,
+  seed_len: usize,
+) -> *mut MwaResult {
+  unsafe { call(&[("seed", seed, seed_len)], |len| crate::make_spendkey_from_seed(len[0])) }
+}
+/// the view key and primary addresses of a hex secret spend key
+#[no_mangle]
+pub unsafe extern "C" fn mwa_make_viewkey(
+  spend_key: *const u8,
+  spend_key_len: usize,
+) -> *mut MwaResult {
+  unsafe { call(&[("spend_key", spend_key, spend_key_len)], |len| crate::make_viewkey(len[0])) }
+}
+/// see make_input, getouts_response is a get_outs.bin response
+#[no_mangle]
+pub unsafe extern "C" fn mwa_make_input(
+  output_json: *const u8,
+  output_json_len: usize,
+  getouts_response: *const u8,
+  getouts_response_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(
+      &[
+        ("output_json", output_json, output_json_len),
+        ("getouts_response", getouts_response, getouts_response_len),
+      ],
+      |len| crate::make_input(len[0], len[1]),
+    )
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_sample_decoys(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::sample_decoys(len[0]))
+  }
+}
+/// parses an address of any network
+#[no_mangle]
+pub unsafe extern "C" fn mwa_parse_address(
+  address: *const u8,
+  address_len: usize,
+) -> *mut MwaResult {
+  unsafe { call(&[("address", address, address_len)], |len| crate::parse_address(len[0])) }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_estimate_fee(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::estimate_fee(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_sign_transaction(
+  signable_transaction: *const u8,
+  signable_transaction_len: usize,
+  secret_spend_key: *const u8,
+  secret_spend_key_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(
+      &[
+        ("signable_transaction", signable_transaction, signable_transaction_len),
+        ("secret_spend_key", secret_spend_key, secret_spend_key_len),
+      ],
+      |len| crate::sign_transaction(len[0], len[1]),
+    )
+  }
+}
+/// getouts_response may be empty
+#[no_mangle]
+pub unsafe extern "C" fn mwa_decode_transaction(
+  transaction: *const u8,
+  transaction_len: usize,
+  getouts_response: *const u8,
+  getouts_response_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(
+      &[
+        ("transaction", transaction, transaction_len),
+        ("getouts_response", getouts_response, getouts_response_len),
+      ],
+      |len| crate::decode_transaction(len[0], len[1]),
+    )
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_get_tx_key(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe { call(&[("json_params", json_params, json_params_len)], |len| crate::get_tx_key(len[0])) }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_check_tx_key(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::check_tx_key(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_get_out_proof(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::get_out_proof(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_check_out_proof(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::check_out_proof(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_check_in_proof(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::check_in_proof(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_parse_reserve_proof(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::parse_reserve_proof(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_check_reserve_proof(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::check_reserve_proof(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_get_spend_proof(
+  json_params: *const u8,
+  json_params_len: usize,
+  secret_spend_key: *const u8,
+  secret_spend_key_len: usize,
+  getouts_response: *const u8,
+  getouts_response_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(
+      &[
+        ("json_params", json_params, json_params_len),
+        ("secret_spend_key", secret_spend_key, secret_spend_key_len),
+        ("getouts_response", getouts_response, getouts_response_len),
+      ],
+      |len| crate::get_spend_proof(len[0], len[1], len[2]),
+    )
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_check_spend_proof(
+  json_params: *const u8,
+  json_params_len: usize,
+  getouts_response: *const u8,
+  getouts_response_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(
+      &[
+        ("json_params", json_params, json_params_len),
+        ("getouts_response", getouts_response, getouts_response_len),
+      ],
+      |len| crate::check_spend_proof(len[0], len[1]),
+    )
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_verify_message(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::verify_message(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_parse_payment_uri(uri: *const u8, uri_len: usize) -> *mut MwaResult {
+  unsafe { call(&[("uri", uri, uri_len)], |len| crate::parse_payment_uri(len[0])) }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_make_payment_uri(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::make_payment_uri(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_openalias_query_name(
+  alias: *const u8,
+  alias_len: usize,
+) -> *mut MwaResult {
+  unsafe { call(&[("alias", alias, alias_len)], |len| crate::openalias_query_name(len[0])) }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_parse_openalias(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::parse_openalias(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_parse_amount(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::parse_amount(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_format_amount(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::format_amount(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_compute_key_image(
+  output: *const u8,
+  output_len: usize,
+  sender_spend_key: *const u8,
+  sender_spend_key_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(
+      &[
+        ("output", output, output_len),
+        ("sender_spend_key", sender_spend_key, sender_spend_key_len),
+      ],
+      |len| crate::compute_key_image(len[0], len[1]),
+    )
+  }
+}
+/// outputs the binary request body
+#[no_mangle]
+pub unsafe extern "C" fn mwa_build_getblocksbin_request(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::build_getblocksbin_request(len[0])
+    })
+  }
+}
+/// outputs the binary request body
+#[no_mangle]
+pub unsafe extern "C" fn mwa_build_getoutsbin_request(
+  outputs_array: *const u8,
+  outputs_array_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("outputs_array", outputs_array, outputs_array_len)], |len| {
+      crate::build_getoutsbin_request(len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_build_send_raw_transaction_request(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::build_send_raw_transaction_request(len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_parse_send_raw_transaction_response(
+  response: *const u8,
+  response_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("response", response, response_len)], |len| {
+      crate::parse_send_raw_transaction_response(len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_convert_get_outs_bin_response_to_json(
+  response: *const u8,
+  response_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("response", response, response_len)], |len| {
+      crate::convert_get_outs_bin_response_to_json(len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_compute_balances(
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| crate::compute_balances(len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_make_integrated_address(
+  handle: u32,
+  payment_id: u64,
+) -> *mut MwaResult {
+  unsafe { call(&[], |_| crate::wallet_make_integrated_address(handle, payment_id)) }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_make_subaddress(
+  handle: u32,
+  major: u32,
+  minor: u32,
+) -> *mut MwaResult {
+  unsafe { call(&[], |_| crate::wallet_make_subaddress(handle, major, minor)) }
+}
+/// parses an address, other networks than the wallet's are an error
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_parse_address(
+  handle: u32,
+  address: *const u8,
+  address_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("address", address, address_len)], |len| crate::wallet_parse_address(handle, len[0]))
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_make_transaction(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_make_transaction(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_make_external_sweep_transaction(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_make_external_sweep_transaction(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_select_inputs(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_select_inputs(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_make_sweep_all_transactions(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_make_sweep_all_transactions(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_make_batch_payout(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_make_batch_payout(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_inspect_signable_transaction(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_inspect_signable_transaction(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_get_in_proof(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_get_in_proof(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_get_reserve_proof(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_get_reserve_proof(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_sign_message(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_sign_message(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_check_tx_key(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_check_tx_key(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_get_out_proof(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_get_out_proof(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_check_out_proof(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_check_out_proof(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_check_in_proof(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_check_in_proof(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_check_reserve_proof(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_check_reserve_proof(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_verify_message(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_verify_message(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_parse_payment_uri(
+  handle: u32,
+  uri: *const u8,
+  uri_len: usize,
+) -> *mut MwaResult {
+  unsafe { call(&[("uri", uri, uri_len)], |len| crate::wallet_parse_payment_uri(handle, len[0])) }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_make_payment_uri(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_make_payment_uri(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_parse_openalias(
+  handle: u32,
+  json_params: *const u8,
+  json_params_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("json_params", json_params, json_params_len)], |len| {
+      crate::wallet_parse_openalias(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_address_ownership(
+  handle: u32,
+  address: *const u8,
+  address_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("address", address, address_len)], |len| {
+      crate::wallet_address_ownership(handle, len[0])
+    })
+  }
+}
+/// outputs the block meta, then the scan result
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_scan_blocks_with_get_blocks_bin(
+  handle: u32,
+  response: *const u8,
+  response_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("response", response, response_len)], |len| {
+      crate::wallet_scan_blocks_with_get_blocks_bin(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_load_get_blocks_bin_response(
+  handle: u32,
+  response: *const u8,
+  response_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("response", response, response_len)], |len| {
+      crate::wallet_load_get_blocks_bin_response(handle, len[0])
+    })
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_get_blocks_bin_scan_one_block(
+  handle: u32,
+  block_index: u32,
+) -> *mut MwaResult {
+  unsafe { call(&[], |_| crate::wallet_get_blocks_bin_scan_one_block(handle, block_index)) }
+}
+/// outputs the new cache file contents, then the changed outputs
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_scan_cache_append(
+  handle: u32,
+  cache: *const u8,
+  cache_len: usize,
+  scan_result: *const u8,
+  scan_result_len: usize,
+  params_json: *const u8,
+  params_json_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(
+      &[
+        ("cache", cache, cache_len),
+        ("scan_result", scan_result, scan_result_len),
+        ("params_json", params_json, params_json_len),
+      ],
+      |len| crate::wallet_scan_cache_append(handle, len[0], len[1], len[2]),
+    )
+  }
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_wallet_scan_cache_compact(
+  handle: u32,
+  cache: *const u8,
+  cache_len: usize,
+) -> *mut MwaResult {
+  unsafe {
+    call(&[("cache", cache, cache_len)], |len| crate::wallet_scan_cache_compact(handle, len[0]))
+  }
+}
@@ -0,0 +1,222 @@
+//! native C API: the mwa_* functions take (ptr, len) inputs and return an MwaResult that
+//! holds what the export output, so a host doesn't have to implement the
+//! input/output/output_error callbacks of the wasm ABI. include/monero_wallet_api.h is
+//! generated from it with cbindgen (make -C tests/c header)
+//!
+//! # Safety
+//! every pointer passed with a length has to point to that many readable bytes, NULL is
+//! only fine with a length of 0. every MwaResult has to be freed once with mwa_result_free
+#![allow(clippy::missing_safety_doc)] // see the safety section above
+
+pub mod exports;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::error::AbiError;
+
+/// the outputs of one call. every output (and the error) is followed by a NUL byte that
+/// its length doesn't count, so json outputs can be used as C strings
+#[derive(Default)]
+pub struct MwaResult {
+  outputs: Vec<Vec<u8>>,
+  error: Option<Vec<u8>>,
+}
+impl MwaResult {
+  fn failure(error: AbiError) -> MwaResult {
+    MwaResult { outputs: vec![], error: Some(nul_terminated(error.to_json().as_bytes())) }
+  }
+}
+fn nul_terminated(bytes: &[u8]) -> Vec<u8> {
+  let mut terminated = Vec::with_capacity(bytes.len() + 1);
+  terminated.extend_from_slice(bytes);
+  terminated.push(0);
+  terminated
+}
+
+/// the inputs of the running mwa_* call and what it output so far
+struct Capture {
+  inputs: VecDeque<Vec<u8>>,
+  result: MwaResult,
+}
+thread_local! {
+  static CAPTURE: RefCell<Option<Capture>> = const { RefCell::new(None) };
+}
+
+pub type MwaInputCallback = extern "C" fn(*mut u8, usize);
+pub type MwaOutputCallback = extern "C" fn(*const u8, usize);
+#[derive(Clone, Copy)]
+struct IoCallbacks {
+  input: Option<MwaInputCallback>,
+  output: Option<MwaOutputCallback>,
+  output_error: Option<MwaOutputCallback>,
+}
+static CALLBACKS: Mutex<IoCallbacks> =
+  Mutex::new(IoCallbacks { input: None, output: None, output_error: None });
+
+/// copied out, so a callback can call back into the library without a deadlock
+fn callbacks() -> IoCallbacks {
+  *CALLBACKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// the host side of the ABI in native builds: the running mwa_* call if there is one,
+/// the callbacks of mwa_set_io_callbacks otherwise
+pub(crate) unsafe fn input(ptr: *mut u8, length: usize) {
+  let captured = CAPTURE.with(|capture| {
+    capture.borrow_mut().as_mut().map(|capture| capture.inputs.pop_front().unwrap_or_default())
+  });
+  match captured {
+    // the buffer is zeroed, a shorter input leaves the rest as it is
+    Some(bytes) => unsafe {
+      std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len().min(length))
+    },
+    None => {
+      if let Some(input) = callbacks().input {
+        input(ptr, length)
+      }
+    }
+  }
+}
+pub(crate) unsafe fn output(ptr: *const u8, length: usize) {
+  let bytes = unsafe { std::slice::from_raw_parts(ptr, length) };
+  let captured = CAPTURE.with(|capture| match capture.borrow_mut().as_mut() {
+    Some(capture) => {
+      capture.result.outputs.push(nul_terminated(bytes));
+      true
+    }
+    None => false,
+  });
+  if let (false, Some(output)) = (captured, callbacks().output) {
+    output(ptr, length)
+  }
+}
+pub(crate) unsafe fn output_error(ptr: *const u8, length: usize) {
+  let bytes = unsafe { std::slice::from_raw_parts(ptr, length) };
+  let captured = CAPTURE.with(|capture| match capture.borrow_mut().as_mut() {
+    Some(capture) => {
+      capture.result.error = Some(nul_terminated(bytes));
+      true
+    }
+    None => false,
+  });
+  if let (false, Some(output_error)) = (captured, callbacks().output_error) {
+    output_error(ptr, length)
+  }
+}
+
+unsafe fn slice<'a>(ptr: *const u8, length: usize) -> Option<&'a [u8]> {
+  if length == 0 {
+    return Some(&[]);
+  }
+  if ptr.is_null() {
+    return None;
+  }
+  Some(unsafe { std::slice::from_raw_parts(ptr, length) })
+}
+
+/// runs an export with the (name, ptr, len) inputs queued, collects what it outputs
+unsafe fn call(
+  inputs: &[(&str, *const u8, usize)],
+  export: impl FnOnce(&[usize]),
+) -> *mut MwaResult {
+  let mut queued = VecDeque::with_capacity(inputs.len());
+  for &(name, ptr, length) in inputs {
+    match unsafe { slice(ptr, length) } {
+      Some(bytes) => queued.push_back(bytes.to_vec()),
+      None => {
+        let error = AbiError::invalid_input(name, format!("{} is NULL", name));
+        return Box::into_raw(Box::new(MwaResult::failure(error)));
+      }
+    }
+  }
+  let lengths: Vec<usize> = queued.iter().map(Vec::len).collect();
+  CAPTURE.with(|capture| {
+    *capture.borrow_mut() = Some(Capture { inputs: queued, result: MwaResult::default() })
+  });
+  export(&lengths);
+  let result = CAPTURE.with(|capture| capture.borrow_mut().take());
+  Box::into_raw(Box::new(result.map(|capture| capture.result).unwrap_or_default()))
+}
+
+/// writes the length without the NUL byte
+unsafe fn with_length(bytes: Option<&Vec<u8>>, length: *mut usize) -> *const u8 {
+  let length = unsafe { length.as_mut() };
+  match bytes {
+    Some(bytes) => {
+      if let Some(length) = length {
+        *length = bytes.len() - 1;
+      }
+      bytes.as_ptr()
+    }
+    None => {
+      if let Some(length) = length {
+        *length = 0;
+      }
+      std::ptr::null()
+    }
+  }
+}
+
+/// false if the call failed, mwa_result_error has the {code, message, field} json then
+#[no_mangle]
+pub unsafe extern "C" fn mwa_result_ok(result: *const MwaResult) -> bool {
+  unsafe { result.as_ref() }.is_some_and(|result| result.error.is_none())
+}
+/// most calls output one buffer, the scans and scan_cache_append two
+#[no_mangle]
+pub unsafe extern "C" fn mwa_result_output_count(result: *const MwaResult) -> usize {
+  unsafe { result.as_ref() }.map_or(0, |result| result.outputs.len())
+}
+/// the index-th output, NULL if there is none. it lives until mwa_result_free
+#[no_mangle]
+pub unsafe extern "C" fn mwa_result_output(
+  result: *const MwaResult,
+  index: usize,
+  length: *mut usize,
+) -> *const u8 {
+  let output = unsafe { result.as_ref() }.and_then(|result| result.outputs.get(index));
+  unsafe { with_length(output, length) }
+}
+/// the {code, message, field} json of a failed call, NULL if it succeeded
+#[no_mangle]
+pub unsafe extern "C" fn mwa_result_error(
+  result: *const MwaResult,
+  length: *mut usize,
+) -> *const u8 {
+  let error = unsafe { result.as_ref() }.and_then(|result| result.error.as_ref());
+  unsafe { with_length(error, length) }
+}
+/// copies the index-th output into a buffer of the caller and returns its length (0 if there
+/// is none). nothing is copied if it is longer than capacity, call again with a larger buffer
+#[no_mangle]
+pub unsafe extern "C" fn mwa_result_copy_output(
+  result: *const MwaResult,
+  index: usize,
+  buffer: *mut u8,
+  capacity: usize,
+) -> usize {
+  let mut length = 0;
+  let output = unsafe { mwa_result_output(result, index, &mut length) };
+  if !output.is_null() && !buffer.is_null() && length <= capacity {
+    unsafe { std::ptr::copy_nonoverlapping(output, buffer, length) };
+  }
+  length
+}
+#[no_mangle]
+pub unsafe extern "C" fn mwa_result_free(result: *mut MwaResult) {
+  if !result.is_null() {
+    drop(unsafe { Box::from_raw(result) });
+  }
+}
+/// for hosts that want the callbacks of the wasm ABI natively: the exports without the
+/// mwa_ prefix use them. NULL unsets one, its output is dropped then
+#[no_mangle]
+pub extern "C" fn mwa_set_io_callbacks(
+  input: Option<MwaInputCallback>,
+  output: Option<MwaOutputCallback>,
+  output_error: Option<MwaOutputCallback>,
+) {
+  *CALLBACKS.lock().unwrap_or_else(|e| e.into_inner()) =
+    IoCallbacks { input, output, output_error };
+}
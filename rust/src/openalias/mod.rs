@@ -0,0 +1,249 @@
+use monero_wallet::address::Network;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AbiError;
+use crate::transaction_building::transaction::parse_address_for_any_network;
+
+pub const OPENALIAS_PREFIX: &str = "oa1:";
+pub const OPENALIAS_SYMBOL: &str = "xmr";
+
+#[derive(Debug, Deserialize)]
+struct OpenAliasParams {
+  /// the TXT records of the domain, as returned by the host's resolver
+  records: Vec<String>,
+  /// the resolver validated the answer with DNSSEC
+  dnssec_valid: bool,
+  /// the resolver could check DNSSEC at all (wallet2's dnssec_available)
+  #[serde(default = "default_dnssec_available")]
+  dnssec_available: bool,
+}
+fn default_dnssec_available() -> bool {
+  true
+}
+#[derive(Debug, Serialize)]
+pub struct OpenAlias {
+  recipients: Vec<OpenAliasRecipient>,
+  dnssec_available: bool,
+  dnssec_valid: bool,
+  /// only trust the recipients without asking the user if this is true
+  trusted: bool,
+}
+#[derive(Debug, Serialize, Default)]
+pub struct OpenAliasRecipient {
+  address: String,
+  recipient_name: Option<String>,
+  tx_description: Option<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct OpenAliasQuery {
+  /// the name whose TXT records the host should look up
+  name: String,
+}
+
+/// wallet2's get_account_address_as_str_from_url: "donate@example.com" is looked up as
+/// "donate.example.com", the name must contain a dot to not be mistaken for an address
+pub fn openalias_query_name(alias: &str) -> Result<OpenAliasQuery, AbiError> {
+  let name = alias.trim().trim_end_matches('.').replacen('@', ".", 1);
+  let valid_label = |label: &str| {
+    !label.is_empty()
+      && label.len() <= 63
+      && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+  };
+  if !name.contains('.') || name.len() > 253 || !name.split('.').all(valid_label) {
+    return Err(AbiError::invalid_input("alias", format!("'{}' is not an OpenAlias", alias)));
+  }
+  Ok(OpenAliasQuery { name: name.to_lowercase() })
+}
+
+/// splits the "key=value; key=value" body of a record, values may be quoted to contain ';'
+fn parse_fields(body: &str) -> Result<Vec<(String, String)>, String> {
+  let mut fields = vec![];
+  let mut rest = body.trim_start();
+  while !rest.is_empty() {
+    let (key, value_and_rest) =
+      rest.split_once('=').ok_or_else(|| format!("bad OpenAlias field '{}'", rest))?;
+    let key = key.trim();
+    if key.is_empty() || !key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+      return Err(format!("bad OpenAlias key '{}'", key));
+    }
+    let value_and_rest = value_and_rest.trim_start();
+    let (value, after) = if let Some(quoted) = value_and_rest.strip_prefix('"') {
+      let end = quoted.find('"').ok_or("unterminated quote in OpenAlias record")?;
+      let after = quoted[end + 1..].trim_start();
+      if !after.is_empty() && !after.starts_with(';') {
+        return Err(format!("unexpected '{}' after quoted OpenAlias value", after));
+      }
+      (&quoted[..end], after.strip_prefix(';').unwrap_or(after))
+    } else {
+      let (value, after) = value_and_rest.split_once(';').unwrap_or((value_and_rest, ""));
+      (value.trim(), after)
+    };
+    if fields.iter().any(|(existing, _)| existing == key) {
+      return Err(format!("duplicate OpenAlias key '{}'", key));
+    }
+    fields.push((key.to_string(), value.to_string()));
+    rest = after.trim_start();
+  }
+  Ok(fields)
+}
+
+/// the recipient of one TXT record, None for records that are not "oa1:xmr"
+fn parse_record(
+  record: &str,
+  network: Option<Network>,
+) -> Result<Option<OpenAliasRecipient>, AbiError> {
+  let Some(body) = record.trim().strip_prefix(OPENALIAS_PREFIX) else {
+    return Ok(None);
+  };
+  let (symbol, body) = body.split_once(' ').unwrap_or((body, ""));
+  if !symbol.eq_ignore_ascii_case(OPENALIAS_SYMBOL) {
+    return Ok(None);
+  }
+  let invalid = |message: String| AbiError::invalid_input("records", message);
+  let mut recipient = OpenAliasRecipient::default();
+  let mut address = None;
+  for (key, value) in parse_fields(body).map_err(invalid)? {
+    match key.as_str() {
+      "recipient_address" => {
+        address = Some(parse_address_for_any_network(&value, network)?.to_string())
+      }
+      "recipient_name" => recipient.recipient_name = Some(value),
+      "tx_description" => recipient.tx_description = Some(value),
+      // other keys (tx_amount, address_signature, checksum, ...) are not used by wallets
+      _ => {}
+    }
+  }
+  recipient.address =
+    address.ok_or_else(|| invalid("OpenAlias record has no recipient_address".to_string()))?;
+  Ok(Some(recipient))
+}
+
+/// parses the TXT records the host resolved for openalias_query_name, no network access here
+pub fn parse_openalias(json_params: &str, network: Option<Network>) -> Result<OpenAlias, AbiError> {
+  let params: OpenAliasParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse openalias params json: {:?}", e),
+    )
+  })?;
+  let mut recipients = vec![];
+  for (i, record) in params.records.iter().enumerate() {
+    let recipient =
+      parse_record(record, network).map_err(|e| e.with_field(&format!("records[{}]", i)))?;
+    if let Some(recipient) = recipient {
+      recipients.push(recipient);
+    }
+  }
+  if recipients.is_empty() {
+    return Err(AbiError::invalid_input("records", "no oa1:xmr record found"));
+  }
+  Ok(OpenAlias {
+    recipients,
+    dnssec_available: params.dnssec_available,
+    dnssec_valid: params.dnssec_valid,
+    trusted: params.dnssec_available && params.dnssec_valid,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::error::{INVALID_INPUT, NETWORK_MISMATCH};
+  use crate::test_util::keys;
+  use serde_json::json;
+
+  fn parse(records: &[String], dnssec_valid: bool) -> Result<OpenAlias, AbiError> {
+    let params = json!({ "records": records, "dnssec_valid": dnssec_valid });
+    parse_openalias(&params.to_string(), Some(Network::Mainnet))
+  }
+  fn record(fields: &str) -> String {
+    format!("oa1:xmr {}", fields)
+  }
+
+  #[test]
+  fn aliases_are_looked_up_with_the_at_as_a_dot() {
+    let query = |alias: &str| openalias_query_name(alias).map(|query| query.name);
+    assert_eq!(query("donate@example.com").unwrap(), "donate.example.com");
+    assert_eq!(query(" Donate@Example.com. ").unwrap(), "donate.example.com");
+    assert_eq!(query("getmonero.org").unwrap(), "getmonero.org");
+    for invalid in ["example", "a..b", "a b.com", keys(9).primary_address().as_str()] {
+      assert_eq!(query(invalid).unwrap_err().field.as_deref(), Some("alias"), "{}", invalid);
+    }
+  }
+
+  #[test]
+  fn quoted_values_can_contain_semicolons() {
+    let address = keys(9).primary_address();
+    let fields = format!(
+      r#"recipient_address={}; recipient_name="Monero; Development"; tx_description=donation"#,
+      address
+    );
+    let alias = parse(&[record(&fields)], true).unwrap();
+    let recipient = &alias.recipients[0];
+    assert_eq!(recipient.address, address);
+    assert_eq!(recipient.recipient_name.as_deref(), Some("Monero; Development"));
+    assert_eq!(recipient.tx_description.as_deref(), Some("donation"));
+
+    let unterminated = format!(r#"recipient_address={}; recipient_name="Monero"#, address);
+    assert!(parse(&[record(&unterminated)], true).is_err());
+  }
+
+  #[test]
+  fn duplicate_keys_are_invalid() {
+    let address = keys(9).primary_address();
+    let other = keys(11).primary_address();
+    let fields = format!("recipient_address={}; recipient_address={}", address, other);
+    let error = parse(&[record(&fields)], true).unwrap_err();
+    assert_eq!(error.code, INVALID_INPUT);
+    assert_eq!(error.field.as_deref(), Some("records[0]"));
+  }
+
+  #[test]
+  fn records_of_other_currencies_are_skipped() {
+    let address = keys(9).primary_address();
+    let records = [
+      "v=spf1 -all".to_string(),
+      "oa1:btc recipient_address=1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2;".to_string(),
+      record(&format!("recipient_address={}", address)),
+      format!("oa1:XMR recipient_address={}", keys(11).primary_address()),
+    ];
+    let alias = parse(&records, true).unwrap();
+    assert_eq!(alias.recipients.len(), 2);
+    assert_eq!(alias.recipients[0].address, address);
+
+    let error = parse(&records[..2], true).unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("records"));
+  }
+
+  #[test]
+  fn addresses_of_another_network_are_a_network_mismatch() {
+    let stagenet = keys(9).viewpair().legacy_address(Network::Stagenet).to_string();
+    let records = [
+      record(&format!("recipient_address={}", keys(11).primary_address())),
+      record(&format!("recipient_address={}", stagenet)),
+    ];
+    let error = parse(&records, true).unwrap_err();
+    assert_eq!(error.code, NETWORK_MISMATCH);
+    assert_eq!(error.field.as_deref(), Some("records[1]"));
+  }
+
+  #[test]
+  fn only_dnssec_validated_answers_are_trusted() {
+    let records = [record(&format!("recipient_address={}", keys(9).primary_address()))];
+    let cases =
+      [(true, true, true), (true, false, false), (false, true, false), (false, false, false)];
+    for (dnssec_available, dnssec_valid, trusted) in cases {
+      let params = json!({
+        "records": records,
+        "dnssec_available": dnssec_available,
+        "dnssec_valid": dnssec_valid,
+      });
+      let alias = parse_openalias(&params.to_string(), Some(Network::Mainnet)).unwrap();
+      assert_eq!(alias.trusted, trusted);
+      assert_eq!((alias.dnssec_available, alias.dnssec_valid), (dnssec_available, dnssec_valid));
+    }
+    // dnssec_available defaults to true
+    assert!(parse(&records, true).unwrap().trusted);
+    assert!(!parse(&records, false).unwrap().trusted);
+  }
+}
@@ -0,0 +1,39 @@
+//! monero's base58: 8 byte blocks, each encoded to 11 characters (the last one to fewer)
+
+const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BLOCK_LEN: usize = 8;
+const ENCODED_BLOCK_LEN: usize = 11;
+/// encoded length of a block with 0..=8 bytes
+const ENCODED_LEN: [usize; BLOCK_LEN + 1] = [0, 2, 3, 5, 6, 7, 9, 10, 11];
+
+pub fn encode(data: &[u8]) -> String {
+  let mut encoded = String::with_capacity(data.len().div_ceil(BLOCK_LEN) * ENCODED_BLOCK_LEN);
+  for block in data.chunks(BLOCK_LEN) {
+    let mut num = block.iter().fold(0u64, |num, byte| (num << 8) | u64::from(*byte));
+    let mut chars = vec![ALPHABET[0]; ENCODED_LEN[block.len()]];
+    for char in chars.iter_mut().rev() {
+      *char = ALPHABET[(num % 58) as usize];
+      num /= 58;
+    }
+    encoded.extend(chars.into_iter().map(char::from));
+  }
+  encoded
+}
+
+pub fn decode(encoded: &str) -> Option<Vec<u8>> {
+  let mut data = Vec::with_capacity(encoded.len() / ENCODED_BLOCK_LEN * BLOCK_LEN + BLOCK_LEN);
+  for block in encoded.as_bytes().chunks(ENCODED_BLOCK_LEN) {
+    let len = ENCODED_LEN.iter().position(|len| *len == block.len())?;
+    let mut num: u128 = 0;
+    for char in block {
+      let digit = ALPHABET.iter().position(|c| c == char)?;
+      num = num * 58 + digit as u128;
+    }
+    // a block may not encode more bytes than it decodes to
+    if num >> (8 * len) != 0 {
+      return None;
+    }
+    data.extend_from_slice(&(num as u64).to_be_bytes()[BLOCK_LEN - len..]);
+  }
+  Some(data)
+}
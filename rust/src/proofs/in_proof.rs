@@ -0,0 +1,163 @@
+use curve25519_dalek::{EdwardsPoint, Scalar};
+use monero_wallet::address::Network;
+use serde::{Deserialize, Serialize};
+
+use super::{
+  CheckedTxProof, check_tx_proofs, encode_tx_proofs, generate_tx_proof, parse_tx_hash, prefix_hash,
+  read_transaction, tx_public_keys,
+};
+use crate::error::AbiError;
+use crate::transaction_building::transaction::parse_address_for_network;
+
+pub const IN_PROOF_HEADER: &str = "InProofV2";
+
+#[derive(Debug, Deserialize)]
+struct InProofParams {
+  tx_hash: String,
+  /// one of our addresses that received outputs in the transaction
+  address: String,
+  #[serde(default)]
+  message: String,
+  /// the full transaction (get_transactions as_hex)
+  transaction: String,
+}
+#[derive(Debug, Serialize)]
+pub struct InProof {
+  signature: String,
+}
+
+/// proves address received the outputs of tx_hash: one signature per tx public key R,
+/// each over the shared secret view key * R
+pub fn get_in_proof(
+  json_params: &str,
+  view_key: &Scalar,
+  network: Network,
+) -> Result<InProof, AbiError> {
+  let params: InProofParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input("json_params", format!("failed to parse in proof params json: {:?}", e))
+  })?;
+  let address = parse_address_for_network(&params.address, network)?;
+  let prefix_hash = prefix_hash(&parse_tx_hash(&params.tx_hash)?, &params.message);
+  let tx = read_transaction("transaction", &params.transaction, &params.tx_hash)?;
+
+  // the view key of a subaddress is a * D, of the primary address a * G
+  let view_public = address.view();
+  let spend_public = address.is_subaddress().then(|| address.spend());
+  let expected_view_public = match &spend_public {
+    Some(spend_public) => view_key * spend_public,
+    None => EdwardsPoint::mul_base(view_key),
+  };
+  if expected_view_public != view_public {
+    let message = format!("{} does not belong to this wallet", params.address);
+    return Err(AbiError::invalid_input("address", message));
+  }
+
+  let (tx_public_key, additional_public_keys) = tx_public_keys(&tx)?;
+  let mut proofs = vec![];
+  for tx_public_key in core::iter::once(&tx_public_key).chain(&additional_public_keys) {
+    let shared_secret = view_key * tx_public_key;
+    let signature = generate_tx_proof(
+      &prefix_hash,
+      &view_public,
+      tx_public_key,
+      spend_public.as_ref(),
+      &shared_secret,
+      view_key,
+    );
+    proofs.push((shared_secret, signature));
+  }
+  Ok(InProof { signature: encode_tx_proofs(IN_PROOF_HEADER, &proofs) })
+}
+
+pub fn check_in_proof(
+  json_params: &str,
+  network: Option<Network>,
+) -> Result<CheckedTxProof, AbiError> {
+  check_tx_proofs(json_params, IN_PROOF_HEADER, true, network)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{Keys, XMR, fixture, keys, payment, sign};
+  use monero_wallet::{
+    address::{MoneroAddress, SubaddressIndex},
+    send::Change,
+  };
+  use serde_json::{Value, json};
+
+  /// keys(7) pays XMR to address, the in proof of keys(9) for it and the params to check it
+  fn prove(address: MoneroAddress, receiver: &Keys) -> Result<(InProof, Value), AbiError> {
+    let tx = payment(&keys(7), vec![(address, XMR)], Change::new(keys(7).viewpair(), None));
+    let tx = sign(&keys(7), tx);
+    let params = json!({
+      "tx_hash": hex::encode(tx.hash()),
+      "address": address.to_string(),
+      "message": "paid",
+      "transaction": hex::encode(tx.serialize()),
+    });
+    let proof = get_in_proof(&params.to_string(), &receiver.view_key, Network::Mainnet)?;
+    Ok((proof, params))
+  }
+  fn check(mut params: Value, proof: &InProof) -> CheckedTxProof {
+    params["signature"] = json!(proof.signature);
+    check_in_proof(&params.to_string(), Some(Network::Mainnet)).unwrap()
+  }
+
+  #[test]
+  fn proves_payments_to_the_primary_address() {
+    let (proof, params) =
+      prove(keys(9).viewpair().legacy_address(Network::Mainnet), &keys(9)).unwrap();
+    assert!(proof.signature.starts_with(IN_PROOF_HEADER));
+    let checked = check(params, &proof);
+    assert!(checked.good);
+    assert_eq!(checked.received, XMR);
+  }
+
+  #[test]
+  fn proves_payments_to_subaddresses() {
+    let index = SubaddressIndex::new(1, 4).unwrap();
+    let address = keys(9).viewpair().subaddress(Network::Mainnet, index);
+    let (proof, params) = prove(address, &keys(9)).unwrap();
+    let checked = check(params, &proof);
+    assert!(checked.good);
+    assert_eq!(checked.received, XMR);
+  }
+
+  #[test]
+  fn proofs_only_hold_for_their_message_and_address() {
+    let (proof, params) =
+      prove(keys(9).viewpair().legacy_address(Network::Mainnet), &keys(9)).unwrap();
+    let mut other_message = params.clone();
+    other_message["message"] = json!("not paid");
+    assert!(!check(other_message, &proof).good);
+    let mut other_address = params;
+    other_address["address"] = json!(keys(11).primary_address());
+    assert!(!check(other_address, &proof).good);
+  }
+
+  #[test]
+  fn rejects_addresses_of_other_wallets() {
+    let address = keys(9).viewpair().legacy_address(Network::Mainnet);
+    assert!(prove(address, &keys(11)).is_err());
+  }
+
+  #[test]
+  fn rejects_addresses_of_other_networks() {
+    let (proof, mut params) =
+      prove(keys(9).viewpair().legacy_address(Network::Mainnet), &keys(9)).unwrap();
+    params["signature"] = json!(proof.signature);
+    let error = check_in_proof(&params.to_string(), Some(Network::Stagenet)).unwrap_err();
+    assert_eq!(error.code, crate::error::NETWORK_MISMATCH);
+    assert_eq!(error.field.as_deref(), Some("address"));
+  }
+
+  #[test]
+  #[ignore = "needs tests/fixtures/in_proof.json, see typescript/scripts/stagenet_fixtures.ts"]
+  fn checks_wallet_cli_proofs() {
+    let fixture = fixture("in_proof.json");
+    let checked = check_in_proof(&fixture.to_string(), Some(Network::Stagenet)).unwrap();
+    assert!(checked.good);
+    assert_eq!(checked.received.to_string(), fixture["received"].as_str().unwrap());
+  }
+}
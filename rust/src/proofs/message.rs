@@ -0,0 +1,262 @@
+use curve25519_dalek::{EdwardsPoint, Scalar};
+use monero_primitives::keccak256;
+use monero_wallet::{
+  ViewPair,
+  address::{MoneroAddress, Network, SubaddressIndex},
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::{
+  ENCODED_SIGNATURE_LEN, base58, check_signature, generate_signature, parse_scalar, read_scalar,
+  subaddress_secret,
+};
+use crate::block_parsing::view_tags::write_varint;
+use crate::error::AbiError;
+use crate::transaction_building::transaction::parse_address_for_any_network;
+
+pub const SIGNATURE_V1_HEADER: &str = "SigV1";
+pub const SIGNATURE_V2_HEADER: &str = "SigV2";
+/// config::HASH_KEY_MESSAGE_SIGNING, hashed including its null terminator
+const HASH_KEY_MESSAGE_SIGNING: &[u8] = b"MoneroMessageSignature\0";
+
+#[derive(Debug, Deserialize)]
+struct SignMessageParams {
+  message: String,
+  /// "spend" or "view"
+  key: String,
+  /// needed to sign with the spend key, and with the view key of a subaddress
+  spend_key: Option<String>,
+  /// sign as this subaddress instead of the primary address
+  subaddress_index: Option<[u32; 2]>,
+}
+#[derive(Debug, Deserialize)]
+struct VerifyMessageParams {
+  message: String,
+  address: String,
+  signature: String,
+}
+#[derive(Debug, Serialize)]
+pub struct SignedMessage {
+  address: String,
+  signature: String,
+}
+#[derive(Debug, Serialize)]
+pub struct VerifiedMessage {
+  good: bool,
+  /// 1 or 2, 0 if the signature is not good
+  version: u8,
+  /// "spend" or "view", None if the signature is not good
+  key: Option<&'static str>,
+}
+
+/// the mode byte of the message hash, wallet2's message_signature_type_t
+fn mode(key: &str) -> Result<u8, AbiError> {
+  match key {
+    "spend" => Ok(0),
+    "view" => Ok(1),
+    _ => {
+      Err(AbiError::invalid_input("key", format!("Invalid key: '{}'. Must be: spend, view", key)))
+    }
+  }
+}
+
+/// wallet2's get_message_hash: domain separator, both public keys of the address, the mode
+/// and the length prefixed message
+fn message_hash(message: &str, address: &MoneroAddress, mode: u8) -> [u8; 32] {
+  let mut buf = HASH_KEY_MESSAGE_SIGNING.to_vec();
+  buf.extend(address.spend().compress().to_bytes());
+  buf.extend(address.view().compress().to_bytes());
+  buf.push(mode);
+  write_varint(message.len() as u64, &mut buf);
+  buf.extend(message.as_bytes());
+  keccak256(buf)
+}
+
+pub fn sign_message(
+  json_params: &str,
+  viewpair: &ViewPair,
+  view_key: &Scalar,
+  network: Network,
+) -> Result<SignedMessage, AbiError> {
+  let params: SignMessageParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse sign message params json: {:?}", e),
+    )
+  })?;
+  let mode = mode(&params.key)?;
+  let index = match params.subaddress_index {
+    Some([major, minor]) => SubaddressIndex::new(major, minor),
+    None => None,
+  };
+  let address = match index {
+    Some(index) => viewpair.subaddress(network, index),
+    None => viewpair.legacy_address(network),
+  };
+  let spend_key = match &params.spend_key {
+    Some(spend_key) => {
+      let spend_key = Zeroizing::new(parse_scalar("spend_key", spend_key)?);
+      if EdwardsPoint::mul_base(&spend_key) != viewpair.spend() {
+        let message = "spend_key does not belong to this wallet";
+        return Err(AbiError::invalid_input("spend_key", message));
+      }
+      Some(spend_key)
+    }
+    None => None,
+  };
+  let missing_spend_key =
+    || AbiError::invalid_input("spend_key", "signing with this key needs the spend_key");
+
+  // the spend key of subaddress (major, minor) is b + m, its view key a * (b + m)
+  let m = subaddress_secret(view_key, index.map(|index| (index.account(), index.address())));
+  let secret = Zeroizing::new(match (mode, index) {
+    (0, _) => *spend_key.ok_or_else(missing_spend_key)?.as_ref() + m,
+    (_, None) => *view_key,
+    (_, Some(_)) => view_key * (*spend_key.ok_or_else(missing_spend_key)?.as_ref() + m),
+  });
+  let public_key = EdwardsPoint::mul_base(&secret);
+  let expected_public_key = if mode == 0 { address.spend() } else { address.view() };
+  if public_key != expected_public_key {
+    return Err(AbiError::from("derived key does not match the address"));
+  }
+
+  let hash = message_hash(&params.message, &address, mode);
+  let (c, r) = generate_signature(&hash, &public_key, &secret);
+  let mut signature = c.to_bytes().to_vec();
+  signature.extend(r.to_bytes());
+  Ok(SignedMessage {
+    address: address.to_string(),
+    signature: format!("{}{}", SIGNATURE_V2_HEADER, base58::encode(&signature)),
+  })
+}
+
+pub fn verify_message(
+  json_params: &str,
+  network: Option<Network>,
+) -> Result<VerifiedMessage, AbiError> {
+  let params: VerifyMessageParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse verify message params json: {:?}", e),
+    )
+  })?;
+  let address = parse_address_for_any_network(&params.address, network)?;
+  let not_good = VerifiedMessage { good: false, version: 0, key: None };
+
+  let (version, body) = if let Some(body) = params.signature.strip_prefix(SIGNATURE_V2_HEADER) {
+    (2, body)
+  } else if let Some(body) = params.signature.strip_prefix(SIGNATURE_V1_HEADER) {
+    (1, body)
+  } else {
+    return Ok(not_good);
+  };
+  if body.len() != ENCODED_SIGNATURE_LEN {
+    return Ok(not_good);
+  }
+  let Some(bytes) = base58::decode(body).filter(|bytes| bytes.len() == 64) else {
+    return Ok(not_good);
+  };
+  let (Ok(c), Ok(r)) = (
+    read_scalar(bytes[..32].try_into().unwrap()), // length checked above
+    read_scalar(bytes[32..].try_into().unwrap()),
+  ) else {
+    return Ok(not_good);
+  };
+
+  for (mode, key, public_key) in [(0, "spend", address.spend()), (1, "view", address.view())] {
+    // v1 signatures only sign the plain message hash
+    let hash = if version == 1 {
+      keccak256(params.message.as_bytes())
+    } else {
+      message_hash(&params.message, &address, mode)
+    };
+    if check_signature(&hash, &public_key, (c, r)) {
+      return Ok(VerifiedMessage { good: true, version, key: Some(key) });
+    }
+  }
+  Ok(not_good)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{fixture, keys};
+  use serde_json::{Value, json};
+
+  fn sign(params: Value) -> Result<SignedMessage, AbiError> {
+    let wallet = keys(9);
+    sign_message(&params.to_string(), &wallet.viewpair(), &wallet.view_key, Network::Mainnet)
+  }
+  fn verify(message: &str, address: &str, signature: &str) -> VerifiedMessage {
+    let params = json!({ "message": message, "address": address, "signature": signature });
+    verify_message(&params.to_string(), Some(Network::Mainnet)).unwrap()
+  }
+
+  #[test]
+  fn signs_with_the_spend_and_the_view_key() {
+    let spend_key = keys(9).spend_key_hex();
+    for (key, spend_key) in [("spend", Some(spend_key)), ("view", None)] {
+      let params = json!({ "message": "hello", "key": key, "spend_key": spend_key });
+      let signed = sign(params).unwrap();
+      assert_eq!(signed.address, keys(9).primary_address());
+      assert!(signed.signature.starts_with(SIGNATURE_V2_HEADER));
+      let verified = verify("hello", &signed.address, &signed.signature);
+      assert!(verified.good);
+      assert_eq!((verified.version, verified.key), (2, Some(key)));
+    }
+  }
+
+  #[test]
+  fn signs_as_subaddresses() {
+    for key in ["spend", "view"] {
+      let params = json!({
+        "message": "hello",
+        "key": key,
+        "spend_key": keys(9).spend_key_hex(),
+        "subaddress_index": [1, 3],
+      });
+      let signed = sign(params).unwrap();
+      let index = SubaddressIndex::new(1, 3).unwrap();
+      let address = keys(9).viewpair().subaddress(Network::Mainnet, index).to_string();
+      assert_eq!(signed.address, address);
+      assert_eq!(verify("hello", &address, &signed.signature).key, Some(key));
+    }
+  }
+
+  #[test]
+  fn subaddress_view_keys_need_the_spend_key() {
+    let params = json!({ "message": "hello", "key": "view", "subaddress_index": [1, 3] });
+    assert_eq!(sign(params).unwrap_err().field.as_deref(), Some("spend_key"));
+  }
+
+  #[test]
+  fn signatures_only_hold_for_their_message_and_address() {
+    let signed = sign(json!({ "message": "hello", "key": "view" })).unwrap();
+    assert!(!verify("hello!", &signed.address, &signed.signature).good);
+    assert!(!verify("hello", &keys(11).primary_address(), &signed.signature).good);
+    assert!(!verify("hello", &signed.address, "SigV2").good);
+  }
+
+  #[test]
+  fn verifies_v1_signatures() {
+    let wallet = keys(9);
+    let (c, r) = generate_signature(&keccak256(b"hello"), &wallet.spend, &wallet.spend_key);
+    let mut signature = c.to_bytes().to_vec();
+    signature.extend(r.to_bytes());
+    let signature = format!("{}{}", SIGNATURE_V1_HEADER, base58::encode(&signature));
+    let verified = verify("hello", &wallet.primary_address(), &signature);
+    assert!(verified.good);
+    assert_eq!((verified.version, verified.key), (1, Some("spend")));
+  }
+
+  #[test]
+  #[ignore = "needs tests/fixtures/message.json, see typescript/scripts/stagenet_fixtures.ts"]
+  fn verifies_wallet_cli_signatures() {
+    let fixture = fixture("message.json");
+    let verified = verify_message(&fixture.to_string(), Some(Network::Stagenet)).unwrap();
+    assert!(verified.good);
+    assert_eq!(verified.version, 2);
+    assert_eq!(verified.key, fixture["key"].as_str());
+  }
+}
@@ -0,0 +1,425 @@
+pub mod base58;
+pub mod in_proof;
+pub mod message;
+pub mod out_proof;
+pub mod reserve_proof;
+pub mod spend_proof;
+pub mod tx_key;
+
+use curve25519_dalek::{EdwardsPoint, Scalar, edwards::CompressedEdwardsY};
+use monero_primitives::{Commitment, keccak256, keccak256_to_scalar};
+use monero_wallet::{
+  address::{MoneroAddress, Network},
+  extra::Extra,
+  generators::biased_hash_to_point,
+  ringct::EncryptedAmount,
+  transaction::Transaction,
+};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::block_parsing::{amount_to_string, view_tags::write_varint};
+use crate::error::AbiError;
+use crate::transaction_building::transaction::parse_address_for_any_network;
+
+/// base58 length of a 32 byte point and of a 64 byte (c, r) signature
+pub const ENCODED_POINT_LEN: usize = 44;
+pub const ENCODED_SIGNATURE_LEN: usize = 88;
+
+#[derive(Debug, Deserialize)]
+struct CheckTxProofParams {
+  tx_hash: String,
+  address: String,
+  #[serde(default)]
+  message: String,
+  signature: String,
+  /// the full transaction (get_transactions as_hex)
+  transaction: String,
+}
+#[derive(Debug, Serialize)]
+pub struct CheckedTxProof {
+  good: bool,
+  #[serde(serialize_with = "amount_to_string")]
+  received: u64,
+}
+
+/// reads a full transaction from hex and makes sure it is the one the proof is about,
+/// field is where the transaction came from in json_params
+pub(crate) fn read_transaction(
+  field: &str,
+  tx_hex: &str,
+  tx_hash: &str,
+) -> Result<Transaction, AbiError> {
+  let invalid = |message: String| AbiError::invalid_input(field, message);
+  let blob = hex::decode(tx_hex)
+    .map_err(|e| invalid(format!("failed to decode transaction hex: {:?}", e)))?;
+  let tx = Transaction::read::<&[u8]>(&mut blob.as_slice())
+    .map_err(|e| invalid(format!("failed to read transaction: {:?}", e)))?;
+  if hex::encode(tx.hash()) != tx_hash {
+    return Err(invalid(format!(
+      "transaction hash is {}, not {}",
+      hex::encode(tx.hash()),
+      tx_hash
+    )));
+  }
+  Ok(tx)
+}
+pub(crate) fn parse_tx_hash(tx_hash: &str) -> Result<[u8; 32], AbiError> {
+  let bytes = hex::decode(tx_hash).map_err(|e| {
+    AbiError::invalid_input("tx_hash", format!("failed to decode tx_hash hex: {:?}", e))
+  })?;
+  bytes.try_into().map_err(|_| AbiError::invalid_input("tx_hash", "tx_hash must be 32 bytes"))
+}
+pub(crate) fn parse_scalar(field: &str, hex_str: &str) -> Result<Scalar, AbiError> {
+  let bytes: [u8; 32] =
+    hex::decode(hex_str).ok().and_then(|bytes| bytes.try_into().ok()).ok_or_else(|| {
+      AbiError::invalid_input(field, format!("'{}' is not a 32 byte hex string", hex_str))
+    })?;
+  Option::from(Scalar::from_canonical_bytes(bytes)).ok_or_else(|| {
+    AbiError::invalid_input(field, format!("'{}' is not a canonical scalar", hex_str))
+  })
+}
+pub(crate) fn read_point(bytes: [u8; 32]) -> Result<EdwardsPoint, String> {
+  CompressedEdwardsY(bytes)
+    .decompress()
+    .ok_or_else(|| format!("invalid point {}", hex::encode(bytes)))
+}
+pub(crate) fn read_scalar(bytes: [u8; 32]) -> Result<Scalar, String> {
+  Option::from(Scalar::from_canonical_bytes(bytes)).ok_or("invalid scalar in signature".to_string())
+}
+
+/// the tx public key and the additional tx public keys from the tx extra
+pub(crate) fn tx_public_keys(
+  tx: &Transaction,
+) -> Result<(EdwardsPoint, Vec<EdwardsPoint>), String> {
+  let extra = Extra::read::<&[u8]>(&mut tx.prefix().extra.as_ref())
+    .map_err(|e| format!("failed to read tx extra: {:?}", e))?;
+  let (keys, additional) = extra.keys().ok_or("transaction has no tx public key")?;
+  let tx_public_key = *keys.first().ok_or("transaction has no tx public key")?;
+  Ok((tx_public_key, additional.unwrap_or_default()))
+}
+
+/// the secret a subaddress adds to the spend key: Hs("SubAddr\0" || a || major || minor)
+pub(crate) fn subaddress_secret(view_key: &Scalar, index: Option<(u32, u32)>) -> Scalar {
+  match index {
+    None => Scalar::ZERO,
+    Some((major, minor)) => {
+      let mut buf = b"SubAddr\0".to_vec();
+      buf.extend(view_key.to_bytes());
+      buf.extend(major.to_le_bytes());
+      buf.extend(minor.to_le_bytes());
+      keccak256_to_scalar(buf)
+    }
+  }
+}
+
+/// cryptonote key derivation: 8 * secret * public
+pub(crate) fn key_derivation(secret: &Scalar, public: &EdwardsPoint) -> EdwardsPoint {
+  (secret * public).mul_by_cofactor()
+}
+/// Hs(derivation || varint(output index))
+pub(crate) fn shared_key(derivation: &EdwardsPoint, output_index: usize) -> Scalar {
+  let mut buf = derivation.compress().to_bytes().to_vec();
+  write_varint(output_index as u64, &mut buf);
+  keccak256_to_scalar(buf)
+}
+
+/// hash of tx hash and message, what the tx proofs sign
+pub(crate) fn prefix_hash(tx_hash: &[u8; 32], message: &str) -> [u8; 32] {
+  let mut buf = tx_hash.to_vec();
+  buf.extend(message.as_bytes());
+  keccak256(buf)
+}
+
+/// decrypts the amount of output i and checks it against the output's commitment
+pub(crate) fn output_amount(
+  tx: &Transaction,
+  i: usize,
+  shared_key: &Scalar,
+) -> Result<u64, String> {
+  let Transaction::V2 { proofs: Some(proofs), .. } = tx else {
+    return Err("only RingCT transactions are supported".to_string());
+  };
+  let (Some(EncryptedAmount::Compact { amount }), Some(commitment)) =
+    (proofs.base.encrypted_amounts.get(i), proofs.base.commitments.get(i))
+  else {
+    return Err(format!("output {} has no compact encrypted amount", i));
+  };
+  let mut amount_key = b"amount".to_vec();
+  amount_key.extend(shared_key.to_bytes());
+  let amount_mask = keccak256(amount_key);
+  let mut amount_bytes = *amount;
+  for (byte, mask) in amount_bytes.iter_mut().zip(amount_mask) {
+    *byte ^= mask;
+  }
+  let amount = u64::from_le_bytes(amount_bytes);
+
+  // the amount is only trustworthy if it opens the commitment
+  let mut mask_key = b"commitment_mask".to_vec();
+  mask_key.extend(shared_key.to_bytes());
+  let expected = Commitment::new(keccak256_to_scalar(mask_key), amount).calculate();
+  if expected.compress().to_bytes() != commitment.to_bytes() {
+    return Err(format!("amount of output {} does not match its commitment", i));
+  }
+  Ok(amount)
+}
+
+/// the amount the outputs of tx send to address, given the derivation for the tx public key
+/// and the derivations for the additional tx public keys (both as the sender or receiver sees them)
+pub(crate) fn received_amount(
+  tx: &Transaction,
+  derivation: Option<&EdwardsPoint>,
+  additional_derivations: &[EdwardsPoint],
+  address: &MoneroAddress,
+) -> Result<u64, String> {
+  let mut received: u64 = 0;
+  for (i, output) in tx.prefix().outputs.iter().enumerate() {
+    let derivations = derivation.into_iter().chain(additional_derivations.get(i));
+    for derivation in derivations {
+      let shared_key = shared_key(derivation, i);
+      let output_key = EdwardsPoint::mul_base(&shared_key) + address.spend();
+      if output_key.compress().to_bytes() != output.key.to_bytes() {
+        continue;
+      }
+      let amount = output_amount(tx, i, &shared_key)?;
+      received = received.checked_add(amount).ok_or("received amount overflows")?;
+      break;
+    }
+  }
+  Ok(received)
+}
+
+/// the challenge of a TXPROOF_V2 signature, hashed like wallet2's s_comm_2
+fn tx_proof_challenge(
+  prefix_hash: &[u8; 32],
+  public_key: &EdwardsPoint,
+  other_public_key: &EdwardsPoint,
+  base: Option<&EdwardsPoint>,
+  shared_secret: &EdwardsPoint,
+  x: &EdwardsPoint,
+  y: &EdwardsPoint,
+) -> Scalar {
+  let mut buf = Vec::with_capacity(32 * 8);
+  buf.extend(prefix_hash);
+  buf.extend(shared_secret.compress().to_bytes());
+  buf.extend(x.compress().to_bytes());
+  buf.extend(y.compress().to_bytes());
+  buf.extend(keccak256(b"TXPROOF_V2"));
+  buf.extend(public_key.compress().to_bytes());
+  buf.extend(other_public_key.compress().to_bytes());
+  buf.extend(base.map(|base| base.compress().to_bytes()).unwrap_or([0; 32]));
+  keccak256_to_scalar(buf)
+}
+
+/// proves knowledge of secret such that public_key = secret * (base or G)
+/// and shared_secret = secret * other_public_key (crypto::generate_tx_proof, version 2).
+/// base is the spend key of the address if it is a subaddress
+pub(crate) fn generate_tx_proof(
+  prefix_hash: &[u8; 32],
+  public_key: &EdwardsPoint,
+  other_public_key: &EdwardsPoint,
+  base: Option<&EdwardsPoint>,
+  shared_secret: &EdwardsPoint,
+  secret: &Scalar,
+) -> (Scalar, Scalar) {
+  let k = Zeroizing::new(Scalar::random(&mut OsRng));
+  let x = match base {
+    Some(base) => *k * base,
+    None => EdwardsPoint::mul_base(&k),
+  };
+  let y = *k * other_public_key;
+  let c =
+    tx_proof_challenge(prefix_hash, public_key, other_public_key, base, shared_secret, &x, &y);
+  (c, *k - c * secret)
+}
+pub(crate) fn check_tx_proof(
+  prefix_hash: &[u8; 32],
+  public_key: &EdwardsPoint,
+  other_public_key: &EdwardsPoint,
+  base: Option<&EdwardsPoint>,
+  shared_secret: &EdwardsPoint,
+  (c, r): (Scalar, Scalar),
+) -> bool {
+  let x = match base {
+    Some(base) => c * public_key + r * base,
+    None => c * public_key + EdwardsPoint::mul_base(&r),
+  };
+  let y = c * shared_secret + r * other_public_key;
+  tx_proof_challenge(prefix_hash, public_key, other_public_key, base, shared_secret, &x, &y) == c
+}
+
+/// crypto::generate_signature: schnorr signature of prefix_hash by public_key = secret * G
+pub(crate) fn generate_signature(
+  prefix_hash: &[u8; 32],
+  public_key: &EdwardsPoint,
+  secret: &Scalar,
+) -> (Scalar, Scalar) {
+  let k = Zeroizing::new(Scalar::random(&mut OsRng));
+  let mut buf = prefix_hash.to_vec();
+  buf.extend(public_key.compress().to_bytes());
+  buf.extend(EdwardsPoint::mul_base(&k).compress().to_bytes());
+  let c = keccak256_to_scalar(buf);
+  (c, *k - c * secret)
+}
+pub(crate) fn check_signature(
+  prefix_hash: &[u8; 32],
+  public_key: &EdwardsPoint,
+  (c, r): (Scalar, Scalar),
+) -> bool {
+  let mut buf = prefix_hash.to_vec();
+  buf.extend(public_key.compress().to_bytes());
+  buf.extend((c * public_key + EdwardsPoint::mul_base(&r)).compress().to_bytes());
+  keccak256_to_scalar(buf) == c
+}
+
+/// crypto::generate_ring_signature: the pre-RingCT ring signature, one (c, r) per ring member
+pub(crate) fn generate_ring_signature(
+  prefix_hash: &[u8; 32],
+  key_image: &EdwardsPoint,
+  ring: &[EdwardsPoint],
+  secret: &Scalar,
+  secret_index: usize,
+) -> Vec<(Scalar, Scalar)> {
+  let k = Zeroizing::new(Scalar::random(&mut OsRng));
+  let mut signature = vec![(Scalar::ZERO, Scalar::ZERO); ring.len()];
+  let mut sum = Scalar::ZERO;
+  let mut buf = prefix_hash.to_vec();
+  for (i, public_key) in ring.iter().enumerate() {
+    let hash_point = biased_hash_to_point(public_key.compress().to_bytes());
+    let (a, b) = if i == secret_index {
+      (EdwardsPoint::mul_base(&k), *k * hash_point)
+    } else {
+      let (c, r) = (Scalar::random(&mut OsRng), Scalar::random(&mut OsRng));
+      signature[i] = (c, r);
+      sum += c;
+      (EdwardsPoint::mul_base(&r) + c * public_key, r * hash_point + c * key_image)
+    };
+    buf.extend(a.compress().to_bytes());
+    buf.extend(b.compress().to_bytes());
+  }
+  let c = keccak256_to_scalar(buf) - sum;
+  signature[secret_index] = (c, *k - c * secret);
+  signature
+}
+pub(crate) fn check_ring_signature(
+  prefix_hash: &[u8; 32],
+  key_image: &EdwardsPoint,
+  ring: &[EdwardsPoint],
+  signature: &[(Scalar, Scalar)],
+) -> bool {
+  if ring.len() != signature.len() || !key_image.is_torsion_free() {
+    return false;
+  }
+  let mut sum = Scalar::ZERO;
+  let mut buf = prefix_hash.to_vec();
+  for (public_key, (c, r)) in ring.iter().zip(signature) {
+    let hash_point = biased_hash_to_point(public_key.compress().to_bytes());
+    buf.extend((EdwardsPoint::mul_base(r) + c * public_key).compress().to_bytes());
+    buf.extend((r * hash_point + c * key_image).compress().to_bytes());
+    sum += c;
+  }
+  keccak256_to_scalar(buf) == sum
+}
+
+/// header followed by base58(shared secret) base58(c || r) for every tx key
+pub(crate) fn encode_tx_proofs(
+  header: &str,
+  proofs: &[(EdwardsPoint, (Scalar, Scalar))],
+) -> String {
+  let mut encoded = header.to_string();
+  for (shared_secret, (c, r)) in proofs {
+    encoded.push_str(&base58::encode(&shared_secret.compress().to_bytes()));
+    let mut signature = c.to_bytes().to_vec();
+    signature.extend(r.to_bytes());
+    encoded.push_str(&base58::encode(&signature));
+  }
+  encoded
+}
+pub(crate) fn decode_tx_proofs(
+  header: &str,
+  encoded: &str,
+) -> Result<Vec<(EdwardsPoint, (Scalar, Scalar))>, String> {
+  let body = encoded
+    .strip_prefix(header)
+    .ok_or_else(|| format!("signature does not start with {}", header))?;
+  let entry_len = ENCODED_POINT_LEN + ENCODED_SIGNATURE_LEN;
+  if body.is_empty() || body.len() % entry_len != 0 || !body.is_ascii() {
+    return Err("signature has an invalid length".to_string());
+  }
+  let mut proofs = vec![];
+  for i in (0..body.len()).step_by(entry_len) {
+    let shared_secret = base58::decode(&body[i..i + ENCODED_POINT_LEN])
+      .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+      .ok_or("invalid base58 in signature")?;
+    let signature = base58::decode(&body[i + ENCODED_POINT_LEN..i + entry_len])
+      .filter(|bytes| bytes.len() == 64)
+      .ok_or("invalid base58 in signature")?;
+    proofs.push((
+      read_point(shared_secret)?,
+      (
+        read_scalar(signature[..32].try_into().unwrap())?, // length checked above
+        read_scalar(signature[32..].try_into().unwrap())?,
+      ),
+    ));
+  }
+  Ok(proofs)
+}
+
+/// check_tx_proof of wallet2: out proofs sign r * (G or B) and r * A,
+/// in proofs sign a * (G or B) = A and a * R, both with one signature per tx public key
+pub(crate) fn check_tx_proofs(
+  json_params: &str,
+  header: &str,
+  inbound: bool,
+  network: Option<Network>,
+) -> Result<CheckedTxProof, AbiError> {
+  let params: CheckTxProofParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse check tx proof params json: {:?}", e),
+    )
+  })?;
+  let address = parse_address_for_any_network(&params.address, network)?;
+  let prefix_hash = prefix_hash(&parse_tx_hash(&params.tx_hash)?, &params.message);
+  let tx = read_transaction("transaction", &params.transaction, &params.tx_hash)?;
+  let proofs = decode_tx_proofs(header, &params.signature)
+    .map_err(|message| AbiError::invalid_input("signature", message))?;
+  let (tx_public_key, additional_public_keys) = tx_public_keys(&tx)?;
+  if proofs.len() != 1 + additional_public_keys.len() {
+    return Err(AbiError::invalid_input(
+      "signature",
+      format!(
+        "signature has {} proofs, the transaction has {} tx keys",
+        proofs.len(),
+        1 + additional_public_keys.len()
+      ),
+    ));
+  }
+
+  let view_public = address.view();
+  let spend_public = address.is_subaddress().then(|| address.spend());
+  let good = core::iter::once(&tx_public_key).chain(&additional_public_keys).zip(&proofs).any(
+    |(tx_public_key, (shared_secret, signature))| {
+      let (public_key, other_public_key) =
+        if inbound { (&view_public, tx_public_key) } else { (tx_public_key, &view_public) };
+      check_tx_proof(
+        &prefix_hash,
+        public_key,
+        other_public_key,
+        spend_public.as_ref(),
+        shared_secret,
+        *signature,
+      )
+    },
+  );
+  if !good {
+    return Ok(CheckedTxProof { good, received: 0 });
+  }
+
+  // the shared secrets are r * A = a * R, the derivations 8 * r * A
+  let derivations: Vec<EdwardsPoint> =
+    proofs.iter().map(|(shared_secret, _)| shared_secret.mul_by_cofactor()).collect();
+  let received = received_amount(&tx, Some(&derivations[0]), &derivations[1..], &address)?;
+  Ok(CheckedTxProof { good, received })
+}
@@ -0,0 +1,69 @@
+use curve25519_dalek::EdwardsPoint;
+use monero_wallet::address::Network;
+use serde::{Deserialize, Serialize};
+
+use super::tx_key::parse_tx_key;
+use super::{
+  CheckedTxProof, check_tx_proofs, encode_tx_proofs, generate_tx_proof, parse_tx_hash, prefix_hash,
+};
+use crate::error::AbiError;
+use crate::transaction_building::transaction::parse_address_for_any_network;
+
+pub const OUT_PROOF_HEADER: &str = "OutProofV2";
+
+#[derive(Debug, Deserialize)]
+struct OutProofParams {
+  tx_hash: String,
+  /// as returned by get_tx_key
+  tx_key: String,
+  address: String,
+  #[serde(default)]
+  message: String,
+}
+#[derive(Debug, Serialize)]
+pub struct OutProof {
+  signature: String,
+}
+
+/// proves we sent the outputs of tx_hash to address: one signature per tx key,
+/// each over the shared secret tx key * view key of the recipient
+pub fn get_out_proof(json_params: &str, network: Option<Network>) -> Result<OutProof, AbiError> {
+  let params: OutProofParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse out proof params json: {:?}", e),
+    )
+  })?;
+  let address = parse_address_for_any_network(&params.address, network)?;
+  let prefix_hash = prefix_hash(&parse_tx_hash(&params.tx_hash)?, &params.message);
+  let (tx_key, additional_keys) = parse_tx_key(&params.tx_key)?;
+
+  let view_public = address.view();
+  let spend_public = address.is_subaddress().then(|| address.spend());
+  let mut proofs = vec![];
+  for secret in core::iter::once(&tx_key).chain(&additional_keys) {
+    // for subaddresses the tx public key is r * B instead of r * G
+    let tx_public_key = match &spend_public {
+      Some(spend_public) => secret * spend_public,
+      None => EdwardsPoint::mul_base(secret),
+    };
+    let shared_secret = secret * view_public;
+    let signature = generate_tx_proof(
+      &prefix_hash,
+      &tx_public_key,
+      &view_public,
+      spend_public.as_ref(),
+      &shared_secret,
+      secret,
+    );
+    proofs.push((shared_secret, signature));
+  }
+  Ok(OutProof { signature: encode_tx_proofs(OUT_PROOF_HEADER, &proofs) })
+}
+
+pub fn check_out_proof(
+  json_params: &str,
+  network: Option<Network>,
+) -> Result<CheckedTxProof, AbiError> {
+  check_tx_proofs(json_params, OUT_PROOF_HEADER, false, network)
+}
@@ -0,0 +1,531 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use curve25519_dalek::{EdwardsPoint, Scalar};
+use monero_primitives::keccak256;
+use monero_wallet::{
+  ViewPair, address::Network, generators::biased_hash_to_point, transaction::Transaction,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::{
+  base58, check_ring_signature, check_signature, check_tx_proof, generate_ring_signature,
+  generate_signature, generate_tx_proof, key_derivation, output_amount, parse_scalar, read_point,
+  read_scalar, read_transaction, shared_key, subaddress_secret, tx_public_keys,
+};
+use crate::block_parsing::{
+  amount_to_string,
+  view_tags::{read_bytes, read_varint, write_varint},
+};
+use crate::error::AbiError;
+use crate::transaction_building::{
+  inputs::read_output_from_string, transaction::parse_address_for_any_network,
+};
+
+pub const RESERVE_PROOF_HEADER: &str = "ReserveProofV2";
+
+/// the VERSION_FIELD of reserve_proof_entry
+const RESERVE_PROOF_ENTRY_VERSION: u64 = 0;
+/// binary_archive writes a std::pair as an array of 2 elements
+const PAIR_LENGTH: u64 = 2;
+
+/// wallet2's reserve_proof_entry
+struct ReserveProofEntry {
+  tx_hash: [u8; 32],
+  index_in_tx: u64,
+  shared_secret: EdwardsPoint,
+  key_image: EdwardsPoint,
+  shared_secret_signature: (Scalar, Scalar),
+  key_image_signature: (Scalar, Scalar),
+}
+struct ReserveProof {
+  entries: Vec<ReserveProofEntry>,
+  /// spend public key of every (sub)address the outputs belong to, signed with its secret
+  spend_key_signatures: Vec<(EdwardsPoint, (Scalar, Scalar))>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReserveProofParams {
+  /// serialized outputs from the scan results (not spent, not burned)
+  outputs: Vec<String>,
+  /// tx hash -> full transaction hex, for every transaction the outputs are from
+  transactions: HashMap<String, String>,
+  spend_key: String,
+  #[serde(default)]
+  message: String,
+}
+#[derive(Debug, Deserialize)]
+struct CheckReserveProofParams {
+  /// primary address of the wallet that made the proof
+  address: String,
+  #[serde(default)]
+  message: String,
+  signature: String,
+  /// tx hash -> full transaction hex, for every tx_hash of parse_reserve_proof
+  transactions: HashMap<String, String>,
+  /// the key images of parse_reserve_proof that the node (is_key_image_spent) reports as spent
+  #[serde(default)]
+  spent_key_images: Vec<String>,
+}
+#[derive(Debug, Deserialize)]
+struct ParseReserveProofParams {
+  signature: String,
+}
+#[derive(Debug, Serialize)]
+pub struct ReserveProofSignature {
+  signature: String,
+}
+#[derive(Debug, Serialize)]
+pub struct ReserveProofEntryJson {
+  tx_hash: String,
+  index_in_tx: u64,
+  key_image: String,
+}
+#[derive(Debug, Serialize)]
+pub struct ParsedReserveProof {
+  entries: Vec<ReserveProofEntryJson>,
+}
+#[derive(Debug, Serialize)]
+pub struct ProvenOutput {
+  #[serde(flatten)]
+  entry: ReserveProofEntryJson,
+  #[serde(serialize_with = "amount_to_string")]
+  amount: u64,
+  spent: bool,
+}
+#[derive(Debug, Serialize)]
+pub struct CheckedReserveProof {
+  good: bool,
+  #[serde(serialize_with = "amount_to_string")]
+  total: u64,
+  /// the part of the total that is already spent
+  #[serde(serialize_with = "amount_to_string")]
+  spent: u64,
+  outputs: Vec<ProvenOutput>,
+}
+
+fn write_signature((c, r): &(Scalar, Scalar), buf: &mut Vec<u8>) {
+  buf.extend(c.to_bytes());
+  buf.extend(r.to_bytes());
+}
+fn read_signature(reader: &mut &[u8]) -> Result<(Scalar, Scalar), String> {
+  Ok((read_scalar(read_bytes(reader)?)?, read_scalar(read_bytes(reader)?)?))
+}
+
+impl ReserveProof {
+  /// binary_archive serialization of the entries vector and the spend key map,
+  /// every entry starts with its VERSION_FIELD and has a VARINT_FIELD index_in_tx,
+  /// every (key, signature) pair of the map starts with its array length
+  fn encode(&self) -> String {
+    let mut buf = vec![];
+    write_varint(self.entries.len() as u64, &mut buf);
+    for entry in &self.entries {
+      write_varint(RESERVE_PROOF_ENTRY_VERSION, &mut buf);
+      buf.extend(entry.tx_hash);
+      write_varint(entry.index_in_tx, &mut buf);
+      buf.extend(entry.shared_secret.compress().to_bytes());
+      buf.extend(entry.key_image.compress().to_bytes());
+      write_signature(&entry.shared_secret_signature, &mut buf);
+      write_signature(&entry.key_image_signature, &mut buf);
+    }
+    write_varint(self.spend_key_signatures.len() as u64, &mut buf);
+    for (spend_key, signature) in &self.spend_key_signatures {
+      write_varint(PAIR_LENGTH, &mut buf);
+      buf.extend(spend_key.compress().to_bytes());
+      write_signature(signature, &mut buf);
+    }
+    format!("{}{}", RESERVE_PROOF_HEADER, base58::encode(&buf))
+  }
+
+  fn decode(signature: &str) -> Result<ReserveProof, String> {
+    let body = signature
+      .strip_prefix(RESERVE_PROOF_HEADER)
+      .ok_or_else(|| format!("signature does not start with {}", RESERVE_PROOF_HEADER))?;
+    let buf = base58::decode(body).ok_or("invalid base58 in signature")?;
+    let reader = &mut buf.as_slice();
+    let mut entries = vec![];
+    for _ in 0..read_varint(reader)? {
+      let version = read_varint(reader)?;
+      if version != RESERVE_PROOF_ENTRY_VERSION {
+        return Err(format!("unsupported reserve proof entry version {}", version));
+      }
+      entries.push(ReserveProofEntry {
+        tx_hash: read_bytes(reader)?,
+        index_in_tx: read_varint(reader)?,
+        shared_secret: read_point(read_bytes(reader)?)?,
+        key_image: read_point(read_bytes(reader)?)?,
+        shared_secret_signature: read_signature(reader)?,
+        key_image_signature: read_signature(reader)?,
+      });
+    }
+    let mut spend_key_signatures = vec![];
+    for _ in 0..read_varint(reader)? {
+      let length = read_varint(reader)?;
+      if length != PAIR_LENGTH {
+        return Err(format!("spend key signature is an array of {}, not a pair", length));
+      }
+      spend_key_signatures.push((read_point(read_bytes(reader)?)?, read_signature(reader)?));
+    }
+    if !reader.is_empty() {
+      return Err("trailing bytes after the reserve proof".to_string());
+    }
+    Ok(ReserveProof { entries, spend_key_signatures })
+  }
+}
+
+/// the transaction tx_hash of the transactions map of json_params
+fn transaction(
+  transactions: &HashMap<String, String>,
+  tx_hash: &str,
+) -> Result<Transaction, AbiError> {
+  let tx_hex = transactions.get(tx_hash).ok_or_else(|| {
+    AbiError::invalid_input("transactions", format!("transaction {} is missing", tx_hash))
+  })?;
+  read_transaction(&format!("transactions.{}", tx_hash), tx_hex, tx_hash)
+}
+
+/// message || primary spend key || primary view key || key images
+fn reserve_proof_prefix_hash(
+  message: &str,
+  spend_public: &EdwardsPoint,
+  view_public: &EdwardsPoint,
+  entries: &[ReserveProofEntry],
+) -> [u8; 32] {
+  let mut buf = message.as_bytes().to_vec();
+  buf.extend(spend_public.compress().to_bytes());
+  buf.extend(view_public.compress().to_bytes());
+  for entry in entries {
+    buf.extend(entry.key_image.compress().to_bytes());
+  }
+  keccak256(buf)
+}
+
+pub fn get_reserve_proof(
+  json_params: &str,
+  viewpair: &ViewPair,
+  view_key: &Scalar,
+) -> Result<ReserveProofSignature, AbiError> {
+  let params: ReserveProofParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse reserve proof params json: {:?}", e),
+    )
+  })?;
+  if params.outputs.is_empty() {
+    return Err(AbiError::invalid_input("outputs", "a reserve proof needs at least one output"));
+  }
+  let spend_key = Zeroizing::new(parse_scalar("spend_key", &params.spend_key)?);
+  if EdwardsPoint::mul_base(&spend_key) != viewpair.spend() {
+    return Err(AbiError::invalid_input("spend_key", "spend_key does not belong to this wallet"));
+  }
+  let view_public = EdwardsPoint::mul_base(view_key);
+
+  // first pass: the shared secrets and key images, the key images are part of the prefix hash
+  let mut entries = vec![];
+  let mut tx_public_keys_used = vec![];
+  let mut spend_secrets: BTreeMap<[u8; 32], (EdwardsPoint, Zeroizing<Scalar>)> = BTreeMap::new();
+  let mut output_secrets = vec![];
+  for (i, serialized) in params.outputs.iter().enumerate() {
+    let field = format!("outputs[{}]", i);
+    let output = read_output_from_string(serialized, &field)?;
+    let tx_hash = hex::encode(output.transaction());
+    let tx = transaction(&params.transactions, &tx_hash)?;
+    let (tx_public_key, additional_public_keys) = tx_public_keys(&tx)?;
+    let index = output.index_in_transaction() as usize;
+    let subaddress = output.subaddress().map(|index| (index.account(), index.address()));
+    let subaddress_secret = subaddress_secret(view_key, subaddress);
+
+    // find the tx public key the output was derived from
+    let tx_public_key_used = core::iter::once(&tx_public_key)
+      .chain(additional_public_keys.get(index))
+      .find(|tx_public_key| {
+        let derivation = key_derivation(view_key, tx_public_key);
+        shared_key(&derivation, index) + subaddress_secret == output.key_offset()
+      })
+      .ok_or_else(|| {
+        let message = format!("output {}:{} does not belong to this wallet", tx_hash, index);
+        AbiError::invalid_input(&field, message)
+      })?;
+
+    let output_secret = Zeroizing::new(*spend_key + output.key_offset());
+    let key_image = *output_secret * biased_hash_to_point(output.key().compress().to_bytes());
+    let subaddress_spend_secret = Zeroizing::new(*spend_key + subaddress_secret);
+    let subaddress_spend_public = EdwardsPoint::mul_base(&subaddress_spend_secret);
+    spend_secrets.insert(
+      subaddress_spend_public.compress().to_bytes(),
+      (subaddress_spend_public, subaddress_spend_secret),
+    );
+    entries.push(ReserveProofEntry {
+      tx_hash: output.transaction(),
+      index_in_tx: index as u64,
+      shared_secret: view_key * tx_public_key_used,
+      key_image,
+      shared_secret_signature: (Scalar::ZERO, Scalar::ZERO),
+      key_image_signature: (Scalar::ZERO, Scalar::ZERO),
+    });
+    tx_public_keys_used.push(*tx_public_key_used);
+    output_secrets.push((output.key(), output_secret));
+  }
+
+  let prefix_hash =
+    reserve_proof_prefix_hash(&params.message, &viewpair.spend(), &view_public, &entries);
+  for ((entry, tx_public_key), (output_key, output_secret)) in
+    entries.iter_mut().zip(&tx_public_keys_used).zip(&output_secrets)
+  {
+    entry.shared_secret_signature = generate_tx_proof(
+      &prefix_hash,
+      &view_public,
+      tx_public_key,
+      None,
+      &entry.shared_secret,
+      view_key,
+    );
+    // signed over the key image itself, like wallet2's (const crypto::hash&)proof.key_image
+    let key_image_hash = entry.key_image.compress().to_bytes();
+    let ring = [*output_key];
+    entry.key_image_signature =
+      generate_ring_signature(&key_image_hash, &entry.key_image, &ring, output_secret, 0)[0];
+  }
+  let spend_key_signatures = spend_secrets
+    .into_values()
+    .map(|(public, secret)| (public, generate_signature(&prefix_hash, &public, &secret)))
+    .collect();
+  Ok(ReserveProofSignature { signature: ReserveProof { entries, spend_key_signatures }.encode() })
+}
+
+/// the transactions and key images a verifier has to fetch before calling check_reserve_proof
+pub fn parse_reserve_proof(json_params: &str) -> Result<ParsedReserveProof, AbiError> {
+  let params: ParseReserveProofParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input("json_params", format!("failed to parse reserve proof json: {:?}", e))
+  })?;
+  let proof = ReserveProof::decode(&params.signature)
+    .map_err(|message| AbiError::invalid_input("signature", message))?;
+  Ok(ParsedReserveProof { entries: proof.entries.iter().map(entry_json).collect() })
+}
+fn entry_json(entry: &ReserveProofEntry) -> ReserveProofEntryJson {
+  ReserveProofEntryJson {
+    tx_hash: hex::encode(entry.tx_hash),
+    index_in_tx: entry.index_in_tx,
+    key_image: hex::encode(entry.key_image.compress().to_bytes()),
+  }
+}
+
+pub fn check_reserve_proof(
+  json_params: &str,
+  network: Option<Network>,
+) -> Result<CheckedReserveProof, AbiError> {
+  let params: CheckReserveProofParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse check reserve proof params json: {:?}", e),
+    )
+  })?;
+  let address = parse_address_for_any_network(&params.address, network)?;
+  if address.is_subaddress() {
+    return Err(AbiError::invalid_input(
+      "address",
+      "a reserve proof is checked against the primary address",
+    ));
+  }
+  let proof = ReserveProof::decode(&params.signature)
+    .map_err(|message| AbiError::invalid_input("signature", message))?;
+  let prefix_hash =
+    reserve_proof_prefix_hash(&params.message, &address.spend(), &address.view(), &proof.entries);
+  let not_good = || CheckedReserveProof { good: false, total: 0, spent: 0, outputs: vec![] };
+
+  // every output counts once, a repeated entry would add its amount to the total again
+  let mut outputs_seen = HashSet::new();
+  let mut key_images_seen = HashSet::new();
+  for entry in &proof.entries {
+    if !outputs_seen.insert((entry.tx_hash, entry.index_in_tx))
+      || !key_images_seen.insert(entry.key_image.compress().to_bytes())
+    {
+      return Ok(not_good());
+    }
+  }
+
+  let spend_keys: Vec<[u8; 32]> =
+    proof.spend_key_signatures.iter().map(|(key, _)| key.compress().to_bytes()).collect();
+  let mut outputs = vec![];
+  for entry in &proof.entries {
+    let tx_hash = hex::encode(entry.tx_hash);
+    let tx = transaction(&params.transactions, &tx_hash)?;
+    let index = entry.index_in_tx as usize;
+    let no_output = || {
+      let message = format!("transaction {} has no valid output {}", tx_hash, index);
+      AbiError::invalid_input("signature", message)
+    };
+    let output_key = tx
+      .prefix()
+      .outputs
+      .get(index)
+      .and_then(|output| output.key.decompress())
+      .ok_or_else(no_output)?;
+
+    let (tx_public_key, additional_public_keys) = tx_public_keys(&tx)?;
+    let shared_secret_good = core::iter::once(&tx_public_key)
+      .chain(additional_public_keys.get(index))
+      .any(|tx_public_key| {
+        check_tx_proof(
+          &prefix_hash,
+          &address.view(),
+          tx_public_key,
+          None,
+          &entry.shared_secret,
+          entry.shared_secret_signature,
+        )
+      });
+    if !shared_secret_good
+      || !check_ring_signature(
+        &entry.key_image.compress().to_bytes(),
+        &entry.key_image,
+        &[output_key],
+        &[entry.key_image_signature],
+      )
+    {
+      return Ok(not_good());
+    }
+
+    // the output has to belong to one of the signed spend keys
+    let derivation = entry.shared_secret.mul_by_cofactor();
+    let shared_key = shared_key(&derivation, index);
+    let spend_key = output_key - EdwardsPoint::mul_base(&shared_key);
+    if !spend_keys.contains(&spend_key.compress().to_bytes()) {
+      return Ok(not_good());
+    }
+    let amount = output_amount(&tx, index, &shared_key)?;
+    let entry = entry_json(entry);
+    let spent = params.spent_key_images.contains(&entry.key_image);
+    outputs.push(ProvenOutput { entry, amount, spent });
+  }
+  for (spend_key, signature) in &proof.spend_key_signatures {
+    if !check_signature(&prefix_hash, spend_key, *signature) {
+      return Ok(not_good());
+    }
+  }
+
+  let mut total: u64 = 0;
+  let mut spent: u64 = 0;
+  for output in &outputs {
+    total = total.checked_add(output.amount).ok_or("total overflows")?;
+    if output.spent {
+      spent += output.amount;
+    }
+  }
+  Ok(CheckedReserveProof { good: true, total, spent, outputs })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{XMR, fixture, keys, payment, received, sign};
+  use monero_wallet::{
+    address::{Network, SubaddressIndex},
+    send::Change,
+  };
+  use serde_json::{Value, json};
+
+  /// keys(7) pays 1 XMR to the primary address and 2 XMR to subaddress 1/2 of keys(9),
+  /// the reserve proof of keys(9) for both outputs and the params to check it
+  fn prove() -> (String, Value) {
+    prove_outputs(|outputs| outputs)
+  }
+  /// as prove, with the list of serialized outputs the proof is made for changed by f
+  fn prove_outputs(f: impl FnOnce(Vec<String>) -> Vec<String>) -> (String, Value) {
+    let wallet = keys(9);
+    let index = SubaddressIndex::new(1, 2).unwrap();
+    let payments = vec![
+      (wallet.viewpair().legacy_address(Network::Mainnet), XMR),
+      (wallet.viewpair().subaddress(Network::Mainnet, index), 2 * XMR),
+    ];
+    let tx = sign(&keys(7), payment(&keys(7), payments, Change::new(keys(7).viewpair(), None)));
+    let outputs: Vec<String> = received(&wallet, &[index], &tx)
+      .iter()
+      .map(|output| hex::encode(output.serialize()))
+      .collect();
+    assert_eq!(outputs.len(), 2);
+    let outputs = f(outputs);
+    let mut transactions = serde_json::Map::new();
+    transactions.insert(hex::encode(tx.hash()), json!(hex::encode(tx.serialize())));
+    let params = json!({
+      "outputs": outputs,
+      "transactions": transactions,
+      "spend_key": wallet.spend_key_hex(),
+      "message": "reserves",
+    });
+    let proof = get_reserve_proof(&params.to_string(), &wallet.viewpair(), &wallet.view_key);
+    let signature = proof.unwrap().signature;
+    let params = json!({
+      "address": wallet.primary_address(),
+      "message": "reserves",
+      "signature": signature,
+      "transactions": transactions,
+    });
+    (signature, params)
+  }
+
+  #[test]
+  fn proves_outputs_of_the_primary_address_and_subaddresses() {
+    let (_, params) = prove();
+    let checked = check_reserve_proof(&params.to_string(), Some(Network::Mainnet)).unwrap();
+    assert!(checked.good);
+    assert_eq!(checked.total, 3 * XMR);
+    assert_eq!(checked.spent, 0);
+  }
+
+  #[test]
+  fn reports_spent_outputs() {
+    let (signature, mut params) = prove();
+    let parsed = parse_reserve_proof(&json!({ "signature": signature }).to_string()).unwrap();
+    assert_eq!(parsed.entries.len(), 2);
+    params["spent_key_images"] = json!([parsed.entries[0].key_image]);
+    let checked = check_reserve_proof(&params.to_string(), Some(Network::Mainnet)).unwrap();
+    assert!(checked.good);
+    assert_eq!(checked.spent, checked.outputs[0].amount);
+  }
+
+  #[test]
+  fn proofs_only_hold_for_their_message() {
+    let (_, mut params) = prove();
+    params["message"] = json!("no reserves");
+    assert!(!check_reserve_proof(&params.to_string(), Some(Network::Mainnet)).unwrap().good);
+  }
+
+  #[test]
+  fn outputs_are_counted_once() {
+    // a prover holding the keys can sign a proof that lists the same output twice
+    let (_, params) = prove_outputs(|outputs| vec![outputs[0].clone(), outputs[0].clone()]);
+    let checked = check_reserve_proof(&params.to_string(), Some(Network::Mainnet)).unwrap();
+    assert!(!checked.good);
+    assert_eq!(checked.total, 0);
+  }
+
+  #[test]
+  fn entries_serialize_like_wallet2() {
+    let (signature, _) = prove();
+    let proof = ReserveProof::decode(&signature).unwrap();
+    let bytes = base58::decode(&signature[RESERVE_PROOF_HEADER.len()..]).unwrap();
+    // entry count, then the version and txid of the first entry, then its varint index
+    assert_eq!(bytes[0], 2);
+    assert_eq!(bytes[1] as u64, RESERVE_PROOF_ENTRY_VERSION);
+    assert_eq!(bytes[2..34], proof.entries[0].tx_hash);
+    assert_eq!(bytes[34] as u64, proof.entries[0].index_in_tx);
+    // version, txid, index, shared secret, key image and the two signatures of each entry,
+    // then the spend key map: its length and every pair as an array of 2
+    let spend_key_map = &bytes[1 + 2 * (1 + 32 + 1 + 32 + 32 + 64 + 64)..];
+    assert_eq!(spend_key_map[0] as usize, proof.spend_key_signatures.len());
+    assert_eq!(spend_key_map[1] as u64, PAIR_LENGTH);
+    assert_eq!(spend_key_map[2..34], proof.spend_key_signatures[0].0.compress().to_bytes());
+    assert_eq!(spend_key_map.len(), proof.spend_key_signatures.len() * (1 + 32 + 64) + 1);
+    assert_eq!(proof.encode(), signature);
+  }
+
+  #[test]
+  #[ignore = "needs tests/fixtures/reserve_proof.json, see typescript/scripts/stagenet_fixtures.ts"]
+  fn checks_wallet_cli_proofs() {
+    let fixture = fixture("reserve_proof.json");
+    let checked = check_reserve_proof(&fixture.to_string(), Some(Network::Stagenet)).unwrap();
+    assert!(checked.good);
+    assert_eq!(checked.total.to_string(), fixture["total"].as_str().unwrap());
+  }
+}
@@ -0,0 +1,237 @@
+use cuprate_rpc_types::bin::GetOutsResponse;
+use curve25519_dalek::EdwardsPoint;
+use monero_wallet::{generators::biased_hash_to_point, transaction::Input};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::{
+  ENCODED_SIGNATURE_LEN, base58, check_ring_signature, generate_ring_signature, parse_scalar,
+  parse_tx_hash, prefix_hash, read_point, read_scalar, read_transaction,
+};
+use crate::error::AbiError;
+use crate::transaction_building::inputs::read_output_from_string;
+
+pub const SPEND_PROOF_HEADER: &str = "SpendProofV1";
+
+#[derive(Debug, Deserialize)]
+struct SpendProofParams {
+  tx_hash: String,
+  /// the full transaction (get_transactions as_hex)
+  transaction: String,
+  #[serde(default)]
+  message: String,
+  /// serialized outputs from the scan results that this transaction spent
+  outputs: Vec<String>,
+}
+#[derive(Debug, Deserialize)]
+struct CheckSpendProofParams {
+  tx_hash: String,
+  transaction: String,
+  #[serde(default)]
+  message: String,
+  signature: String,
+}
+#[derive(Debug, Serialize)]
+pub struct CheckedSpendProof {
+  good: bool,
+}
+
+/// key image and ring of every input, the ring members are taken from a getouts.bin response
+/// for the ring_member_indices of decode_transaction (input after input)
+fn inputs_with_rings(
+  tx: &monero_wallet::transaction::Transaction,
+  ring_members: GetOutsResponse,
+) -> Result<Vec<(EdwardsPoint, Vec<EdwardsPoint>)>, AbiError> {
+  let invalid_response = |message: &str| AbiError::invalid_input("getouts_response", message);
+  let mut outs = ring_members.outs.into_iter();
+  let mut inputs = vec![];
+  for input in &tx.prefix().inputs {
+    let Input::ToKey { key_offsets, key_image, .. } = input else {
+      return Err(AbiError::invalid_input(
+        "transaction",
+        "coinbase transactions have no spend proof",
+      ));
+    };
+    let key_image = key_image
+      .decompress()
+      .ok_or_else(|| AbiError::invalid_input("transaction", "invalid key image"))?;
+    let mut ring = Vec::with_capacity(key_offsets.len());
+    for out in outs.by_ref().take(key_offsets.len()) {
+      ring.push(read_point(out.key).map_err(|e| invalid_response(&e))?);
+    }
+    if ring.len() != key_offsets.len() {
+      return Err(invalid_response(
+        "getouts.bin response has fewer ring members than the transaction",
+      ));
+    }
+    inputs.push((key_image, ring));
+  }
+  if outs.next().is_some() {
+    return Err(invalid_response(
+      "getouts.bin response has more ring members than the transaction",
+    ));
+  }
+  Ok(inputs)
+}
+
+/// wallet2's get_spend_proof: a ring signature over hash(tx hash || message) for every input
+pub fn get_spend_proof(
+  json_params: &str,
+  spend_key: &str,
+  ring_members: GetOutsResponse,
+) -> Result<String, AbiError> {
+  let params: SpendProofParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse spend proof params json: {:?}", e),
+    )
+  })?;
+  let spend_key = Zeroizing::new(parse_scalar("secret_spend_key", spend_key)?);
+  let prefix_hash = prefix_hash(&parse_tx_hash(&params.tx_hash)?, &params.message);
+  let tx = read_transaction("transaction", &params.transaction, &params.tx_hash)?;
+
+  // the secret key and key image of every output we could have spent
+  let mut owned = vec![];
+  for (i, serialized) in params.outputs.iter().enumerate() {
+    let output = read_output_from_string(serialized, &format!("outputs[{}]", i))?;
+    let secret = Zeroizing::new(*spend_key + output.key_offset());
+    let key_image = *secret * biased_hash_to_point(output.key().compress().to_bytes());
+    owned.push((output.key(), key_image, secret));
+  }
+
+  let mut signature = SPEND_PROOF_HEADER.to_string();
+  for (key_image, ring) in inputs_with_rings(&tx, ring_members)? {
+    let (output_key, _, secret) = owned
+      .iter()
+      .find(|(_, owned_key_image, _)| *owned_key_image == key_image)
+      .ok_or("this transaction was not sent by this wallet (or an output is missing)")?;
+    let secret_index = ring
+      .iter()
+      .position(|member| member == output_key)
+      .ok_or("the spent output is not part of the ring, wrong getouts.bin response?")?;
+    for (c, r) in generate_ring_signature(&prefix_hash, &key_image, &ring, secret, secret_index) {
+      let mut bytes = c.to_bytes().to_vec();
+      bytes.extend(r.to_bytes());
+      signature.push_str(&base58::encode(&bytes));
+    }
+  }
+  Ok(signature)
+}
+
+pub fn check_spend_proof(
+  json_params: &str,
+  ring_members: GetOutsResponse,
+) -> Result<CheckedSpendProof, AbiError> {
+  let params: CheckSpendProofParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse check spend proof params json: {:?}", e),
+    )
+  })?;
+  let prefix_hash = prefix_hash(&parse_tx_hash(&params.tx_hash)?, &params.message);
+  let tx = read_transaction("transaction", &params.transaction, &params.tx_hash)?;
+  let invalid_signature = |message: String| AbiError::invalid_input("signature", message);
+  let body = params.signature.strip_prefix(SPEND_PROOF_HEADER).ok_or_else(|| {
+    invalid_signature(format!("signature does not start with {}", SPEND_PROOF_HEADER))
+  })?;
+  let inputs = inputs_with_rings(&tx, ring_members)?;
+  let signature_count: usize = inputs.iter().map(|(_, ring)| ring.len()).sum();
+  if !body.is_ascii() || body.len() != signature_count * ENCODED_SIGNATURE_LEN {
+    return Ok(CheckedSpendProof { good: false });
+  }
+
+  let mut signatures = vec![];
+  for i in (0..body.len()).step_by(ENCODED_SIGNATURE_LEN) {
+    let bytes = base58::decode(&body[i..i + ENCODED_SIGNATURE_LEN])
+      .filter(|bytes| bytes.len() == 64)
+      .ok_or_else(|| invalid_signature("invalid base58 in signature".to_string()))?;
+    let c: [u8; 32] = bytes[..32].try_into().unwrap(); // length checked above
+    let r: [u8; 32] = bytes[32..].try_into().unwrap();
+    signatures.push((
+      read_scalar(c).map_err(invalid_signature)?,
+      read_scalar(r).map_err(invalid_signature)?,
+    ));
+  }
+  let mut signatures = signatures.as_slice();
+  for (key_image, ring) in inputs {
+    let (ring_signature, rest) = signatures.split_at(ring.len());
+    if !check_ring_signature(&prefix_hash, &key_image, &ring, ring_signature) {
+      return Ok(CheckedSpendProof { good: false });
+    }
+    signatures = rest;
+  }
+  Ok(CheckedSpendProof { good: true })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{XMR, fixture, keys, output_json, payment, ring_members, sign};
+  use cuprate_epee_encoding::from_bytes;
+  use monero_wallet::{address::Network, send::Change, transaction::Transaction};
+  use serde_json::{Value, json};
+
+  /// keys(7) pays keys(9)
+  fn sent() -> Transaction {
+    let payer = keys(7);
+    let recipient = keys(9).viewpair().legacy_address(Network::Mainnet);
+    sign(&payer, payment(&payer, vec![(recipient, XMR)], Change::new(payer.viewpair(), None)))
+  }
+  /// the spend proof of keys(7) for sent and the params to check it
+  fn prove(message: &str) -> (String, Value) {
+    let payer = keys(7);
+    let tx = sent();
+    let spent = output_json(&payer, 5 * XMR, 10, 100, true);
+    let params = json!({
+      "tx_hash": hex::encode(tx.hash()),
+      "transaction": hex::encode(tx.serialize()),
+      "message": message,
+      "outputs": [spent["serialized"]],
+    });
+    let signature =
+      get_spend_proof(&params.to_string(), &payer.spend_key_hex(), ring_members(&payer)).unwrap();
+    let params = json!({
+      "tx_hash": params["tx_hash"],
+      "transaction": params["transaction"],
+      "message": message,
+      "signature": signature,
+    });
+    (signature, params)
+  }
+
+  #[test]
+  fn proves_spends() {
+    let (signature, params) = prove("sent");
+    assert!(signature.starts_with(SPEND_PROOF_HEADER));
+    assert!(check_spend_proof(&params.to_string(), ring_members(&keys(7))).unwrap().good);
+  }
+
+  #[test]
+  fn proofs_only_hold_for_their_message() {
+    let (_, mut params) = prove("sent");
+    params["message"] = json!("not sent");
+    assert!(!check_spend_proof(&params.to_string(), ring_members(&keys(7))).unwrap().good);
+  }
+
+  #[test]
+  fn needs_the_spent_outputs() {
+    let payer = keys(7);
+    let tx = sent();
+    let params = json!({
+      "tx_hash": hex::encode(tx.hash()),
+      "transaction": hex::encode(tx.serialize()),
+      "outputs": [],
+    });
+    let proof = get_spend_proof(&params.to_string(), &payer.spend_key_hex(), ring_members(&payer));
+    assert!(proof.is_err());
+  }
+
+  #[test]
+  #[ignore = "needs tests/fixtures/spend_proof.json, see typescript/scripts/stagenet_fixtures.ts"]
+  fn checks_wallet_cli_proofs() {
+    let fixture = fixture("spend_proof.json");
+    let response = hex::decode(fixture["ring_members"].as_str().unwrap()).unwrap();
+    let ring_members = from_bytes::<GetOutsResponse, _>(&mut response.as_slice()).unwrap();
+    assert!(check_spend_proof(&fixture.to_string(), ring_members).unwrap().good);
+  }
+}
@@ -0,0 +1,220 @@
+use core::cmp::Reverse;
+
+use curve25519_dalek::{EdwardsPoint, Scalar};
+use monero_primitives::keccak256;
+use monero_wallet::address::Network;
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::{key_derivation, parse_scalar, parse_tx_hash, read_transaction, received_amount};
+use crate::block_parsing::amount_to_string;
+use crate::error::AbiError;
+use crate::transaction_building::inspect::{SerializedChange, decode};
+use crate::transaction_building::transaction::{parse_address, parse_address_for_any_network};
+
+#[derive(Debug, Deserialize)]
+struct GetTxKeyParams {
+  /// the SignableTransaction the transaction was signed from, it contains the outgoing view key
+  signable_transaction: String,
+}
+#[derive(Debug, Serialize)]
+pub struct TxKey {
+  /// tx private key followed by the additional tx private keys, as wallet2's get_tx_key
+  tx_key: String,
+}
+#[derive(Debug, Deserialize)]
+struct CheckTxKeyParams {
+  tx_hash: String,
+  tx_key: String,
+  address: String,
+  /// the full transaction (get_transactions as_hex)
+  transaction: String,
+}
+#[derive(Debug, Serialize)]
+pub struct CheckTxKey {
+  #[serde(serialize_with = "amount_to_string")]
+  received: u64,
+}
+
+/// the tx keys as SignableTransaction derives them: a ChaCha20 rng seeded with the
+/// outgoing view key and the (sorted) input keys, the first scalar is the tx key,
+/// the next ones are the additional keys, one per payment
+fn transaction_keys(
+  outgoing_view_key: &[u8],
+  mut input_keys: Vec<EdwardsPoint>,
+  additional_keys: usize,
+) -> (Zeroizing<Scalar>, Vec<Zeroizing<Scalar>>) {
+  let dst = b"transaction_keys";
+  let mut transcript = Zeroizing::new(vec![dst.len() as u8]);
+  transcript.extend(dst);
+  transcript.extend(outgoing_view_key);
+  // monero-oxide's key_image_sort: descending by the compressed key
+  input_keys.sort_by_key(|key| Reverse(key.compress().to_bytes()));
+  for key in input_keys {
+    transcript.extend(key.compress().to_bytes());
+  }
+  let mut rng = ChaCha20Rng::from_seed(keccak256(transcript.as_slice()));
+  let tx_key = Zeroizing::new(Scalar::random(&mut rng));
+  let additional = (0..additional_keys).map(|_| Zeroizing::new(Scalar::random(&mut rng))).collect();
+  (tx_key, additional)
+}
+
+pub fn get_tx_key(json_params: &str) -> Result<TxKey, AbiError> {
+  let params: GetTxKeyParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse get_tx_key params json: {:?}", e),
+    )
+  })?;
+  let invalid = |message: String| AbiError::invalid_input("signable_transaction", message);
+  let bytes = hex::decode(&params.signable_transaction)
+    .map_err(|e| invalid(format!("failed to decode signable transaction hex: {:?}", e)))?;
+  let outgoing_view_key =
+    bytes.get(1..33).ok_or_else(|| invalid("signable transaction is too short".to_string()))?;
+  let (inputs, payments, change, _) = decode(&bytes).map_err(invalid)?;
+  // the addresses come out of the signable transaction, not out of json_params
+  let read_address =
+    |address: &str| parse_address(address).map_err(|e| e.with_field("signable_transaction"));
+
+  // should_use_additional_keys of SignableTransaction: additional keys are used when paying a
+  // subaddress, unless the only other output is change we hold the view key for. change with a
+  // view key is never a payment to a subaddress, its shared key is computed with the view key
+  let mut to_subaddress = false;
+  for (address, _) in &payments {
+    to_subaddress |= read_address(address)?.is_subaddress();
+  }
+  let change_view_key = matches!(change, Some(SerializedChange::ViewPair(..)));
+  if let Some(SerializedChange::AddressOnly(address)) = &change {
+    to_subaddress |= read_address(address)?.is_subaddress();
+  }
+  let outputs = payments.len() + usize::from(change.is_some());
+  let additional_keys =
+    if to_subaddress && !(outputs == 2 && change_view_key) { outputs } else { 0 };
+
+  let (tx_key, additional) = transaction_keys(
+    outgoing_view_key,
+    inputs.iter().map(|input| input.key()).collect(),
+    additional_keys,
+  );
+  let mut encoded = hex::encode(tx_key.to_bytes());
+  for key in additional {
+    encoded.push_str(&hex::encode(key.to_bytes()));
+  }
+  Ok(TxKey { tx_key: encoded })
+}
+
+/// splits wallet2's tx key format: 64 hex characters per key, the first one is the tx key
+pub(crate) fn parse_tx_key(tx_key: &str) -> Result<(Scalar, Vec<Scalar>), AbiError> {
+  if tx_key.is_empty() || tx_key.len() % 64 != 0 || !tx_key.is_ascii() {
+    return Err(AbiError::invalid_input(
+      "tx_key",
+      "tx_key must be a multiple of 64 hex characters",
+    ));
+  }
+  let mut keys = (0..tx_key.len()).step_by(64).map(|i| parse_scalar("tx_key", &tx_key[i..i + 64]));
+  let first = keys.next().unwrap()?; // not empty, checked above
+  Ok((first, keys.collect::<Result<Vec<Scalar>, AbiError>>()?))
+}
+
+/// how much a transaction sent to an address, using the tx key the sender kept
+pub fn check_tx_key(json_params: &str, network: Option<Network>) -> Result<CheckTxKey, AbiError> {
+  let params: CheckTxKeyParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse check_tx_key params json: {:?}", e),
+    )
+  })?;
+  let address = parse_address_for_any_network(&params.address, network)?;
+  parse_tx_hash(&params.tx_hash)?;
+  let tx = read_transaction("transaction", &params.transaction, &params.tx_hash)?;
+  let (tx_key, additional_keys) = parse_tx_key(&params.tx_key)?;
+
+  let derivation = key_derivation(&tx_key, &address.view());
+  let additional_derivations: Vec<EdwardsPoint> =
+    additional_keys.iter().map(|key| key_derivation(key, &address.view())).collect();
+  Ok(CheckTxKey {
+    received: received_amount(&tx, Some(&derivation), &additional_derivations, &address)?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{XMR, keys, payment_with_inputs, sign};
+  use monero_wallet::{
+    address::{MoneroAddress, SubaddressIndex},
+    send::Change,
+  };
+  use serde_json::json;
+
+  fn subaddress(seed: u64, minor: u32) -> MoneroAddress {
+    keys(seed).viewpair().subaddress(Network::Mainnet, SubaddressIndex::new(0, minor).unwrap())
+  }
+  /// the tx key get_tx_key derives for the transaction, checked against the signed transaction
+  /// with check_tx_key for every payment. returns how many keys the tx key has
+  fn tx_key_count(payments: Vec<(MoneroAddress, u64)>, change: Change) -> usize {
+    tx_key_count_with_inputs(&[5 * XMR], payments, change)
+  }
+  fn tx_key_count_with_inputs(
+    input_amounts: &[u64],
+    payments: Vec<(MoneroAddress, u64)>,
+    change: Change,
+  ) -> usize {
+    let tx = payment_with_inputs(&keys(7), input_amounts, payments.clone(), change);
+    let params = json!({ "signable_transaction": hex::encode(tx.serialize()) });
+    let tx_key = get_tx_key(&params.to_string()).unwrap().tx_key;
+    let signed = sign(&keys(7), tx);
+
+    for (address, amount) in payments {
+      let params = json!({
+        "tx_hash": hex::encode(signed.hash()),
+        "tx_key": tx_key,
+        "address": address.to_string(),
+        "transaction": hex::encode(signed.serialize()),
+      });
+      let checked = check_tx_key(&params.to_string(), Some(Network::Mainnet)).unwrap();
+      assert_eq!(checked.received, amount);
+    }
+    tx_key.len() / 64
+  }
+
+  #[test]
+  fn no_additional_keys_without_subaddress_payments() {
+    let recipient = keys(9).viewpair().legacy_address(Network::Mainnet);
+    let change = Change::new(keys(7).viewpair(), SubaddressIndex::new(0, 3));
+    assert_eq!(tx_key_count(vec![(recipient, XMR)], change), 1);
+  }
+
+  #[test]
+  fn change_to_a_subaddress_with_its_view_key_needs_no_additional_keys() {
+    let change = Change::new(keys(7).viewpair(), SubaddressIndex::new(1, 1));
+    assert_eq!(tx_key_count(vec![(subaddress(9, 1), XMR)], change), 1);
+  }
+
+  #[test]
+  fn payments_to_subaddresses_use_additional_keys() {
+    let change = Change::new(keys(7).viewpair(), None);
+    let payments = vec![(subaddress(9, 1), XMR), (subaddress(11, 2), 2 * XMR)];
+    assert_eq!(tx_key_count(payments, change), 1 + 3);
+  }
+
+  #[test]
+  fn address_only_change_to_a_subaddress_uses_additional_keys() {
+    let recipient = keys(9).viewpair().legacy_address(Network::Mainnet);
+    let change = Change::fingerprintable(Some(subaddress(7, 5)));
+    assert_eq!(tx_key_count(vec![(recipient, XMR)], change), 1 + 2);
+  }
+
+  #[test]
+  fn the_tx_key_of_several_inputs() {
+    // the rng is seeded with the input keys in key image order
+    let change = Change::new(keys(7).viewpair(), None);
+    let inputs = [XMR, 2 * XMR, 3 * XMR, 4 * XMR];
+    assert_eq!(tx_key_count_with_inputs(&inputs, vec![(subaddress(9, 1), 5 * XMR)], change), 1);
+    let change = Change::new(keys(7).viewpair(), None);
+    let payments = vec![(subaddress(9, 1), XMR), (subaddress(11, 2), 2 * XMR)];
+    assert_eq!(tx_key_count_with_inputs(&inputs, payments, change), 1 + 3);
+  }
+}
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::AbiError;
+
+/// body of a /send_raw_transaction request (json rpc, not binary like getblocks.bin)
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SendRawTransactionRequest {
+  tx_as_hex: String,
+  /// check the transaction and add it to the pool of the node without broadcasting it
+  #[serde(default)]
+  do_not_relay: bool,
+  #[serde(default = "default_do_sanity_checks")]
+  do_sanity_checks: bool,
+}
+fn default_do_sanity_checks() -> bool {
+  true
+}
+
+/// the fields of a /send_raw_transaction response, every flag defaults to false
+/// because older daemons don't send all of them
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct SendRawTransactionResponse {
+  status: String,
+  reason: String,
+  double_spend: bool,
+  fee_too_low: bool,
+  invalid_input: bool,
+  invalid_output: bool,
+  low_mixin: bool,
+  not_relayed: bool,
+  overspend: bool,
+  too_big: bool,
+  too_few_outputs: bool,
+  sanity_check_failed: bool,
+  tx_extra_too_big: bool,
+  nonzero_unlock_time: bool,
+  untrusted: bool,
+}
+#[derive(Serialize, Debug)]
+pub struct RelayResult {
+  /// false if do_not_relay was set (or the node decided not to relay)
+  relayed: bool,
+  /// the node is bootstrapping and relied on a remote node for the answer
+  untrusted: bool,
+}
+
+/// why the node rejected a transaction, the codes are stable and meant to be matched on
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum RelayError {
+  DoubleSpend,
+  FeeTooLow,
+  TooBig,
+  InvalidInput,
+  InvalidOutput,
+  LowMixin,
+  Overspend,
+  TooFewOutputs,
+  SanityCheckFailed,
+  TxExtraTooBig,
+  NonzeroUnlockTime,
+  /// the node is still syncing
+  Busy,
+  /// rejected without one of the flags above, reason is whatever the node sent
+  Failed {
+    reason: String,
+  },
+  /// the response could not be parsed
+  InvalidResponse {
+    reason: String,
+  },
+}
+
+impl RelayError {
+  pub fn message(&self) -> String {
+    match self {
+      RelayError::DoubleSpend => "one of the inputs was already spent".to_string(),
+      RelayError::FeeTooLow => "the fee is too low".to_string(),
+      RelayError::TooBig => "the transaction is too big".to_string(),
+      RelayError::InvalidInput => "the transaction has an invalid input".to_string(),
+      RelayError::InvalidOutput => "the transaction has an invalid output".to_string(),
+      RelayError::LowMixin => "the ring size is too small".to_string(),
+      RelayError::Overspend => "the outputs are worth more than the inputs".to_string(),
+      RelayError::TooFewOutputs => "the transaction has too few outputs".to_string(),
+      RelayError::SanityCheckFailed => "the transaction failed the sanity checks".to_string(),
+      RelayError::TxExtraTooBig => "the tx extra is too big".to_string(),
+      RelayError::NonzeroUnlockTime => "the transaction has an unlock time".to_string(),
+      RelayError::Busy => "the node is busy syncing".to_string(),
+      RelayError::Failed { reason } => format!("the node rejected the transaction: {}", reason),
+      RelayError::InvalidResponse { reason } => {
+        format!("failed to parse send_raw_transaction response: {}", reason)
+      }
+    }
+  }
+}
+
+pub fn build_send_raw_transaction_request(json_params: &str) -> Result<String, AbiError> {
+  let request: SendRawTransactionRequest = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse send_raw_transaction params json: {}", e),
+    )
+  })?;
+  hex::decode(&request.tx_as_hex).map_err(|e| {
+    AbiError::invalid_input(
+      "tx_as_hex",
+      format!("tx_as_hex is not a hex encoded transaction: {:?}", e),
+    )
+  })?;
+  let request = serde_json::to_string(&request)
+    .map_err(|e| format!("failed to serialize send_raw_transaction request: {}", e))?;
+  Ok(request)
+}
+
+pub fn parse_send_raw_transaction_response(response: &str) -> Result<RelayResult, RelayError> {
+  let response: SendRawTransactionResponse = serde_json::from_str(response)
+    .map_err(|e| RelayError::InvalidResponse { reason: e.to_string() })?;
+  if response.status == "OK" {
+    return Ok(RelayResult { relayed: !response.not_relayed, untrusted: response.untrusted });
+  }
+  if response.status == "BUSY" {
+    return Err(RelayError::Busy);
+  }
+  // the daemon can set several flags, the first one is the most specific
+  let flags = [
+    (response.double_spend, RelayError::DoubleSpend),
+    (response.overspend, RelayError::Overspend),
+    (response.fee_too_low, RelayError::FeeTooLow),
+    (response.too_big, RelayError::TooBig),
+    (response.low_mixin, RelayError::LowMixin),
+    (response.invalid_input, RelayError::InvalidInput),
+    (response.invalid_output, RelayError::InvalidOutput),
+    (response.too_few_outputs, RelayError::TooFewOutputs),
+    (response.tx_extra_too_big, RelayError::TxExtraTooBig),
+    (response.nonzero_unlock_time, RelayError::NonzeroUnlockTime),
+    (response.sanity_check_failed, RelayError::SanityCheckFailed),
+  ];
+  match flags.into_iter().find(|(flag, _)| *flag) {
+    Some((_, error)) => Err(error),
+    None => Err(RelayError::Failed {
+      reason: if response.reason.is_empty() { response.status } else { response.reason },
+    }),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::error::INVALID_INPUT;
+  use serde_json::{Value, json};
+
+  /// a monerod 0.18 response, with the flags set in flags
+  fn response(status: &str, reason: &str, flags: &[&str]) -> String {
+    let mut response = json!({
+      "credits": 0,
+      "double_spend": false,
+      "fee_too_low": false,
+      "invalid_input": false,
+      "invalid_output": false,
+      "low_mixin": false,
+      "nonzero_unlock_time": false,
+      "not_relayed": false,
+      "overspend": false,
+      "reason": reason,
+      "sanity_check_failed": false,
+      "status": status,
+      "too_big": false,
+      "too_few_outputs": false,
+      "top_hash": "",
+      "tx_extra_too_big": false,
+      "untrusted": false,
+    });
+    for flag in flags {
+      response[*flag] = json!(true);
+    }
+    response.to_string()
+  }
+
+  #[test]
+  fn builds_the_request_with_defaults() {
+    let request = build_send_raw_transaction_request(r#"{"tx_as_hex":"0200"}"#).unwrap();
+    let request: Value = serde_json::from_str(&request).unwrap();
+    assert_eq!(
+      request,
+      json!({ "tx_as_hex": "0200", "do_not_relay": false, "do_sanity_checks": true })
+    );
+    let params = json!({ "tx_as_hex": "0200", "do_not_relay": true, "do_sanity_checks": false });
+    let request = build_send_raw_transaction_request(&params.to_string()).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&request).unwrap(), params);
+  }
+
+  #[test]
+  fn rejects_requests_without_a_hex_transaction() {
+    let error = build_send_raw_transaction_request(r#"{"tx_as_hex":"zz"}"#).unwrap_err();
+    assert_eq!((error.code.as_str(), error.field.as_deref()), (INVALID_INPUT, Some("tx_as_hex")));
+    let error = build_send_raw_transaction_request("{}").unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("json_params"));
+  }
+
+  #[test]
+  fn parses_relayed_and_not_relayed_transactions() {
+    let result = parse_send_raw_transaction_response(&response("OK", "", &[])).unwrap();
+    assert!(result.relayed && !result.untrusted);
+    // do_not_relay
+    let not_relayed = response("OK", "Not relayed", &["not_relayed"]);
+    assert!(!parse_send_raw_transaction_response(&not_relayed).unwrap().relayed);
+    // older daemons leave out flags
+    let result = parse_send_raw_transaction_response(r#"{"status":"OK","untrusted":true}"#);
+    assert!(result.unwrap().untrusted);
+  }
+
+  #[test]
+  fn parses_the_rejection_flags() {
+    let cases = [
+      (response("Failed", "double spend", &["double_spend"]), RelayError::DoubleSpend),
+      (response("Failed", "fee too low", &["fee_too_low"]), RelayError::FeeTooLow),
+      (response("Failed", "bad ring size", &["low_mixin"]), RelayError::LowMixin),
+      (response("Failed", "too few outputs", &["too_few_outputs"]), RelayError::TooFewOutputs),
+      (response("Failed", "overspend", &["overspend"]), RelayError::Overspend),
+      (response("Failed", "tx extra too big", &["tx_extra_too_big"]), RelayError::TxExtraTooBig),
+      // several flags: the most specific one
+      (
+        response("Failed", "double spend, invalid input", &["double_spend", "invalid_input"]),
+        RelayError::DoubleSpend,
+      ),
+      (response("Failed", "", &[]), RelayError::Failed { reason: "Failed".to_string() }),
+      (r#"{"status":"BUSY"}"#.to_string(), RelayError::Busy),
+    ];
+    for (response, expected) in cases {
+      assert_eq!(parse_send_raw_transaction_response(&response).unwrap_err(), expected);
+    }
+  }
+
+  #[test]
+  fn relay_errors_keep_their_codes() {
+    let error = parse_send_raw_transaction_response(&response("Failed", "", &["fee_too_low"]));
+    let error = AbiError::from(error.unwrap_err());
+    assert_eq!((error.code.as_str(), error.field), ("fee_too_low", None));
+    assert_eq!(error.message, RelayError::FeeTooLow.message());
+
+    let error = AbiError::from(parse_send_raw_transaction_response("<html>").unwrap_err());
+    assert_eq!(
+      (error.code.as_str(), error.field.as_deref()),
+      ("invalid_response", Some("response"))
+    );
+  }
+}
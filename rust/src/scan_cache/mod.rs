@@ -0,0 +1,313 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use crate::error::AbiError;
+use crate::transaction_building::inputs::compute_key_image;
+
+/// how many blocks lie between two checkpoints if the caller does not specify it
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 1000;
+/// older checkpoints are dropped on compaction, the newest ones are enough to detect a reorg
+pub const MAX_CHECKPOINTS: usize = 100;
+
+/// in memory representation of the _cache.json file (see docs/wallet-files.md)
+/// fields that are only maintained by the typescript side (scanned_ranges, tx_logs, subaddresses ..)
+/// are carried along untouched in `rest`
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ScanCache {
+  primary_address: String,
+  #[serde(default)]
+  daemon_height: u64,
+  #[serde(default)]
+  outputs: BTreeMap<String, CachedOutput>, // keyed by index_on_blockchain.to_string()
+  #[serde(default)]
+  own_key_images: BTreeMap<String, String>, // key image hex -> index_on_blockchain.to_string()
+  #[serde(default)]
+  checkpoints: Vec<Checkpoint>,
+  #[serde(flatten)]
+  rest: Map<String, Value>,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedOutput {
+  stealth_address: String,
+  index_on_blockchain: u64,
+  serialized: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  burned: Option<u64>,
+  #[serde(flatten)]
+  rest: Map<String, Value>,
+}
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+  block_height: u64,
+  block_hash: String,
+}
+/// the scan result as produced by scan_blocks_with_get_blocks_bin,
+/// optionally merged with the meta (block_infos, daemon_height)
+#[derive(Deserialize, Debug)]
+struct ScanResultJson {
+  outputs: Vec<CachedOutput>,
+  all_key_images: Vec<KeyImageJson>,
+  block_infos: Option<Vec<Checkpoint>>,
+  daemon_height: Option<u64>,
+}
+#[derive(Deserialize, Debug)]
+struct KeyImageJson {
+  key_image_hex: String,
+  relative_index: usize,
+  tx_hash: String,
+  block_height: u64,
+  block_timestamp: u64,
+}
+#[derive(Deserialize, Debug, Default)]
+pub struct AppendParams {
+  /// without a spend key this is a view only sync, no own spends are detected
+  spend_key: Option<String>,
+  checkpoint_interval: Option<u64>,
+}
+/// the ChangedOutput of the typescript side: {output, change_reason}
+#[derive(Serialize, Debug)]
+pub struct ChangedOutput {
+  output: CachedOutput,
+  change_reason: &'static str,
+}
+
+pub fn read_scan_cache(bytes: &[u8], primary_address: &str) -> Result<ScanCache, AbiError> {
+  if bytes.is_empty() {
+    return Ok(ScanCache { primary_address: primary_address.to_string(), ..Default::default() });
+  }
+  let cache: ScanCache = serde_json::from_slice(bytes).map_err(|e| {
+    AbiError::invalid_input("cache", format!("failed to parse scan cache json: {}", e))
+  })?;
+  if cache.primary_address != primary_address {
+    let message =
+      format!("scan cache belongs to {}, not to {}", cache.primary_address, primary_address);
+    return Err(AbiError::invalid_input("cache", message));
+  }
+  Ok(cache)
+}
+pub fn write_scan_cache(cache: &ScanCache) -> Result<Vec<u8>, String> {
+  serde_json::to_vec_pretty(cache).map_err(|e| format!("failed to serialize scan cache: {}", e))
+}
+
+impl ScanCache {
+  /// merge a scan result into the cache, returns the outputs that changed
+  pub fn append(
+    &mut self,
+    scan_result_bytes: &[u8],
+    params: &AppendParams,
+  ) -> Result<Vec<ChangedOutput>, AbiError> {
+    let result: ScanResultJson = serde_json::from_slice(scan_result_bytes).map_err(|e| {
+      AbiError::invalid_input("scan_result", format!("failed to parse scan result json: {}", e))
+    })?;
+    let mut changed_outputs = vec![];
+
+    for mut output in result.outputs {
+      let global_id = output.index_on_blockchain.to_string();
+      match self.live_output_with_stealth_address(&output.stealth_address) {
+        // plain duplicate, keep the existing output (it might already be marked as spent)
+        Some(existing) if existing == output.index_on_blockchain => continue,
+        // burning bug: the same one time address twice, only the earlier output can be spent
+        Some(existing) if existing < output.index_on_blockchain => {
+          output.burned = Some(existing);
+          changed_outputs.push(ChangedOutput { output: output.clone(), change_reason: "burned" });
+          self.outputs.insert(global_id, output);
+          continue;
+        }
+        // the new output is the earlier one, it replaces the existing one as the live output
+        Some(existing) => {
+          if let Some(burned) = self.outputs.get_mut(&existing.to_string()) {
+            burned.burned = Some(output.index_on_blockchain);
+            changed_outputs.push(ChangedOutput { output: burned.clone(), change_reason: "burned" });
+          }
+        }
+        None => {}
+      }
+      if let Some(spend_key) = &params.spend_key {
+        // same stealth address, same key image: it maps to the live output
+        // the output comes from the scan result, the spend key from the append params
+        let key_image =
+          compute_key_image(output.serialized.clone(), spend_key.clone()).map_err(|e| {
+            match e.field.as_deref() {
+              Some("sender_spend_key") => e.with_field("spend_key"),
+              _ => e.with_field("scan_result"),
+            }
+          })?;
+        self.own_key_images.insert(key_image, global_id.clone());
+      }
+      changed_outputs.push(ChangedOutput { output: output.clone(), change_reason: "added" });
+      self.outputs.insert(global_id, output);
+    }
+
+    // key images that aren't ours are dropped here, only our own spends are recorded
+    for key_image in result.all_key_images {
+      let Some(global_id) = self.own_key_images.get(&key_image.key_image_hex) else {
+        continue;
+      };
+      let Some(output) = self.outputs.get_mut(global_id) else {
+        continue;
+      };
+      output.rest.insert("spent_relative_index".into(), json!(key_image.relative_index));
+      output.rest.insert("spent_in_tx_hash".into(), json!(key_image.tx_hash));
+      output.rest.insert("spent_block_height".into(), json!(key_image.block_height));
+      output.rest.insert("spent_block_timestamp".into(), json!(key_image.block_timestamp));
+      changed_outputs.push(ChangedOutput { output: output.clone(), change_reason: "ownspend" });
+    }
+
+    if let Some(daemon_height) = result.daemon_height {
+      self.daemon_height = self.daemon_height.max(daemon_height);
+    }
+    if let Some(block_infos) = result.block_infos {
+      let interval = params.checkpoint_interval.unwrap_or(DEFAULT_CHECKPOINT_INTERVAL).max(1);
+      for block_info in block_infos {
+        if block_info.block_height % interval == 0 {
+          self.add_checkpoint(block_info);
+        }
+      }
+    }
+    Ok(changed_outputs)
+  }
+
+  /// deduplicate outputs by stealth address, drop key images without an output
+  /// and keep only the newest checkpoints
+  pub fn compact(&mut self) {
+    let mut by_stealth_address: HashMap<String, u64> = HashMap::new();
+    for output in self.outputs.values() {
+      let earliest = by_stealth_address
+        .entry(output.stealth_address.clone())
+        .or_insert(output.index_on_blockchain);
+      *earliest = (*earliest).min(output.index_on_blockchain);
+    }
+    for output in self.outputs.values_mut() {
+      let live_index = by_stealth_address[&output.stealth_address];
+      if live_index != output.index_on_blockchain {
+        output.burned = Some(live_index);
+      }
+    }
+    let outputs = &self.outputs;
+    self.own_key_images.retain(|_, global_id| outputs.contains_key(global_id));
+
+    self.checkpoints.sort_by_key(|checkpoint| checkpoint.block_height);
+    self.checkpoints.dedup_by_key(|checkpoint| checkpoint.block_height);
+    if self.checkpoints.len() > MAX_CHECKPOINTS {
+      self.checkpoints.drain(..self.checkpoints.len() - MAX_CHECKPOINTS);
+    }
+  }
+
+  fn add_checkpoint(&mut self, checkpoint: Checkpoint) {
+    // a new hash at a known height means the old one got reorged out, drop everything above it
+    self.checkpoints.retain(|existing| existing.block_height < checkpoint.block_height);
+    self.checkpoints.push(checkpoint);
+  }
+
+  fn live_output_with_stealth_address(&self, stealth_address: &str) -> Option<u64> {
+    self
+      .outputs
+      .values()
+      .find(|output| output.stealth_address == stealth_address && output.burned.is_none())
+      .map(|output| output.index_on_blockchain)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{keys, output_json};
+
+  fn scan_result(outputs: &[Value], key_images: &[&str]) -> Vec<u8> {
+    let all_key_images: Vec<Value> = key_images
+      .iter()
+      .map(|key_image| {
+        json!({ "key_image_hex": key_image, "relative_index": 0, "tx_hash": "00",
+          "block_height": 200, "block_timestamp": 0 })
+      })
+      .collect();
+    json!({ "outputs": outputs, "all_key_images": all_key_images }).to_string().into_bytes()
+  }
+  /// the same one time address at another global index, what the burning bug looks like
+  fn at_index(output: &Value, index_on_blockchain: u64) -> Value {
+    let mut output = output.clone();
+    output["index_on_blockchain"] = json!(index_on_blockchain);
+    output
+  }
+  fn reasons(changed: &[ChangedOutput]) -> Vec<(u64, &'static str)> {
+    changed.iter().map(|c| (c.output.index_on_blockchain, c.change_reason)).collect()
+  }
+
+  #[test]
+  fn duplicates_keep_the_spent_output() {
+    let wallet = keys(7);
+    let params = AppendParams { spend_key: Some(wallet.spend_key_hex()), ..Default::default() };
+    let output = output_json(&wallet, 1000, 10, 100, false);
+    let mut cache = read_scan_cache(&[], &wallet.primary_address()).unwrap();
+
+    let changed = cache.append(&scan_result(&[output.clone()], &[]), &params).unwrap();
+    assert_eq!(reasons(&changed), vec![(10, "added")]);
+    let key_image = cache.own_key_images.keys().next().unwrap().clone();
+    let changed = cache.append(&scan_result(&[], &[&key_image]), &params).unwrap();
+    assert_eq!(reasons(&changed), vec![(10, "ownspend")]);
+
+    let changed = cache.append(&scan_result(&[output], &[]), &params).unwrap();
+    assert!(changed.is_empty());
+    assert_eq!(cache.outputs["10"].rest["spent_block_height"], json!(200));
+  }
+
+  #[test]
+  fn a_later_output_with_the_same_address_is_burned() {
+    let wallet = keys(7);
+    let params = AppendParams { spend_key: Some(wallet.spend_key_hex()), ..Default::default() };
+    let output = output_json(&wallet, 1000, 10, 100, false);
+    let mut cache = read_scan_cache(&[], &wallet.primary_address()).unwrap();
+
+    cache.append(&scan_result(&[output.clone()], &[]), &params).unwrap();
+    let changed = cache.append(&scan_result(&[at_index(&output, 20)], &[]), &params).unwrap();
+    assert_eq!(reasons(&changed), vec![(20, "burned")]);
+    assert_eq!(cache.outputs["20"].burned, Some(10));
+    assert_eq!(cache.outputs["10"].burned, None);
+    assert_eq!(cache.own_key_images.values().collect::<Vec<_>>(), vec!["10"]);
+  }
+
+  #[test]
+  fn an_earlier_output_with_the_same_address_becomes_the_live_one() {
+    let wallet = keys(7);
+    let params = AppendParams { spend_key: Some(wallet.spend_key_hex()), ..Default::default() };
+    let output = output_json(&wallet, 1000, 20, 100, false);
+    let mut cache = read_scan_cache(&[], &wallet.primary_address()).unwrap();
+
+    cache.append(&scan_result(&[output.clone()], &[]), &params).unwrap();
+    let changed = cache.append(&scan_result(&[at_index(&output, 10)], &[]), &params).unwrap();
+    assert_eq!(reasons(&changed), vec![(20, "burned"), (10, "added")]);
+    assert_eq!(cache.outputs["20"].burned, Some(10));
+    assert_eq!(cache.outputs["10"].burned, None);
+    assert_eq!(cache.own_key_images.values().collect::<Vec<_>>(), vec!["10"]);
+
+    // the spend is recorded on the live output
+    let key_image = cache.own_key_images.keys().next().unwrap().clone();
+    let changed = cache.append(&scan_result(&[], &[&key_image]), &params).unwrap();
+    assert_eq!(reasons(&changed), vec![(10, "ownspend")]);
+  }
+
+  #[test]
+  fn changed_outputs_have_the_typescript_shape() {
+    let wallet = keys(7);
+    let output = output_json(&wallet, 1000, 10, 100, false);
+    let mut cache = read_scan_cache(&[], &wallet.primary_address()).unwrap();
+    let changed = cache.append(&scan_result(&[output], &[]), &AppendParams::default()).unwrap();
+    let json = serde_json::to_value(&changed).unwrap();
+    assert_eq!(json[0]["change_reason"], "added");
+    assert_eq!(json[0]["output"]["index_on_blockchain"], 10);
+    assert_eq!(json[0]["output"]["amount"], "1000");
+  }
+
+  #[test]
+  fn compaction_keeps_the_newest_checkpoints() {
+    let mut cache = ScanCache::default();
+    for block_height in (0..(MAX_CHECKPOINTS as u64 + 10)).rev() {
+      cache.checkpoints.push(Checkpoint { block_height, block_hash: "00".into() });
+    }
+    cache.compact();
+    assert_eq!(cache.checkpoints.len(), MAX_CHECKPOINTS);
+    assert_eq!(cache.checkpoints[0].block_height, 10);
+  }
+}
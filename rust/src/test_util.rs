@@ -0,0 +1,221 @@
+//! deterministic keys and consensus encoded transactions for the unit tests
+use cuprate_rpc_types::{base::AccessResponseBase, bin::GetOutsResponse, misc::OutKeyBin};
+use curve25519_dalek::{EdwardsPoint, Scalar, constants::ED25519_BASEPOINT_TABLE};
+use monero_wallet::{
+  OutputWithDecoys, Scanner, ViewPair, WalletOutput,
+  address::{MoneroAddress, Network, SubaddressIndex},
+  ringct::RctType,
+  rpc::FeeRate,
+  send::{Change, SignableTransaction},
+  transaction::{Pruned, Transaction},
+};
+use rand_core::OsRng;
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::block_parsing::view_tags::{view_tag, write_varint};
+use crate::keypairs::make_viewkey;
+use crate::proofs::{key_derivation, shared_key};
+use crate::transaction_building::fee_estimation::RING_LEN;
+
+pub(crate) struct Keys {
+  pub(crate) spend_key: Scalar,
+  pub(crate) spend: EdwardsPoint,
+  pub(crate) view_key: Scalar,
+}
+impl Keys {
+  pub(crate) fn viewpair(&self) -> ViewPair {
+    ViewPair::new(self.spend, Zeroizing::new(self.view_key)).unwrap()
+  }
+  pub(crate) fn spend_key_hex(&self) -> String {
+    hex::encode(self.spend_key.to_bytes())
+  }
+  pub(crate) fn primary_address(&self) -> String {
+    self.viewpair().legacy_address(Network::Mainnet).to_string()
+  }
+}
+/// a wallet like wallet-cli derives it: the view key is Hs(spend key)
+pub(crate) fn keys(seed: u64) -> Keys {
+  let spend_key = Scalar::from(seed);
+  let view_key = make_viewkey(spend_key.to_bytes());
+  Keys { spend_key, spend: &spend_key * ED25519_BASEPOINT_TABLE, view_key }
+}
+
+/// a v2 miner transaction paying amount to every recipient, view tagged if tagged
+pub(crate) fn coinbase_blob(
+  recipients: &[&Keys],
+  amount: u64,
+  tx_key: Scalar,
+  tagged: bool,
+) -> Vec<u8> {
+  let mut blob = Vec::new();
+  write_varint(2, &mut blob); // version
+  write_varint(0, &mut blob); // unlock time
+  write_varint(1, &mut blob);
+  blob.push(0xff);
+  write_varint(100, &mut blob); // height
+  write_varint(recipients.len() as u64, &mut blob);
+  for (index, recipient) in recipients.iter().enumerate() {
+    let view = &recipient.view_key * ED25519_BASEPOINT_TABLE;
+    let derivation = key_derivation(&tx_key, &view);
+    let key = &shared_key(&derivation, index) * ED25519_BASEPOINT_TABLE + recipient.spend;
+    write_varint(amount, &mut blob);
+    blob.push(if tagged { 0x03 } else { 0x02 });
+    blob.extend(key.compress().to_bytes());
+    if tagged {
+      blob.push(view_tag(&derivation.compress().to_bytes(), index));
+    }
+  }
+  write_varint(33, &mut blob);
+  blob.push(0x01);
+  blob.extend((&tx_key * ED25519_BASEPOINT_TABLE).compress().to_bytes());
+  blob.push(0); // rct type null
+  blob
+}
+
+/// what a full scan of the blob finds, the first output has the global index first_index
+pub(crate) fn scan(keys: &Keys, blob: &[u8], first_index: u64) -> Vec<WalletOutput> {
+  let tx = Transaction::<Pruned>::read::<&[u8]>(&mut &blob[..]).unwrap();
+  let scanned = Scanner::new(keys.viewpair()).scan_transaction(Some(first_index), [0; 32], &tx);
+  scanned.unwrap().not_additionally_locked()
+}
+
+/// the output of amount to keys at index_on_blockchain, from a miner transaction of its own
+fn scanned_output(keys: &Keys, amount: u64, index_on_blockchain: u64) -> WalletOutput {
+  let tx_key = Scalar::from(index_on_blockchain + 1000);
+  let blob = coinbase_blob(&[keys], amount, tx_key, true);
+  scan(keys, &blob, index_on_blockchain).remove(0)
+}
+/// an output of amount at index_on_blockchain as scan_blocks outputs it
+pub(crate) fn output_json(
+  keys: &Keys,
+  amount: u64,
+  index_on_blockchain: u64,
+  block_height: u64,
+  is_miner_tx: bool,
+) -> Value {
+  let output = scanned_output(keys, amount, index_on_blockchain);
+  crate::block_parsing::wallet_output_to_json(
+    &output,
+    block_height,
+    0,
+    &keys.primary_address(),
+    is_miner_tx,
+  )
+}
+
+/// output_json of each amount at the blockchain indexes 10, 20, 30..., with the input() of
+/// the output prepared, like a candidate of coin selection after make_input
+pub(crate) fn candidates_with_inputs(keys: &Keys, amounts: &[u64]) -> Vec<Value> {
+  (10..)
+    .step_by(10)
+    .zip(amounts)
+    .map(|(index, amount)| {
+      let mut candidate = output_json(keys, *amount, index, 100, false);
+      candidate["input"] = hex::encode(input(keys, *amount, index).serialize()).into();
+      candidate
+    })
+    .collect()
+}
+
+/// the ring of input: made up decoys, the output itself is the 4th member
+fn ring(output: &WalletOutput) -> Vec<(EdwardsPoint, EdwardsPoint)> {
+  (0..RING_LEN)
+    .map(|member| {
+      if member == SIGNER_INDEX {
+        return (output.key(), output.commitment().calculate());
+      }
+      let decoy = &Scalar::from(5000 + member as u64) * ED25519_BASEPOINT_TABLE;
+      (decoy, decoy)
+    })
+    .collect()
+}
+const SIGNER_INDEX: usize = 3;
+
+/// an input spending the output of output_json, in a ring of RING_LEN made up decoys.
+/// index_on_blockchain has to be at least 3
+pub(crate) fn input(keys: &Keys, amount: u64, index_on_blockchain: u64) -> OutputWithDecoys {
+  let output = scanned_output(keys, amount, index_on_blockchain);
+  // OutputWithDecoys::write: the output, relative key offsets, signer index, ring
+  let mut bytes = output.serialize();
+  write_varint(RING_LEN as u64, &mut bytes);
+  write_varint(index_on_blockchain - SIGNER_INDEX as u64, &mut bytes);
+  for _ in 1..RING_LEN {
+    write_varint(1, &mut bytes);
+  }
+  bytes.push(SIGNER_INDEX as u8);
+  write_varint(RING_LEN as u64, &mut bytes);
+  for (key, commitment) in ring(&output) {
+    bytes.extend(key.compress().to_bytes());
+    bytes.extend(commitment.compress().to_bytes());
+  }
+  OutputWithDecoys::read(&mut bytes.as_slice()).unwrap()
+}
+
+pub(crate) const XMR: u64 = 1_000_000_000_000;
+/// payer pays with one input of 5 XMR
+pub(crate) fn payment(
+  payer: &Keys,
+  payments: Vec<(MoneroAddress, u64)>,
+  change: Change,
+) -> SignableTransaction {
+  payment_with_inputs(payer, &[5 * XMR], payments, change)
+}
+/// payer pays with one input per amount, at the blockchain indexes 10, 20, 30...
+pub(crate) fn payment_with_inputs(
+  payer: &Keys,
+  input_amounts: &[u64],
+  payments: Vec<(MoneroAddress, u64)>,
+  change: Change,
+) -> SignableTransaction {
+  let inputs =
+    (10..).step_by(10).zip(input_amounts).map(|(index, amount)| input(payer, *amount, index));
+  SignableTransaction::new(
+    RctType::ClsagBulletproofPlus,
+    Zeroizing::new([3; 32]),
+    inputs.collect(),
+    payments,
+    change,
+    vec![],
+    FeeRate::new(20_000, 10_000).unwrap(),
+  )
+  .unwrap()
+}
+pub(crate) fn sign(payer: &Keys, tx: SignableTransaction) -> Transaction {
+  tx.sign(&mut OsRng, &Zeroizing::new(payer.spend_key)).unwrap()
+}
+/// the ring of the input of payment, as a getouts.bin response for it lists it
+pub(crate) fn ring_members(payer: &Keys) -> GetOutsResponse {
+  let outs = ring(&scanned_output(payer, 5 * XMR, 10))
+    .into_iter()
+    .map(|(key, commitment)| OutKeyBin {
+      key: key.compress().to_bytes(),
+      mask: commitment.compress().to_bytes(),
+      unlocked: true,
+      height: 100,
+      txid: [0; 32],
+    })
+    .collect();
+  GetOutsResponse { base: AccessResponseBase::OK, outs }
+}
+
+/// what keys finds in a signed transaction, looking out for the subaddresses too
+pub(crate) fn received(
+  keys: &Keys,
+  subaddresses: &[SubaddressIndex],
+  tx: &Transaction,
+) -> Vec<WalletOutput> {
+  let pruned = Transaction::<Pruned>::read::<&[u8]>(&mut tx.serialize().as_slice()).unwrap();
+  let mut scanner = Scanner::new(keys.viewpair());
+  for index in subaddresses {
+    scanner.register_subaddress(*index);
+  }
+  scanner.scan_transaction(Some(100), tx.hash(), &pruned).unwrap().not_additionally_locked()
+}
+
+/// a proof wallet-cli made, read from tests/fixtures (see the README there)
+pub(crate) fn fixture(name: &str) -> Value {
+  let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+  let json = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+  serde_json::from_str(&json).unwrap()
+}
@@ -0,0 +1,287 @@
+use monero_wallet::{
+  ViewPair,
+  address::{MoneroAddress, Network},
+};
+use serde::{Deserialize, Serialize};
+
+use super::coin_selection::{
+  Candidate, CandidateJson, build_transaction, estimated_fee, greedy, parse_candidates,
+};
+use super::fee_estimation::TxShape;
+use super::sweep::MAX_TX_WEIGHT;
+use super::transaction::{
+  ChangeJson, FeeResponse, PaymentJson, parse_fee_rate, parse_payments, random_outgoing_view_key,
+};
+use crate::block_parsing::amount_to_string;
+use crate::error::{AbiError, NOT_ENOUGH_FUNDS};
+
+/// MAX_OUTPUTS of monero-wallet, one of them is reserved for change
+pub const MAX_OUTPUTS: usize = 16;
+
+#[derive(Debug, Deserialize)]
+struct BatchPayoutParams {
+  /// spendable outputs from the scanner, with a prepared input where available
+  candidates: Vec<CandidateJson>,
+  payments: Vec<PaymentJson>,
+  fee_response: FeeResponse,
+  fee_priority: String,
+  /// payments per transaction, defaults to (and can't be more than) MAX_OUTPUTS - 1
+  max_payments_per_tx: Option<usize>,
+  /// a transaction gets fewer payments if its inputs would go over this, defaults to MAX_TX_WEIGHT
+  max_weight: Option<usize>,
+  #[serde(flatten)]
+  change: ChangeJson,
+}
+#[derive(Debug, Serialize)]
+pub struct PayoutTransaction {
+  /// indices into the payments of the request
+  payments: Vec<usize>,
+  /// index_on_blockchain of the selected outputs, no output is used by two transactions
+  selected: Vec<u64>,
+  #[serde(serialize_with = "amount_to_string")]
+  amount: u64,
+  #[serde(serialize_with = "amount_to_string")]
+  total_input: u64,
+  #[serde(serialize_with = "amount_to_string")]
+  fee: u64,
+  #[serde(serialize_with = "amount_to_string")]
+  change: u64,
+  /// "necessary_fee" if the transaction was built, "estimate" if inputs are missing
+  fee_source: &'static str,
+  /// only set if every selected output had a prepared input
+  signable_transaction: Option<String>,
+  /// selected outputs without a prepared input, call make_input for them and plan again
+  missing_inputs: Vec<u64>,
+}
+#[derive(Debug, Serialize)]
+pub struct PayoutPlan {
+  transactions: Vec<PayoutTransaction>,
+  #[serde(serialize_with = "amount_to_string")]
+  total_amount: u64,
+  #[serde(serialize_with = "amount_to_string")]
+  total_fee: u64,
+}
+
+fn payment_total(payments: &[(MoneroAddress, u64)]) -> Result<u64, AbiError> {
+  payments
+    .iter()
+    .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+    .ok_or_else(|| AbiError::invalid_input("payments", "payment total overflows u64"))
+}
+
+pub fn make_batch_payout(
+  json_params: &str,
+  viewpair: ViewPair,
+  network: Network,
+) -> Result<PayoutPlan, AbiError> {
+  let params: BatchPayoutParams = serde_json::from_str(json_params).map_err(|e| {
+    let message = format!("failed to parse batch payout params json: {:?}", e);
+    AbiError::invalid_input("json_params", message)
+  })?;
+  let fee_rate = parse_fee_rate(&params.fee_priority, params.fee_response)?;
+  if params.payments.is_empty() {
+    return Err(AbiError::invalid_input("payments", "no payments to make"));
+  }
+  if params.payments.iter().any(|payment| payment.subtract_fee_from_amount) {
+    let message = "subtract_fee_from_amount is not supported for batch payouts";
+    return Err(AbiError::invalid_input("payments", message));
+  }
+  let payments = parse_payments(&params.payments, network)?;
+  let change = params.change.to_change(&viewpair, network)?;
+  let max_payments =
+    params.max_payments_per_tx.unwrap_or(MAX_OUTPUTS - 1).clamp(1, MAX_OUTPUTS - 1);
+
+  let candidates = parse_candidates(params.candidates)?;
+  // largest first, like fewest_inputs
+  let mut remaining: Vec<&Candidate> = candidates.iter().collect();
+  remaining.sort_by(|a, b| b.amount.cmp(&a.amount));
+
+  let max_weight = params.max_weight.unwrap_or(MAX_TX_WEIGHT);
+
+  let mut transactions = vec![];
+  let mut next_payment = 0;
+  while next_payment < payments.len() {
+    let group = transactions.len();
+    let not_enough_funds = || {
+      let message = format!("not enough funds to cover payments and fee of transaction {}", group);
+      AbiError::new(NOT_ENOUGH_FUNDS, message)
+    };
+    // as many payments as fit, fewer if their inputs make the transaction too heavy
+    let mut count = max_payments.min(payments.len() - next_payment);
+    let (chunk, mut selected) = loop {
+      let chunk = next_payment..next_payment + count;
+      let amount = payment_total(&payments[chunk.clone()])?;
+      let selected =
+        greedy(&[], &remaining, amount, count + 1, &fee_rate).ok_or_else(not_enough_funds)?;
+      if TxShape::new(selected.len(), count + 1).estimate(&fee_rate).weight <= max_weight {
+        break (chunk, selected);
+      }
+      if count == 1 {
+        let message = format!(
+          "the inputs for payment {} don't fit into a transaction of max_weight {}",
+          next_payment, max_weight
+        );
+        return Err(AbiError::invalid_input("max_weight", message));
+      }
+      count -= 1;
+    };
+    next_payment = chunk.end;
+    let group_payments = &payments[chunk.clone()];
+    let amount = payment_total(group_payments)?;
+    let outputs = group_payments.len() + 1; // change
+    remaining.retain(|candidate| {
+      !selected.iter().any(|selected| selected.index_on_blockchain == candidate.index_on_blockchain)
+    });
+
+    let missing_inputs: Vec<u64> = selected
+      .iter()
+      .filter(|candidate| candidate.input.is_none())
+      .map(|candidate| candidate.index_on_blockchain)
+      .collect();
+    let mut fee = estimated_fee(selected.len(), outputs, &fee_rate);
+    let mut fee_source = "estimate";
+    let mut signable_transaction = None;
+
+    if missing_inputs.is_empty() {
+      let tx = build_transaction(
+        &mut selected,
+        &mut remaining,
+        group_payments,
+        &change,
+        random_outgoing_view_key(),
+        fee_rate,
+      )
+      .map_err(|e| AbiError { message: format!("transaction {}: {}", group, e.message), ..e })?
+      .ok_or_else(not_enough_funds)?;
+      fee = tx.necessary_fee();
+      fee_source = "necessary_fee";
+      signable_transaction = Some(hex::encode(tx.serialize()));
+    }
+
+    let total_input = selected
+      .iter()
+      .try_fold(0u64, |total, candidate| total.checked_add(candidate.amount))
+      .ok_or_else(|| AbiError::invalid_input("candidates", "input total overflows u64"))?;
+    transactions.push(PayoutTransaction {
+      payments: chunk.collect(),
+      selected: selected.iter().map(|candidate| candidate.index_on_blockchain).collect(),
+      amount,
+      total_input,
+      fee,
+      change: total_input.saturating_sub(amount).saturating_sub(fee),
+      fee_source,
+      signable_transaction,
+      missing_inputs,
+    });
+  }
+  Ok(PayoutPlan {
+    total_amount: payment_total(&payments)?,
+    total_fee: transactions.iter().map(|tx| tx.fee).sum(),
+    transactions,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{XMR, candidates_with_inputs, keys, output_json, payment_with_inputs};
+  use monero_wallet::send::Change;
+  use serde_json::{Value, json};
+
+  fn params(candidates: usize, payments: &[u64], max_weight: usize) -> String {
+    let wallet = keys(7);
+    let candidates: Vec<Value> = (0..candidates as u64)
+      .map(|index| output_json(&wallet, XMR / 10, index, 100, false))
+      .collect();
+    let address = keys(9).primary_address();
+    let payments: Vec<Value> = payments
+      .iter()
+      .map(|amount| json!({ "address": address, "amount": amount.to_string() }))
+      .collect();
+    json!({
+      "candidates": candidates,
+      "payments": payments,
+      "fee_response": { "fee": 20_000, "quantization_mask": 10_000 },
+      "fee_priority": "normal",
+      "max_weight": max_weight,
+    })
+    .to_string()
+  }
+  fn plan(params: &str) -> Value {
+    let plan = make_batch_payout(params, keys(7).viewpair(), Network::Mainnet).unwrap();
+    serde_json::to_value(plan).unwrap()
+  }
+
+  #[test]
+  fn payments_share_a_transaction_while_it_stays_under_max_weight() {
+    let plan = plan(&params(70, &[XMR, XMR], MAX_TX_WEIGHT));
+    assert_eq!(plan["transactions"].as_array().unwrap().len(), 1);
+    assert_eq!(plan["transactions"][0]["payments"], json!([0, 1]));
+    assert_eq!(plan["transactions"][0]["fee_source"], "estimate");
+  }
+
+  #[test]
+  fn heavy_transactions_are_split_by_weight() {
+    // 31 inputs per payment, 62 together are over 30_000
+    let plan = plan(&params(70, &[3 * XMR, 3 * XMR], 30_000));
+    let transactions = plan["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[0]["payments"], json!([0]));
+    assert_eq!(transactions[1]["payments"], json!([1]));
+    let selected: Vec<u64> = transactions
+      .iter()
+      .flat_map(|tx| tx["selected"].as_array().unwrap())
+      .map(|index| index.as_u64().unwrap())
+      .collect();
+    let unique: std::collections::HashSet<&u64> = selected.iter().collect();
+    assert_eq!(unique.len(), selected.len());
+  }
+
+  #[test]
+  fn a_payment_too_heavy_for_one_transaction_is_an_error() {
+    let params = params(70, &[5 * XMR], 20_000);
+    assert!(make_batch_payout(&params, keys(7).viewpair(), Network::Mainnet).is_err());
+  }
+
+  #[test]
+  fn prepared_inputs_build_the_transaction() {
+    let wallet = keys(7);
+    let mut params: Value = serde_json::from_str(&params(0, &[XMR, XMR], MAX_TX_WEIGHT)).unwrap();
+    params["candidates"] = candidates_with_inputs(&wallet, &[2 * XMR, 3 * XMR]).into();
+    let plan = plan(&params.to_string());
+    let transactions = plan["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 1);
+    let tx = &transactions[0];
+    assert_eq!(tx["payments"], json!([0, 1]));
+    // the 3 XMR input at blockchain index 20 covers both payments
+    assert_eq!(tx["selected"], json!([20]));
+    assert_eq!(tx["fee_source"], "necessary_fee");
+    assert!(tx["missing_inputs"].as_array().unwrap().is_empty());
+    assert!(tx["signable_transaction"].is_string());
+
+    let address = keys(9).viewpair().legacy_address(Network::Mainnet);
+    let change = Change::new(wallet.viewpair(), None);
+    let fee =
+      payment_with_inputs(&wallet, &[3 * XMR], vec![(address, XMR), (address, XMR)], change)
+        .necessary_fee();
+    assert_eq!(tx["total_input"], (3 * XMR).to_string());
+    assert_eq!(tx["fee"], fee.to_string());
+    assert_eq!(tx["change"], (XMR - fee).to_string());
+    assert_eq!(plan["total_amount"], (2 * XMR).to_string());
+    assert_eq!(plan["total_fee"], fee.to_string());
+  }
+
+  #[test]
+  fn overflowing_inputs_are_an_error() {
+    let mut params: Value =
+      serde_json::from_str(&params(2, &[u64::MAX / 2 + XMR], MAX_TX_WEIGHT)).unwrap();
+    // the first input alone doesn't cover the payment, both overflow
+    for candidate in params["candidates"].as_array_mut().unwrap() {
+      candidate["amount"] = json!((u64::MAX / 2 + 1).to_string());
+    }
+    let error =
+      make_batch_payout(&params.to_string(), keys(7).viewpair(), Network::Mainnet).unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("candidates"));
+    assert_eq!(error.message, "input total overflows u64");
+  }
+}
@@ -0,0 +1,441 @@
+use std::collections::BTreeMap;
+
+use monero_wallet::{
+  ViewPair,
+  address::{MoneroAddress, Network},
+  ringct::RctType,
+  rpc::FeeRate,
+  send::{Change, SendError, SignableTransaction},
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::fee_estimation::TxShape;
+use super::inputs::read_output_from_string;
+use super::transaction::{
+  FeeResponse, PaymentJson, parse_fee_rate, parse_payments, read_one_input, send_error,
+};
+use crate::error::{AbiError, NOT_ENOUGH_FUNDS};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CandidateJson {
+  amount: String,
+  index_on_blockchain: u64,
+  block_height: u64,
+  serialized: String,
+  /// the output with decoys as returned by make_input, if already prepared
+  input: Option<String>,
+}
+#[derive(Debug, Deserialize)]
+struct CoinSelectionParams {
+  /// spendable outputs from the scanner
+  candidates: Vec<CandidateJson>,
+  payments: Vec<PaymentJson>,
+  fee_response: FeeResponse,
+  fee_priority: String,
+  /// fewest_inputs | same_account | age_weighted | consolidate_dust
+  strategy: String,
+  /// only used by same_account, default: the account that needs the fewest inputs
+  account: Option<u32>,
+  /// only used by consolidate_dust, outputs below this amount are swept along
+  dust_threshold: Option<String>,
+  current_height: u64,
+}
+#[derive(Debug)]
+pub(crate) struct Candidate {
+  pub(crate) amount: u64,
+  pub(crate) index_on_blockchain: u64,
+  pub(crate) block_height: u64,
+  pub(crate) account: u32,
+  pub(crate) input: Option<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct CoinSelection {
+  /// index_on_blockchain of the selected outputs
+  selected: Vec<u64>,
+  total_input: String,
+  fee: String,
+  change: String,
+  /// "necessary_fee" if the fee was computed from a SignableTransaction,
+  /// "estimate" if some selected outputs still need decoys (see missing_inputs)
+  fee_source: &'static str,
+  /// selected outputs without a prepared input, call make_input for them and select again
+  missing_inputs: Vec<u64>,
+}
+
+pub(crate) fn estimated_fee(inputs: usize, outputs: usize, fee_rate: &FeeRate) -> u64 {
+  TxShape::new(inputs, outputs).estimate(fee_rate).fee
+}
+
+fn order_candidates<'a>(
+  strategy: &str,
+  candidates: &'a [Candidate],
+  current_height: u64,
+) -> Result<Vec<&'a Candidate>, AbiError> {
+  let mut ordered: Vec<&Candidate> = candidates.iter().collect();
+  match strategy {
+    "fewest_inputs" | "same_account" | "consolidate_dust" => {
+      ordered.sort_by(|a, b| b.amount.cmp(&a.amount))
+    }
+    "age_weighted" => ordered.sort_by_key(|candidate| {
+      let age = current_height.saturating_sub(candidate.block_height);
+      core::cmp::Reverse(u128::from(candidate.amount) * u128::from(age.max(1)))
+    }),
+    _ => {
+      let message = format!(
+        "Invalid strategy: '{}'. Must be: fewest_inputs, same_account, age_weighted, consolidate_dust",
+        strategy
+      );
+      return Err(AbiError::invalid_input("strategy", message));
+    }
+  }
+  Ok(ordered)
+}
+
+/// takes the forced candidates, then from ordered until payments + estimated fee are covered
+pub(crate) fn greedy<'a>(
+  forced: &[&'a Candidate],
+  ordered: &[&'a Candidate],
+  payment_total: u64,
+  outputs: usize,
+  fee_rate: &FeeRate,
+) -> Option<Vec<&'a Candidate>> {
+  let mut selected: Vec<&Candidate> = forced.to_vec();
+  let mut remaining = ordered.iter().copied().filter(|candidate| {
+    !forced.iter().any(|forced| forced.index_on_blockchain == candidate.index_on_blockchain)
+  });
+  loop {
+    let total =
+      selected.iter().fold(0u64, |total, candidate| total.saturating_add(candidate.amount));
+    let fee = estimated_fee(selected.len(), outputs, fee_rate);
+    if !selected.is_empty() && total >= payment_total.saturating_add(fee) {
+      return Some(selected);
+    }
+    selected.push(remaining.next()?);
+  }
+}
+
+/// builds the transaction from the selected candidates, which all have a prepared input. the
+/// fee estimate can be a bit off, so prepared inputs are moved from remaining to selected until
+/// the transaction builds. Ok(None) if remaining runs out of prepared inputs first
+pub(crate) fn build_transaction<'a>(
+  selected: &mut Vec<&'a Candidate>,
+  remaining: &mut Vec<&'a Candidate>,
+  payments: &[(MoneroAddress, u64)],
+  change: &Change,
+  outgoing_view_key: Zeroizing<[u8; 32]>,
+  fee_rate: FeeRate,
+) -> Result<Option<SignableTransaction>, AbiError> {
+  loop {
+    let mut inputs = Vec::with_capacity(selected.len());
+    for candidate in selected.iter() {
+      let input = candidate.input.clone().ok_or("selected output has no prepared input")?;
+      inputs.push(read_one_input(input, "candidates")?);
+    }
+    match SignableTransaction::new(
+      RctType::ClsagBulletproofPlus,
+      outgoing_view_key.clone(),
+      inputs,
+      payments.to_vec(),
+      change.clone(),
+      vec![],
+      fee_rate,
+    ) {
+      Ok(tx) => return Ok(Some(tx)),
+      Err(SendError::NotEnoughFunds { .. }) => {
+        let Some(position) = remaining.iter().position(|candidate| candidate.input.is_some())
+        else {
+          return Ok(None);
+        };
+        selected.push(remaining.remove(position));
+      }
+      Err(e) => return Err(send_error(e)),
+    }
+  }
+}
+
+pub(crate) fn parse_candidates(
+  candidates_json: Vec<CandidateJson>,
+) -> Result<Vec<Candidate>, AbiError> {
+  let mut candidates = Vec::with_capacity(candidates_json.len());
+  for (i, candidate) in candidates_json.into_iter().enumerate() {
+    let amount = candidate.amount.parse::<u64>().map_err(|e| {
+      let message = format!("failed to parse candidate amount '{}' : {:?}", candidate.amount, e);
+      AbiError::invalid_input(&format!("candidates[{}].amount", i), message)
+    })?;
+    let account =
+      read_output_from_string(&candidate.serialized, &format!("candidates[{}].serialized", i))?
+        .subaddress()
+        .map(|subaddress| subaddress.account())
+        .unwrap_or(0);
+    candidates.push(Candidate {
+      amount,
+      index_on_blockchain: candidate.index_on_blockchain,
+      block_height: candidate.block_height,
+      account,
+      input: candidate.input,
+    });
+  }
+  Ok(candidates)
+}
+
+pub fn select_inputs(
+  json_params: &str,
+  viewpair: ViewPair,
+  network: Network,
+) -> Result<CoinSelection, AbiError> {
+  let params: CoinSelectionParams = serde_json::from_str(json_params).map_err(|e| {
+    let message = format!("failed to parse coin selection params json: {:?}", e);
+    AbiError::invalid_input("json_params", message)
+  })?;
+  let fee_rate = parse_fee_rate(&params.fee_priority, params.fee_response)?;
+  if params.payments.iter().any(|payment| payment.subtract_fee_from_amount) {
+    let message = "subtract_fee_from_amount is not supported for coin selection";
+    return Err(AbiError::invalid_input("payments", message));
+  }
+  let payments = parse_payments(&params.payments, network)?;
+  let payment_total = payments
+    .iter()
+    .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+    .ok_or_else(|| AbiError::invalid_input("payments", "payment total overflows u64"))?;
+  let outputs = payments.len() + 1; // change
+
+  let candidates = parse_candidates(params.candidates)?;
+  let ordered = order_candidates(&params.strategy, &candidates, params.current_height)?;
+
+  let not_enough_funds = || {
+    let message =
+      format!("not enough funds to cover payments and fee with strategy {}", params.strategy);
+    AbiError::new(NOT_ENOUGH_FUNDS, message)
+  };
+  let mut selected = match params.strategy.as_str() {
+    "same_account" => {
+      let mut by_account: BTreeMap<u32, Vec<&Candidate>> = BTreeMap::new();
+      for candidate in ordered.iter().copied() {
+        if params.account.is_none_or(|account| account == candidate.account) {
+          by_account.entry(candidate.account).or_default().push(candidate);
+        }
+      }
+      by_account
+        .values()
+        .filter_map(|account| greedy(&[], account, payment_total, outputs, &fee_rate))
+        .min_by_key(|selected| selected.len())
+        .ok_or_else(not_enough_funds)?
+    }
+    "consolidate_dust" => {
+      // what one more input adds to the fee
+      let shape = TxShape::new(1, outputs);
+      let input_fee =
+        fee_rate.calculate_fee_from_weight(shape.input_size() + shape.input_proof_size());
+      let dust_threshold = match &params.dust_threshold {
+        Some(threshold) => threshold.parse::<u64>().map_err(|e| {
+          let message = format!("failed to parse dust_threshold '{}' : {:?}", threshold, e);
+          AbiError::invalid_input("dust_threshold", message)
+        })?,
+        None => input_fee * 10,
+      };
+      // dust that costs more to spend than it is worth is left alone
+      let dust: Vec<&Candidate> = ordered
+        .iter()
+        .copied()
+        .filter(|candidate| candidate.amount < dust_threshold && candidate.amount > input_fee)
+        .collect();
+      greedy(&dust, &ordered, payment_total, outputs, &fee_rate).ok_or_else(not_enough_funds)?
+    }
+    _ => greedy(&[], &ordered, payment_total, outputs, &fee_rate).ok_or_else(not_enough_funds)?,
+  };
+
+  let missing_inputs: Vec<u64> = selected
+    .iter()
+    .filter(|candidate| candidate.input.is_none())
+    .map(|candidate| candidate.index_on_blockchain)
+    .collect();
+  let mut fee = estimated_fee(selected.len(), outputs, &fee_rate);
+  let mut fee_source = "estimate";
+
+  if missing_inputs.is_empty() {
+    // every input has decoys: build the transaction and add inputs until necessary_fee() is covered
+    let account = selected[0].account;
+    let mut remaining: Vec<&Candidate> = ordered
+      .iter()
+      .copied()
+      .filter(|candidate| {
+        !selected
+          .iter()
+          .any(|selected| selected.index_on_blockchain == candidate.index_on_blockchain)
+          && (params.strategy != "same_account" || candidate.account == account)
+      })
+      .collect();
+    let tx = build_transaction(
+      &mut selected,
+      &mut remaining,
+      &payments,
+      &Change::new(viewpair.clone(), None),
+      Zeroizing::new([0; 32]), // this transaction is only built to compute the fee
+      fee_rate,
+    )?
+    .ok_or_else(not_enough_funds)?;
+    fee = tx.necessary_fee();
+    fee_source = "necessary_fee";
+  }
+
+  let total_input = selected
+    .iter()
+    .try_fold(0u64, |total, candidate| total.checked_add(candidate.amount))
+    .ok_or_else(|| AbiError::invalid_input("candidates", "input total overflows u64"))?;
+  Ok(CoinSelection {
+    selected: selected.iter().map(|candidate| candidate.index_on_blockchain).collect(),
+    total_input: total_input.to_string(),
+    fee: fee.to_string(),
+    change: total_input.saturating_sub(payment_total).saturating_sub(fee).to_string(),
+    fee_source,
+    missing_inputs,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{XMR, candidates_with_inputs, keys, output_json, payment_with_inputs};
+  use serde_json::{Value, json};
+
+  fn candidate(amount: u64, index_on_blockchain: u64, block_height: u64) -> Candidate {
+    Candidate { amount, index_on_blockchain, block_height, account: 0, input: None }
+  }
+  fn indices(selected: &[&Candidate]) -> Vec<u64> {
+    selected.iter().map(|candidate| candidate.index_on_blockchain).collect()
+  }
+  fn fee_rate() -> FeeRate {
+    FeeRate::new(20_000, 10_000).unwrap()
+  }
+
+  #[test]
+  fn fewest_inputs_takes_the_largest_outputs_first() {
+    let candidates = [candidate(1_000, 1, 0), candidate(900_000_000, 2, 0), candidate(5, 3, 0)];
+    let ordered = order_candidates("fewest_inputs", &candidates, 100).unwrap();
+    assert_eq!(indices(&ordered), vec![2, 1, 3]);
+    let selected = greedy(&[], &ordered, 100_000_000, 2, &fee_rate()).unwrap();
+    assert_eq!(indices(&selected), vec![2]);
+  }
+
+  #[test]
+  fn age_weighted_prefers_old_outputs_of_the_same_amount() {
+    let candidates = [candidate(1_000, 1, 90), candidate(1_000, 2, 10), candidate(50_000, 3, 99)];
+    let ordered = order_candidates("age_weighted", &candidates, 100).unwrap();
+    assert_eq!(indices(&ordered), vec![2, 3, 1]);
+    assert!(order_candidates("smallest_first", &candidates, 100).is_err());
+  }
+
+  #[test]
+  fn forced_dust_is_selected_before_the_rest() {
+    let candidates = [candidate(900_000_000, 1, 0), candidate(400_000_000, 2, 0)];
+    let ordered = order_candidates("consolidate_dust", &candidates, 100).unwrap();
+    let selected = greedy(&[&candidates[1]], &ordered, 100_000_000, 2, &fee_rate()).unwrap();
+    assert_eq!(indices(&selected), vec![2]);
+    let selected = greedy(&[&candidates[1]], &ordered, 500_000_000, 2, &fee_rate()).unwrap();
+    assert_eq!(indices(&selected), vec![2, 1]);
+    assert!(greedy(&[], &ordered, 1_300_000_000, 2, &fee_rate()).is_none());
+  }
+
+  fn params(strategy: &str, amounts: &[u64], payment: u64) -> Value {
+    let wallet = keys(7);
+    let candidates: Vec<Value> = amounts
+      .iter()
+      .enumerate()
+      .map(|(index, amount)| output_json(&wallet, *amount, index as u64, 100, false))
+      .collect();
+    json!({
+      "candidates": candidates,
+      "payments": [{ "address": keys(9).primary_address(), "amount": payment.to_string() }],
+      "fee_response": { "fee": 20_000, "quantization_mask": 10_000 },
+      "fee_priority": "normal",
+      "strategy": strategy,
+      "current_height": 200,
+    })
+  }
+
+  #[test]
+  fn every_strategy_builds_the_transaction_once_the_inputs_are_prepared() {
+    let wallet = keys(7);
+    // at the blockchain indexes 10, 20 and 30
+    let amounts = [2 * XMR, 3 * XMR, XMR / 100];
+    let payment = 4 * XMR;
+    for (strategy, expected) in [
+      ("fewest_inputs", vec![20, 10]),
+      ("same_account", vec![20, 10]),
+      ("age_weighted", vec![20, 10]),
+      ("consolidate_dust", vec![30, 20, 10]),
+    ] {
+      let mut params = params(strategy, &[], payment);
+      params["candidates"] = candidates_with_inputs(&wallet, &amounts).into();
+      params["dust_threshold"] = json!((XMR / 10).to_string());
+      let selection =
+        select_inputs(&params.to_string(), wallet.viewpair(), Network::Mainnet).unwrap();
+      assert_eq!(selection.selected, expected, "{}", strategy);
+      assert!(selection.missing_inputs.is_empty());
+      assert_eq!(selection.fee_source, "necessary_fee");
+
+      let selected: Vec<u64> =
+        selection.selected.iter().map(|index| amounts[*index as usize / 10 - 1]).collect();
+      let total_input: u64 = selected.iter().sum();
+      assert_eq!(selection.total_input, total_input.to_string());
+      // the fee doesn't depend on the order or the blockchain indexes of the inputs
+      let payments = vec![(keys(9).viewpair().legacy_address(Network::Mainnet), payment)];
+      let tx =
+        payment_with_inputs(&wallet, &selected, payments, Change::new(wallet.viewpair(), None));
+      let fee = tx.necessary_fee();
+      assert_eq!(selection.fee, fee.to_string(), "{}", strategy);
+      assert_eq!(selection.change, (total_input - payment - fee).to_string(), "{}", strategy);
+    }
+  }
+
+  #[test]
+  fn selection_without_decoys_reports_the_missing_inputs() {
+    let params = params("fewest_inputs", &[300_000_000, 800_000_000, 10_000], 1_000_000_000);
+    let wallet = keys(7);
+    let selection =
+      select_inputs(&params.to_string(), wallet.viewpair(), Network::Mainnet).unwrap();
+    assert_eq!(selection.selected, vec![1, 0]);
+    assert_eq!(selection.missing_inputs, vec![1, 0]);
+    assert_eq!(selection.fee_source, "estimate");
+    assert_eq!(selection.total_input, "1100000000");
+  }
+
+  #[test]
+  fn same_account_only_spends_from_the_requested_account() {
+    let mut params = params("same_account", &[300_000_000], 100_000_000);
+    let wallet = keys(7);
+    assert!(select_inputs(&params.to_string(), wallet.viewpair(), Network::Mainnet).is_ok());
+    params["account"] = json!(1);
+    let error =
+      select_inputs(&params.to_string(), wallet.viewpair(), Network::Mainnet).unwrap_err();
+    assert_eq!(error.code, NOT_ENOUGH_FUNDS);
+    assert_eq!(
+      error.message,
+      "not enough funds to cover payments and fee with strategy same_account"
+    );
+  }
+
+  #[test]
+  fn subtracting_the_fee_from_payments_is_rejected() {
+    let mut params = params("fewest_inputs", &[300_000_000], 100_000_000);
+    params["payments"][0]["subtract_fee_from_amount"] = json!(true);
+    let wallet = keys(7);
+    assert!(select_inputs(&params.to_string(), wallet.viewpair(), Network::Mainnet).is_err());
+  }
+
+  #[test]
+  fn overflowing_payments_are_an_error() {
+    let mut params = params("fewest_inputs", &[300_000_000], 0);
+    let payment = params["payments"][0].clone();
+    params["payments"] = json!([
+      { "address": payment["address"], "amount": u64::MAX.to_string() },
+      { "address": payment["address"], "amount": "1" },
+    ]);
+    let wallet = keys(7);
+    let error =
+      select_inputs(&params.to_string(), wallet.viewpair(), Network::Mainnet).unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("payments"));
+    assert_eq!(error.message, "payment total overflows u64");
+  }
+}
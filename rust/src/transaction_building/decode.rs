@@ -0,0 +1,301 @@
+use cuprate_rpc_types::bin::GetOutsResponse;
+use curve25519_dalek::{EdwardsPoint, Scalar};
+use monero_io::CompressedPoint;
+use monero_primitives::Commitment;
+use monero_wallet::{
+  extra::{Extra, PaymentId},
+  ringct::RctPrunable,
+  transaction::{Input, Transaction},
+};
+use rand_core::OsRng;
+use serde::Serialize;
+
+use crate::block_parsing::amount_to_string;
+use crate::error::AbiError;
+
+#[derive(Debug, Serialize)]
+pub struct DecodedInput {
+  key_image: String,
+  /// absolute indices of the ring members, request them with build_getoutsbin_request to verify
+  ring_member_indices: Vec<u64>,
+}
+#[derive(Debug, Serialize)]
+pub struct DecodedOutput {
+  key: String,
+  view_tag: Option<u8>,
+}
+#[derive(Debug, Serialize)]
+pub struct DecodedExtra {
+  /// the raw tx extra, hex encoded
+  raw: String,
+  tx_public_key: Option<String>,
+  additional_public_keys: Vec<String>,
+  payment_id: Option<String>,
+  data: Vec<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct Verification {
+  /// one entry per input
+  clsags: Vec<bool>,
+  bulletproof: bool,
+  /// pseudo outs = output commitments + fee
+  balanced: bool,
+  valid: bool,
+}
+#[derive(Debug, Serialize)]
+pub struct DecodedTransaction {
+  tx_hash: String,
+  size: usize,
+  weight: usize,
+  #[serde(serialize_with = "amount_to_string")]
+  fee: u64,
+  rct_type: String,
+  inputs: Vec<DecodedInput>,
+  outputs: Vec<DecodedOutput>,
+  extra: DecodedExtra,
+  /// only set if the ring members were supplied
+  verification: Option<Verification>,
+}
+
+fn decompress(point: &CompressedPoint) -> Result<EdwardsPoint, String> {
+  point.decompress().ok_or_else(|| format!("invalid point {}", hex::encode(point.to_bytes())))
+}
+
+fn decode_extra(raw: &[u8]) -> DecodedExtra {
+  // an extra that doesn't parse is still relayed by the daemon, report it raw
+  let extra = Extra::read::<&[u8]>(&mut raw.as_ref()).ok();
+  let keys = extra.as_ref().and_then(|extra| extra.keys());
+  DecodedExtra {
+    raw: hex::encode(raw),
+    tx_public_key: keys
+      .as_ref()
+      .and_then(|(keys, _)| keys.first())
+      .map(|key| hex::encode(key.compress().to_bytes())),
+    additional_public_keys: keys
+      .and_then(|(_, additional)| additional)
+      .unwrap_or_default()
+      .iter()
+      .map(|key| hex::encode(key.compress().to_bytes()))
+      .collect(),
+    payment_id: extra.as_ref().and_then(|extra| extra.payment_id()).map(|payment_id| {
+      match payment_id {
+        PaymentId::Unencrypted(id) => hex::encode(id),
+        PaymentId::Encrypted(id) => hex::encode(id),
+      }
+    }),
+    data: extra.map(|extra| extra.data()).unwrap_or_default().iter().map(hex::encode).collect(),
+  }
+}
+
+/// verifies the CLSAGs against the ring members (in the order of ring_member_indices,
+/// input after input), the Bulletproof+ and that the commitments balance
+fn verify(
+  tx: &Transaction,
+  key_images: &[EdwardsPoint],
+  rings: Vec<Vec<[EdwardsPoint; 2]>>,
+) -> Result<Verification, String> {
+  let Transaction::V2 { proofs: Some(proofs), .. } = tx else {
+    return Err("only RingCT transactions can be verified".to_string());
+  };
+  let RctPrunable::Clsag { bulletproof, clsags, pseudo_outs } = &proofs.prunable else {
+    return Err(format!("can't verify a {:?} transaction", proofs.rct_type()));
+  };
+  let signature_hash = tx.signature_hash().ok_or("transaction has no signature hash")?;
+  let commitments = proofs
+    .base
+    .commitments
+    .iter()
+    .map(decompress)
+    .collect::<Result<Vec<EdwardsPoint>, String>>()?;
+  let pseudo_outs = pseudo_outs.iter().map(decompress).collect::<Result<Vec<_>, String>>()?;
+  if clsags.len() != key_images.len() || pseudo_outs.len() != key_images.len() {
+    return Err("number of CLSAGs does not match the number of inputs".to_string());
+  }
+
+  let mut clsag_results = Vec::with_capacity(clsags.len());
+  for (i, ring) in rings.into_iter().enumerate() {
+    clsag_results
+      .push(clsags[i].verify(ring, &key_images[i], &pseudo_outs[i], &signature_hash).is_ok());
+  }
+  let bulletproof = bulletproof.verify(&mut OsRng, &commitments);
+  let fee_commitment = Commitment::new(Scalar::ZERO, proofs.base.fee).calculate();
+  let balanced = pseudo_outs.iter().sum::<EdwardsPoint>()
+    == commitments.iter().sum::<EdwardsPoint>() + fee_commitment;
+  Ok(Verification {
+    valid: clsag_results.iter().all(|valid| *valid) && bulletproof && balanced,
+    clsags: clsag_results,
+    bulletproof,
+    balanced,
+  })
+}
+
+/// decodes a signed transaction, with ring members (getouts.bin for ring_member_indices)
+/// the proofs are verified too
+pub fn decode_transaction(
+  tx_hex: &str,
+  ring_members: Option<GetOutsResponse>,
+) -> Result<DecodedTransaction, AbiError> {
+  let invalid = |message: String| AbiError::invalid_input("transaction", message);
+  let invalid_response = |message: String| AbiError::invalid_input("getouts_response", message);
+  let blob = hex::decode(tx_hex)
+    .map_err(|e| invalid(format!("failed to decode transaction hex: {:?}", e)))?;
+  let mut reader = blob.as_slice();
+  let tx = Transaction::read::<&[u8]>(&mut reader)
+    .map_err(|e| invalid(format!("failed to read transaction: {:?}", e)))?;
+  if !reader.is_empty() {
+    return Err(invalid(format!("{} trailing bytes after the transaction", reader.len())));
+  }
+
+  let mut inputs = vec![];
+  let mut key_images = vec![];
+  for input in &tx.prefix().inputs {
+    let Input::ToKey { key_offsets, key_image, .. } = input else {
+      return Err(invalid("coinbase transactions can't be decoded here".to_string()));
+    };
+    // key offsets are relative to the previous ring member
+    let mut absolute = 0u64;
+    let ring_member_indices = key_offsets
+      .iter()
+      .map(|offset| {
+        absolute = absolute.saturating_add(*offset);
+        absolute
+      })
+      .collect();
+    key_images.push(decompress(key_image).map_err(invalid)?);
+    inputs.push(DecodedInput { key_image: hex::encode(key_image.to_bytes()), ring_member_indices });
+  }
+  let outputs = tx
+    .prefix()
+    .outputs
+    .iter()
+    .map(|output| DecodedOutput {
+      key: hex::encode(output.key.to_bytes()),
+      view_tag: output.view_tag,
+    })
+    .collect();
+  let (fee, rct_type) = match &tx {
+    Transaction::V2 { proofs: Some(proofs), .. } => {
+      (proofs.base.fee, format!("{:?}", proofs.rct_type()))
+    }
+    _ => return Err(invalid("only RingCT transactions are supported".to_string())),
+  };
+
+  let verification = match ring_members {
+    Some(response) => {
+      let ring_sizes: Vec<usize> =
+        inputs.iter().map(|input| input.ring_member_indices.len()).collect();
+      if response.outs.len() != ring_sizes.iter().sum::<usize>() {
+        return Err(invalid_response(format!(
+          "getouts.bin response has {} ring members, the transaction references {}",
+          response.outs.len(),
+          ring_sizes.iter().sum::<usize>()
+        )));
+      }
+      let mut outs = response.outs.into_iter();
+      let mut rings = Vec::with_capacity(ring_sizes.len());
+      for ring_size in ring_sizes {
+        let mut ring = Vec::with_capacity(ring_size);
+        for out in outs.by_ref().take(ring_size) {
+          ring.push([
+            decompress(&CompressedPoint(out.key)).map_err(invalid_response)?,
+            decompress(&CompressedPoint(out.mask)).map_err(invalid_response)?,
+          ]);
+        }
+        rings.push(ring);
+      }
+      Some(verify(&tx, &key_images, rings).map_err(invalid)?)
+    }
+    None => None,
+  };
+
+  Ok(DecodedTransaction {
+    tx_hash: hex::encode(tx.hash()),
+    size: blob.len(),
+    weight: tx.weight(),
+    fee,
+    rct_type,
+    inputs,
+    outputs,
+    extra: decode_extra(&tx.prefix().extra),
+    verification,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{XMR, keys, payment, ring_members, sign};
+  use monero_wallet::{address::Network, send::Change};
+
+  /// keys(7) pays keys(9), signed
+  fn signed() -> Transaction {
+    let payer = keys(7);
+    let recipient = keys(9).viewpair().legacy_address(Network::Mainnet);
+    sign(&payer, payment(&payer, vec![(recipient, XMR)], Change::new(payer.viewpair(), None)))
+  }
+  fn verification(tx: &Transaction) -> Verification {
+    let decoded = decode_transaction(&hex::encode(tx.serialize()), Some(ring_members(&keys(7))));
+    decoded.unwrap().verification.unwrap()
+  }
+
+  #[test]
+  fn decodes_and_verifies_a_signed_transaction() {
+    let tx = signed();
+    let decoded = decode_transaction(&hex::encode(tx.serialize()), None).unwrap();
+    assert_eq!(decoded.tx_hash, hex::encode(tx.hash()));
+    assert_eq!(decoded.weight, tx.weight());
+    assert_eq!(decoded.inputs.len(), 1);
+    // the input spends the output at index 10, the 4th of a ring of consecutive outputs
+    assert_eq!(decoded.inputs[0].ring_member_indices[3], 10);
+    assert_eq!(decoded.outputs.len(), 2);
+    assert!(decoded.extra.tx_public_key.is_some());
+    assert!(decoded.verification.is_none());
+
+    let verification = verification(&tx);
+    assert_eq!(verification.clsags, vec![true]);
+    assert!(verification.bulletproof && verification.balanced && verification.valid);
+  }
+
+  #[test]
+  fn a_changed_commitment_fails_verification() {
+    let mut tx = signed();
+    let Transaction::V2 { proofs: Some(proofs), .. } = &mut tx else { unreachable!() };
+    // the commitment to one more piconero
+    let commitment = decompress(&proofs.base.commitments[0]).unwrap()
+      + Commitment::new(Scalar::ZERO, 1).calculate();
+    proofs.base.commitments[0] = CompressedPoint(commitment.compress().to_bytes());
+    let verification = verification(&tx);
+    assert!(!verification.bulletproof);
+    assert!(!verification.balanced);
+    assert!(!verification.valid);
+  }
+
+  #[test]
+  fn wrong_key_offsets_fail_verification() {
+    let mut tx = signed();
+    let Transaction::V2 { prefix, .. } = &mut tx else { unreachable!() };
+    let Input::ToKey { key_offsets, .. } = &mut prefix.inputs[0] else { unreachable!() };
+    key_offsets[0] += 1;
+    let verification = verification(&tx);
+    assert_eq!(verification.clsags, vec![false]);
+    assert!(verification.balanced);
+    assert!(!verification.valid);
+  }
+
+  #[test]
+  fn truncated_transactions_and_rings_are_invalid_input() {
+    let blob = signed().serialize();
+    let error = decode_transaction(&hex::encode(&blob[..blob.len() - 1]), None).unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("transaction"));
+    let mut trailing = blob.clone();
+    trailing.push(0);
+    let error = decode_transaction(&hex::encode(trailing), None).unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("transaction"));
+
+    let mut ring_members = ring_members(&keys(7));
+    ring_members.outs.pop();
+    let error = decode_transaction(&hex::encode(blob), Some(ring_members)).unwrap_err();
+    assert_eq!(error.code, crate::error::INVALID_INPUT);
+    assert_eq!(error.field.as_deref(), Some("getouts_response"));
+  }
+}
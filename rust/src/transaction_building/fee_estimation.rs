@@ -0,0 +1,212 @@
+use monero_wallet::rpc::FeeRate;
+use serde::{Deserialize, Serialize};
+
+use super::transaction::{FeeResponse, parse_fee_rate};
+use crate::block_parsing::{amount_to_string, view_tags::write_varint};
+use crate::error::AbiError;
+
+pub const RING_LEN: usize = 16;
+/// wallet2 assumes 2 bytes per relative key offset when estimating
+pub const DEFAULT_KEY_OFFSETS_SIZE: usize = RING_LEN * 2;
+/// every transaction has at least two outputs, a dummy change output is added otherwise
+pub const MIN_OUTPUTS: usize = 2;
+
+/// everything the weight of a CLSAG + Bulletproof+ transaction depends on. the weight is an
+/// estimate in the way of wallet2's estimate_tx_weight, not what necessary_fee() computes from
+/// a built transaction: extra and key offsets are only known after building it
+#[derive(Debug, Clone, Deserialize)]
+pub struct TxShape {
+  pub inputs: usize,
+  /// including change
+  pub outputs: usize,
+  /// size of the serialized tx extra, computed from the other fields if not set
+  pub extra_size: Option<usize>,
+  /// the arbitrary data that will be put into tx extra (length of each entry)
+  #[serde(default)]
+  pub data_sizes: Vec<usize>,
+  /// one of the destinations is an integrated address
+  #[serde(default)]
+  pub payment_id: bool,
+  /// one of the destinations is a subaddress, assumed to need additional tx keys with more than
+  /// 2 outputs (SignableTransaction decides that from the actual payments and change)
+  #[serde(default)]
+  pub subaddress_destinations: bool,
+  /// serialized size of the key offsets of one input, unknown before decoys are selected
+  pub key_offsets_size: Option<usize>,
+}
+#[derive(Debug, Deserialize)]
+struct EstimateFeeParams {
+  #[serde(flatten)]
+  shape: TxShape,
+  fee_response: FeeResponse,
+  fee_priority: String,
+}
+#[derive(Debug, Serialize)]
+pub struct FeeEstimate {
+  pub weight: usize,
+  pub size: usize,
+  #[serde(serialize_with = "amount_to_string")]
+  pub fee: u64,
+}
+
+fn varint_len(value: usize) -> usize {
+  let mut buf = vec![];
+  write_varint(value as u64, &mut buf);
+  buf.len()
+}
+
+impl TxShape {
+  pub fn new(inputs: usize, outputs: usize) -> TxShape {
+    TxShape {
+      inputs,
+      outputs,
+      extra_size: None,
+      data_sizes: vec![],
+      payment_id: false,
+      subaddress_destinations: false,
+      key_offsets_size: None,
+    }
+  }
+
+  fn outputs(&self) -> usize {
+    self.outputs.max(MIN_OUTPUTS)
+  }
+
+  /// approximates the tx extra of SignableTransaction: tx key, additional keys, payment id, data
+  pub fn extra_size(&self) -> usize {
+    if let Some(extra_size) = self.extra_size {
+      return extra_size;
+    }
+    let outputs = self.outputs();
+    let mut size = 1 + 32;
+    if self.subaddress_destinations && outputs != 2 {
+      size += 1 + varint_len(outputs) + 32 * outputs;
+    }
+    // a dummy payment id is added to 2 output transactions
+    if self.payment_id || outputs == 2 {
+      size += 1 + 1 + 1 + 8;
+    }
+    for data_size in &self.data_sizes {
+      size += 1 + varint_len(data_size + 1) + 1 + data_size;
+    }
+    size
+  }
+
+  /// serialized size of one input: type, amount, ring size, key offsets, key image
+  pub fn input_size(&self) -> usize {
+    1 + 1 + varint_len(RING_LEN) + self.key_offsets_size.unwrap_or(DEFAULT_KEY_OFFSETS_SIZE) + 32
+  }
+
+  /// signature data of one input: CLSAG (s, c1, D) + pseudo out
+  pub fn input_proof_size(&self) -> usize {
+    32 * RING_LEN + 32 + 32 + 32
+  }
+
+  fn log_padded_outputs(&self) -> usize {
+    let mut log_padded_outputs = 0;
+    while (1 << log_padded_outputs) < self.outputs() {
+      log_padded_outputs += 1;
+    }
+    log_padded_outputs
+  }
+
+  /// serialized size for a given fee (the fee is a varint, so it changes the size)
+  pub fn size(&self, fee: u64) -> usize {
+    let outputs = self.outputs();
+    let extra_size = self.extra_size();
+    // prefix
+    let mut size = 1 + 1; // version, unlock time
+    size += varint_len(self.inputs) + self.inputs * self.input_size();
+    size += varint_len(outputs) + outputs * (1 + 1 + 32 + 1); // amount, type, key, view tag
+    size += varint_len(extra_size) + extra_size;
+    // rct base
+    let mut fee_varint = vec![];
+    write_varint(fee, &mut fee_varint);
+    size += 1 + fee_varint.len() + outputs * (8 + 32); // type, fee, encrypted amounts, commitments
+    // rct prunable: one Bulletproof+ (A, A1, B, r1, s1, d1, L, R), CLSAGs, pseudo outs
+    let lr = 6 + self.log_padded_outputs();
+    size += 1 + 6 * 32 + 2 * (varint_len(lr) + lr * 32);
+    size += self.inputs * self.input_proof_size();
+    size
+  }
+
+  /// the weight is the size plus the bulletproof clawback for more than 2 outputs
+  pub fn weight(&self, fee: u64) -> usize {
+    let mut weight = self.size(fee);
+    if self.outputs() > 2 {
+      let bp_base = (32 * 6 + 7 * 2 * 32) / 2;
+      let padded_outputs = 1 << self.log_padded_outputs();
+      let bp_size = 32 * (6 + 2 * (6 + self.log_padded_outputs()));
+      weight += (bp_base * padded_outputs - bp_size) * 4 / 5;
+    }
+    weight
+  }
+
+  pub fn estimate(&self, fee_rate: &FeeRate) -> FeeEstimate {
+    // the fee is part of the transaction, iterate until the fee varint length is stable
+    let mut fee = 0;
+    loop {
+      let weight = self.weight(fee);
+      let necessary_fee = fee_rate.calculate_fee_from_weight(weight);
+      if necessary_fee <= fee {
+        return FeeEstimate { weight, size: self.size(fee), fee };
+      }
+      fee = necessary_fee;
+    }
+  }
+}
+
+pub fn estimate_fee(json_params: &str) -> Result<FeeEstimate, AbiError> {
+  let params: EstimateFeeParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse estimate_fee params json: {:?}", e),
+    )
+  })?;
+  if params.shape.inputs == 0 {
+    return Err(AbiError::invalid_input("inputs", "a transaction needs at least one input"));
+  }
+  let fee_rate = parse_fee_rate(&params.fee_priority, params.fee_response)?;
+  Ok(params.shape.estimate(&fee_rate))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{XMR, keys, payment_with_inputs, sign};
+  use monero_wallet::{address::Network, send::Change};
+
+  #[test]
+  fn estimates_cover_built_transactions_of_every_shape() {
+    let payer = keys(7);
+    let recipient = keys(9).viewpair().legacy_address(Network::Mainnet);
+    let fee_rate = FeeRate::new(20_000, 10_000).unwrap();
+    // (input amounts, payments), the change is one more output
+    let shapes: [(&[u64], usize); 5] = [
+      (&[5 * XMR], 1),
+      (&[5 * XMR], 2),
+      (&[5 * XMR], 15),
+      (&[XMR, 2 * XMR, 3 * XMR], 1),
+      (&[XMR, 2 * XMR, 3 * XMR, 4 * XMR], 15),
+    ];
+    for (input_amounts, payments) in shapes {
+      let tx = payment_with_inputs(
+        &payer,
+        input_amounts,
+        vec![(recipient, XMR / 10); payments],
+        Change::new(payer.viewpair(), None),
+      );
+      let necessary_fee = tx.necessary_fee();
+      let weight = sign(&payer, tx).weight();
+
+      let estimate = TxShape::new(input_amounts.len(), payments + 1).estimate(&fee_rate);
+      let shape = format!("{} inputs, {} outputs", input_amounts.len(), payments + 1);
+      assert!(estimate.fee >= necessary_fee, "{}: {} < {}", shape, estimate.fee, necessary_fee);
+      assert!(estimate.weight >= weight, "{}: {} < {}", shape, estimate.weight, weight);
+      // the estimate assumes 2 bytes per key offset, the test rings need 1
+      let slack = input_amounts.len() * DEFAULT_KEY_OFFSETS_SIZE / 2 + 64;
+      assert!(estimate.weight <= weight + slack, "{}: {} > {}", shape, estimate.weight, weight);
+      assert!(estimate.fee <= fee_rate.calculate_fee_from_weight(weight + slack), "{}", shape);
+    }
+  }
+}
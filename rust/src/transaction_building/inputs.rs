@@ -5,10 +5,12 @@ use rand_core::OsRng;
 use zeroize::Zeroizing;
 use std::{io::Cursor};
 
-use curve25519_dalek::{Scalar, edwards::CompressedEdwardsY, constants::ED25519_BASEPOINT_TABLE};
+use curve25519_dalek::{edwards::CompressedEdwardsY, constants::ED25519_BASEPOINT_TABLE};
 use monero_wallet::{rpc::OutputInformation, OutputWithDecoys, WalletOutput};
 use serde::Deserialize;
-use hex::FromHex;
+
+use crate::error::AbiError;
+use crate::keypairs::parse_secret_key;
 
 #[derive(Debug, Deserialize)]
 struct SampleCandidatesJson {
@@ -17,33 +19,49 @@ struct SampleCandidatesJson {
   candidates_len: usize,
 }
 
-pub fn sample_candidates(sample_json_str: &str) -> Result<Vec<u64>, monero_wallet::rpc::RpcError> {
-  let sample_json: SampleCandidatesJson = serde_json::from_str(sample_json_str).unwrap();
+pub fn sample_candidates(sample_json_str: &str) -> Result<Vec<u64>, AbiError> {
+  let sample_json: SampleCandidatesJson = serde_json::from_str(sample_json_str).map_err(|e| {
+    let message = format!("failed to parse sample candidates params json: {}", e);
+    AbiError::invalid_input("json_params", message)
+  })?;
   let mut rng = OsRng;
-  OutputWithDecoys::sample_candidates(
+  let candidates = OutputWithDecoys::sample_candidates(
     &mut rng,
     sample_json.output_being_spent_index,
     &sample_json.distribution,
     sample_json.candidates_len,
   )
+  .map_err(|e| format!("failed to sample candidates: {:?}", e))?;
+  Ok(candidates)
 }
 /// convert outkeys from GetOutsResponse ( cuprate rpc type) to OutputInformation from monero oxide
 pub fn convert_outkey_to_output_information(
   outkey: OutKeyBin,
-) -> Result<OutputInformation, String> {
+) -> Result<OutputInformation, AbiError> {
   // legacy name of the rpc for commitment is mask(as in, masks the amount)
+  let commitment = CompressedPoint(outkey.mask)
+    .decompress()
+    .ok_or_else(|| AbiError::invalid_input("getouts_response", "invalid point"))?;
   Ok(OutputInformation {
     height: outkey.height as usize,
     unlocked: outkey.unlocked,
     key: CompressedEdwardsY(outkey.key),
-    commitment: CompressedPoint(outkey.mask).decompress().ok_or("invalid point")?,
+    commitment,
     transaction: outkey.txid,
   })
 }
-pub fn read_output_from_string(output_hex_string: &str) -> Result<WalletOutput, String> {
-  let output_bytes: Vec<u8> = hex::decode(output_hex_string).unwrap();
+/// field is the name of the input or json key the output came from, for the error
+pub fn read_output_from_string(
+  output_hex_string: &str,
+  field: &str,
+) -> Result<WalletOutput, AbiError> {
+  let output_bytes: Vec<u8> = hex::decode(output_hex_string).map_err(|e| {
+    AbiError::invalid_input(field, format!("failed to parse serialized output hex: {:?}", e))
+  })?;
   let mut reader = Cursor::new(output_bytes);
-  WalletOutput::read(&mut reader).map_err(|e| format!("failed to read  serialized output: {:?}", e))
+  WalletOutput::read(&mut reader).map_err(|e| {
+    AbiError::invalid_input(field, format!("failed to read  serialized output: {:?}", e))
+  })
 }
 #[derive(Debug, Deserialize)]
 struct InputJson {
@@ -53,7 +71,7 @@ struct InputJson {
 pub fn make_input_sync(
   input_sts: &str,
   outs_resonse: GetOutsResponse,
-) -> Result<OutputWithDecoys, String> {
+) -> Result<OutputWithDecoys, AbiError> {
   match outs_resonse
     .outs
     .into_iter()
@@ -61,12 +79,14 @@ pub fn make_input_sync(
     .collect()
   {
     Ok(outs) => {
-      let input_json: InputJson = serde_json::from_str(input_sts)
-        .map_err(|e| format!("failed to parse arguments JSON (missing or invalid field): {}", e))?;
-      let output = read_output_from_string(&input_json.serialized_input)?;
+      let input_json: InputJson = serde_json::from_str(input_sts).map_err(|e| {
+        let message = format!("failed to parse arguments JSON (missing or invalid field): {}", e);
+        AbiError::invalid_input("output_json", message)
+      })?;
+      let output = read_output_from_string(&input_json.serialized_input, "serialized_input")?;
       let mut rng = OsRng;
       let output = OutputWithDecoys::new_sync(&mut rng, 16, output, outs, input_json.candidates);
-      output.map_err(|e| e.to_string())
+      Ok(output.map_err(|e| e.to_string())?)
     }
     Err(e) => Err(e),
   }
@@ -75,17 +95,40 @@ pub fn make_input_sync(
 pub fn compute_key_image(
   output_hex_string: String,
   sender_spend_key: String,
-) -> Result<String, String> {
-  let output = read_output_from_string(&output_hex_string)?;
+) -> Result<String, AbiError> {
+  let output = read_output_from_string(&output_hex_string, "output")?;
 
-  let spend_bytes = <[u8; 32]>::from_hex(sender_spend_key).unwrap();
-  let spend_scalar = Zeroizing::new(Scalar::from_canonical_bytes(spend_bytes).unwrap()); // okay to panic if spend_key is invalid
+  let spend_scalar = parse_secret_key("sender_spend_key", &sender_spend_key)?;
 
   let input_key = Zeroizing::new(spend_scalar.deref() + output.key_offset());
   if (input_key.deref() * ED25519_BASEPOINT_TABLE) != output.key() {
-    Err("Wrong private key to compute key image for this output.")?;
+    let message = "Wrong private key to compute key image for this output.";
+    return Err(AbiError::invalid_input("sender_spend_key", message));
   }
   let key_image = input_key.deref()
     * monero_wallet::generators::biased_hash_to_point(output.key().compress().to_bytes());
   Ok(hex::encode(key_image.compress().to_bytes()))
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::error::INVALID_INPUT;
+  use crate::test_util::{XMR, keys, output_json};
+
+  #[test]
+  fn key_images_need_the_spend_key_of_the_output() {
+    let output = output_json(&keys(9), XMR, 10, 100, false);
+    let output = output["serialized"].as_str().unwrap().to_string();
+    assert!(compute_key_image(output.clone(), keys(9).spend_key_hex()).is_ok());
+    // another wallet's key, bad hex, a non-canonical scalar
+    for spend_key in [keys(11).spend_key_hex(), "abcd".to_string(), "ff".repeat(32)] {
+      let error = compute_key_image(output.clone(), spend_key).unwrap_err();
+      assert_eq!(error.code, INVALID_INPUT);
+      assert_eq!(error.field.as_deref(), Some("sender_spend_key"));
+    }
+    let error = compute_key_image("00".repeat(40), keys(9).spend_key_hex()).unwrap_err();
+    assert_eq!(error.code, INVALID_INPUT);
+    assert_eq!(error.field.as_deref(), Some("output"));
+  }
+}
@@ -0,0 +1,372 @@
+use core::ops::Deref;
+use std::io::Cursor;
+
+use curve25519_dalek::{
+  EdwardsPoint, Scalar, constants::ED25519_BASEPOINT_TABLE, edwards::CompressedEdwardsY,
+};
+use monero_wallet::{
+  OutputWithDecoys, ViewPair,
+  address::{MoneroAddress, Network, SubaddressIndex},
+  send::SignableTransaction,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::transaction::parse_address_for_network;
+use crate::block_parsing::{
+  amount_to_string,
+  view_tags::{read_byte, read_bytes, read_varint},
+};
+use crate::error::AbiError;
+use crate::keypairs::parse_secret_key;
+
+#[derive(Debug, Deserialize)]
+struct InspectParams {
+  signable_transaction: String,
+  /// with the spend key the key images of the inputs are computed too
+  spend_key: Option<String>,
+  /// change to an address that is not the primary address of this wallet is an error
+  /// unless this is set, the scanner won't find that change
+  #[serde(default)]
+  allow_external_change: bool,
+}
+#[derive(Debug, Serialize)]
+pub struct InspectedPayment {
+  address: String,
+  #[serde(serialize_with = "amount_to_string")]
+  amount: u64,
+}
+#[derive(Debug, Serialize)]
+pub struct InspectedChange {
+  address: String,
+  /// None for change to the primary address or to an address without view key
+  subaddress_index: Option<[u32; 2]>,
+  /// the change goes to a change_address that is not the primary address of this wallet,
+  /// the scanner won't find it
+  external: bool,
+  #[serde(serialize_with = "amount_to_string")]
+  amount: u64,
+}
+#[derive(Debug, Serialize)]
+pub struct InspectedInput {
+  #[serde(serialize_with = "amount_to_string")]
+  amount: u64,
+  key_image: Option<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct Inspection {
+  recipients: Vec<InspectedPayment>,
+  change: Option<InspectedChange>,
+  #[serde(serialize_with = "amount_to_string")]
+  fee: u64,
+  inputs: Vec<InspectedInput>,
+  #[serde(serialize_with = "amount_to_string")]
+  total_input: u64,
+  /// arbitrary data that will be put into tx extra, hex encoded
+  data: Vec<String>,
+}
+
+/// the change as it is serialized by SignableTransaction::write
+pub(crate) enum SerializedChange {
+  AddressOnly(String),
+  /// spend key, view key, subaddress index (0, 0 for the primary address)
+  ViewPair(EdwardsPoint, Zeroizing<Scalar>, u32, u32),
+}
+
+fn read_string(reader: &mut &[u8]) -> Result<String, String> {
+  let len = read_varint(reader)? as usize;
+  if reader.len() < len {
+    return Err("unexpected end of signable transaction".to_string());
+  }
+  let (bytes, rest) = reader.split_at(len);
+  *reader = rest;
+  String::from_utf8(bytes.to_vec()).map_err(|e| format!("invalid utf8 in address: {:?}", e))
+}
+fn read_u32(reader: &mut &[u8]) -> Result<u32, String> {
+  Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+fn read_u64(reader: &mut &[u8]) -> Result<u64, String> {
+  Ok(u64::from_le_bytes(read_bytes(reader)?))
+}
+
+/// SignableTransaction has no accessors, so this walks its serialization:
+/// rct type, outgoing view key, inputs, payments (0: payment, 1: address only change,
+/// 2 and 3: change with view pair), data, fee rate. the tests below pin this format against
+/// transactions built with SignableTransaction::new
+pub(crate) fn decode(
+  mut reader: &[u8],
+) -> Result<
+  (Vec<OutputWithDecoys>, Vec<(String, u64)>, Option<SerializedChange>, Vec<Vec<u8>>),
+  String,
+> {
+  let reader = &mut reader;
+  read_byte(reader)?; // rct type
+  read_bytes::<32>(reader)?; // outgoing view key
+
+  let mut inputs = vec![];
+  for _ in 0..read_varint(reader)? {
+    inputs
+      .push(OutputWithDecoys::read(reader).map_err(|e| format!("failed to read input: {:?}", e))?);
+  }
+
+  let mut payments = vec![];
+  let mut change = None;
+  for _ in 0..read_varint(reader)? {
+    match read_byte(reader)? {
+      0 => {
+        let address = read_string(reader)?;
+        payments.push((address, read_u64(reader)?));
+      }
+      1 => change = Some(SerializedChange::AddressOnly(read_string(reader)?)),
+      2 | 3 => {
+        let spend =
+          CompressedEdwardsY(read_bytes(reader)?).decompress().ok_or("invalid change spend key")?;
+        let view = Option::<Scalar>::from(Scalar::from_canonical_bytes(read_bytes(reader)?))
+          .ok_or("invalid change view key")?;
+        let account = read_u32(reader)?;
+        let address = read_u32(reader)?;
+        change = Some(SerializedChange::ViewPair(spend, Zeroizing::new(view), account, address));
+      }
+      kind => return Err(format!("unknown payment kind {} in signable transaction", kind)),
+    }
+  }
+
+  let mut data = vec![];
+  for _ in 0..read_varint(reader)? {
+    let len = read_varint(reader)? as usize;
+    if reader.len() < len {
+      return Err("unexpected end of signable transaction".to_string());
+    }
+    let (bytes, rest) = reader.split_at(len);
+    data.push(bytes.to_vec());
+    *reader = rest;
+  }
+  Ok((inputs, payments, change, data))
+}
+
+fn key_image(input: &OutputWithDecoys, spend_key: &Scalar) -> Result<String, AbiError> {
+  let input_key = Zeroizing::new(spend_key + input.key_offset());
+  if (input_key.deref() * ED25519_BASEPOINT_TABLE) != input.key() {
+    let message = "the spend key does not own all inputs of this transaction";
+    return Err(AbiError::invalid_input("spend_key", message));
+  }
+  let key_image = input_key.deref()
+    * monero_wallet::generators::biased_hash_to_point(input.key().compress().to_bytes());
+  Ok(hex::encode(key_image.compress().to_bytes()))
+}
+
+/// decodes a SignableTransaction so a signer can show what it is about to approve.
+/// fails if the change goes to another wallet, which make_transaction only does with a
+/// change_address and the caller opting in with allow_external_change
+pub fn inspect_signable_transaction(
+  json_params: &str,
+  viewpair: &ViewPair,
+  view_key: &Scalar,
+  network: Network,
+) -> Result<Inspection, AbiError> {
+  let params: InspectParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input("json_params", format!("failed to parse inspect params json: {:?}", e))
+  })?;
+  let invalid = |message: String| AbiError::invalid_input("signable_transaction", message);
+  let bytes = hex::decode(&params.signable_transaction)
+    .map_err(|e| invalid(format!("failed to decode signable transaction hex: {:?}", e)))?;
+  let transaction = SignableTransaction::read(&mut Cursor::new(&bytes))
+    .map_err(|e| invalid(format!("failed to read SignableTransaction: {:?}", e)))?;
+  let (inputs, payments, change, data) = decode(&bytes).map_err(invalid)?;
+
+  let spend_key = params
+    .spend_key
+    .as_deref()
+    .map(|spend_key| parse_secret_key("spend_key", spend_key))
+    .transpose()?;
+  let mut inspected_inputs = Vec::with_capacity(inputs.len());
+  for input in &inputs {
+    inspected_inputs.push(InspectedInput {
+      amount: input.commitment().amount,
+      key_image: match &spend_key {
+        Some(spend_key) => Some(key_image(input, spend_key)?),
+        None => None,
+      },
+    });
+  }
+  let total_input = inspected_inputs
+    .iter()
+    .try_fold(0u64, |total, input| total.checked_add(input.amount))
+    .ok_or_else(|| invalid("the input amounts overflow".to_string()))?;
+  let payment_total = payments
+    .iter()
+    .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+    .ok_or_else(|| invalid("the payment amounts overflow".to_string()))?;
+  let not_enough = || invalid("the inputs do not cover the payments and the fee".to_string());
+  let available = total_input.checked_sub(payment_total).ok_or_else(not_enough)?;
+  // without change everything the payments don't take is burned as fee
+  let fee = if change.is_some() { transaction.necessary_fee() } else { available };
+  let change_amount = available.checked_sub(fee).ok_or_else(not_enough)?;
+
+  let change = match change {
+    None => None,
+    // change_address of make_transaction
+    Some(SerializedChange::AddressOnly(address)) => {
+      parse_address_for_network(&address, network)
+        .map_err(|e| e.with_field("signable_transaction"))?;
+      let external = address != viewpair.legacy_address(network).to_string();
+      if external && !params.allow_external_change {
+        return Err(invalid(format!(
+          "change goes to a foreign address: {}, set allow_external_change to accept it",
+          address
+        )));
+      }
+      Some(InspectedChange { address, subaddress_index: None, external, amount: change_amount })
+    }
+    Some(SerializedChange::ViewPair(spend, view, account, minor)) => {
+      if spend != viewpair.spend() || view.deref() != view_key {
+        let foreign = ViewPair::new(spend, view)
+          .map_err(|e| invalid(format!("invalid change view pair: {:?}", e)))?;
+        let address = match SubaddressIndex::new(account, minor) {
+          Some(index) => foreign.subaddress(network, index),
+          None => foreign.legacy_address(network),
+        };
+        return Err(invalid(format!("change goes to a foreign address: {}", address)));
+      }
+      let index = SubaddressIndex::new(account, minor);
+      let address: MoneroAddress = match index {
+        Some(index) => viewpair.subaddress(network, index),
+        None => viewpair.legacy_address(network),
+      };
+      Some(InspectedChange {
+        address: address.to_string(),
+        subaddress_index: index.map(|index| [index.account(), index.address()]),
+        external: false,
+        amount: change_amount,
+      })
+    }
+  };
+
+  Ok(Inspection {
+    recipients: payments
+      .into_iter()
+      .map(|(address, amount)| InspectedPayment { address, amount })
+      .collect(),
+    change,
+    fee,
+    inputs: inspected_inputs,
+    total_input,
+    data: data.iter().map(hex::encode).collect(),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{input, keys};
+  use monero_wallet::{ringct::RctType, rpc::FeeRate, send::Change};
+  use serde_json::{Value, json};
+
+  fn signable_transaction(change: Change, data: Vec<Vec<u8>>) -> SignableTransaction {
+    let wallet = keys(7);
+    let recipient = keys(9).viewpair().legacy_address(Network::Mainnet);
+    SignableTransaction::new(
+      RctType::ClsagBulletproofPlus,
+      Zeroizing::new([3; 32]),
+      vec![input(&wallet, 2_000_000_000_000, 10), input(&wallet, 500_000_000_000, 20)],
+      vec![(recipient, 1_000_000_000_000)],
+      change,
+      data,
+      FeeRate::new(20_000, 10_000).unwrap(),
+    )
+    .unwrap()
+  }
+  fn inspect(tx: &SignableTransaction, spend_key: Option<String>) -> Result<Inspection, AbiError> {
+    inspect_params(
+      json!({ "signable_transaction": hex::encode(tx.serialize()), "spend_key": spend_key }),
+    )
+  }
+  fn inspect_params(params: Value) -> Result<Inspection, AbiError> {
+    let wallet = keys(7);
+    inspect_signable_transaction(
+      &params.to_string(),
+      &wallet.viewpair(),
+      &wallet.view_key,
+      Network::Mainnet,
+    )
+  }
+
+  #[test]
+  fn decodes_what_signable_transaction_new_was_given() {
+    let wallet = keys(7);
+    let index = SubaddressIndex::new(0, 4);
+    let tx = signable_transaction(Change::new(wallet.viewpair(), index), vec![b"memo".to_vec()]);
+    let (inputs, payments, change, data) = decode(&tx.serialize()).unwrap();
+
+    let amounts: Vec<u64> = inputs.iter().map(|input| input.commitment().amount).collect();
+    assert_eq!(amounts, vec![2_000_000_000_000, 500_000_000_000]);
+    let recipient = keys(9).viewpair().legacy_address(Network::Mainnet).to_string();
+    assert_eq!(payments, vec![(recipient, 1_000_000_000_000)]);
+    assert_eq!(data, vec![b"memo".to_vec()]);
+    let Some(SerializedChange::ViewPair(spend, view, 0, 4)) = change else {
+      panic!("change with the view pair of subaddress 0/4 expected");
+    };
+    assert_eq!((spend, *view), (wallet.spend, wallet.view_key));
+  }
+
+  #[test]
+  fn inspects_change_to_a_subaddress() {
+    let wallet = keys(7);
+    let tx =
+      signable_transaction(Change::new(wallet.viewpair(), SubaddressIndex::new(1, 2)), vec![]);
+    let inspection = inspect(&tx, Some(wallet.spend_key_hex())).unwrap();
+    let change = inspection.change.unwrap();
+    assert_eq!(change.subaddress_index, Some([1, 2]));
+    assert!(!change.external);
+    assert_eq!(change.amount, inspection.total_input - 1_000_000_000_000 - inspection.fee);
+    assert!(inspection.inputs.iter().all(|input| input.key_image.is_some()));
+    assert!(inspect(&tx, Some(keys(8).spend_key_hex())).is_err());
+  }
+
+  #[test]
+  fn change_to_an_external_address_needs_allow_external_change() {
+    let external = keys(11).viewpair().legacy_address(Network::Mainnet);
+    let tx = signable_transaction(Change::fingerprintable(Some(external)), vec![]);
+    let error = inspect(&tx, None).unwrap_err();
+    assert_eq!(error.code, crate::error::INVALID_INPUT);
+    assert_eq!(error.field.as_deref(), Some("signable_transaction"));
+    let params = json!({
+      "signable_transaction": hex::encode(tx.serialize()),
+      "allow_external_change": true,
+    });
+    let change = inspect_params(params).unwrap().change.unwrap();
+    assert_eq!(change.address, external.to_string());
+    assert!(change.external);
+
+    let primary = keys(7).viewpair().legacy_address(Network::Mainnet);
+    let tx = signable_transaction(Change::fingerprintable(Some(primary)), vec![]);
+    assert!(!inspect(&tx, None).unwrap().change.unwrap().external);
+  }
+
+  #[test]
+  fn without_change_the_rest_is_fee() {
+    // without change SignableTransaction::new needs two payments
+    let wallet = keys(7);
+    let recipient = keys(9).viewpair().legacy_address(Network::Mainnet);
+    let tx = SignableTransaction::new(
+      RctType::ClsagBulletproofPlus,
+      Zeroizing::new([3; 32]),
+      vec![input(&wallet, 2_000_000_000_000, 10), input(&wallet, 500_000_000_000, 20)],
+      vec![(recipient, 1_000_000_000_000), (recipient, 1_000_000_000_000)],
+      Change::fingerprintable(None),
+      vec![],
+      FeeRate::new(20_000, 10_000).unwrap(),
+    )
+    .unwrap();
+    let inspection = inspect(&tx, None).unwrap();
+    assert!(inspection.change.is_none());
+    assert_eq!(inspection.fee, 500_000_000_000);
+    assert!(inspection.fee > tx.necessary_fee());
+  }
+
+  #[test]
+  fn change_to_another_view_pair_is_rejected() {
+    let tx = signable_transaction(Change::new(keys(11).viewpair(), None), vec![]);
+    assert!(inspect(&tx, None).is_err());
+  }
+}
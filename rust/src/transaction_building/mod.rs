@@ -1,2 +1,8 @@
+pub mod batch;
+pub mod coin_selection;
+pub mod decode;
+pub mod fee_estimation;
 pub mod inputs;
+pub mod inspect;
+pub mod sweep;
 pub mod transaction;
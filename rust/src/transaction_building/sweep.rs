@@ -0,0 +1,213 @@
+use monero_wallet::{OutputWithDecoys, ViewPair, address::Network};
+use serde::{Deserialize, Serialize};
+
+use super::fee_estimation::{RING_LEN, TxShape};
+use super::transaction::{
+  ChangeJson, FeeResponse, amount_after_fee, input_total, parse_address_for_network,
+  parse_fee_rate, random_outgoing_view_key, read_inputs, sweep_transaction,
+};
+use crate::block_parsing::amount_to_string;
+use crate::error::AbiError;
+
+/// get_transaction_weight_limit for hard fork 16:
+/// half the full reward zone minus the space reserved for the coinbase blob
+pub const MAX_TX_WEIGHT: usize = 300_000 / 2 - 600;
+
+#[derive(Debug, Deserialize)]
+struct SweepAllParams {
+  /// every spendable output, prepared with make_input
+  inputs: Vec<String>,
+  address: String,
+  fee_response: FeeResponse,
+  fee_priority: String,
+  /// only sweep inputs with an amount below this threshold
+  below_threshold: Option<String>,
+  /// defaults to MAX_TX_WEIGHT
+  max_weight: Option<usize>,
+  #[serde(flatten)]
+  change: ChangeJson,
+}
+#[derive(Debug, Serialize)]
+pub struct SweepTransaction {
+  signable_transaction: String,
+  inputs: usize,
+  #[serde(serialize_with = "amount_to_string")]
+  amount: u64,
+  #[serde(serialize_with = "amount_to_string")]
+  fee: u64,
+}
+#[derive(Debug, Serialize)]
+pub struct SweepPlan {
+  transactions: Vec<SweepTransaction>,
+  #[serde(serialize_with = "amount_to_string")]
+  total_amount: u64,
+  #[serde(serialize_with = "amount_to_string")]
+  total_fee: u64,
+  /// inputs that cost more to spend than they are worth, left out of the sweep
+  skipped_inputs: usize,
+}
+
+/// the most inputs a 2 output transaction can have without going over max_weight
+fn max_inputs_per_tx(
+  max_weight: usize,
+  fee_rate: &monero_wallet::rpc::FeeRate,
+) -> Result<usize, AbiError> {
+  let shape = |inputs| TxShape {
+    // the real key offsets are only known after decoy selection, assume the worst case
+    key_offsets_size: Some(RING_LEN * 4),
+    ..TxShape::new(inputs, 2)
+  };
+  let single_input_weight = shape(1).estimate(fee_rate).weight;
+  if single_input_weight > max_weight {
+    return Err(AbiError::invalid_input(
+      "max_weight",
+      format!(
+        "max_weight {} is below the weight of a transaction with one input ({})",
+        max_weight, single_input_weight
+      ),
+    ));
+  }
+  let mut inputs = 1;
+  while shape(inputs + 1).estimate(fee_rate).weight <= max_weight {
+    inputs += 1;
+  }
+  Ok(inputs)
+}
+
+/// deals the inputs round robin to as few transactions as max_inputs allows
+fn partition<T>(inputs: Vec<T>, max_inputs: usize) -> Vec<Vec<T>> {
+  let tx_count = inputs.len().div_ceil(max_inputs);
+  let mut partitions: Vec<Vec<T>> = (0..tx_count).map(|_| vec![]).collect();
+  for (i, input) in inputs.into_iter().enumerate() {
+    partitions[i % tx_count].push(input);
+  }
+  partitions
+}
+
+pub fn make_sweep_all_transactions(
+  json_params: &str,
+  viewpair: ViewPair,
+  network: Network,
+) -> Result<SweepPlan, AbiError> {
+  let params: SweepAllParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input(
+      "json_params",
+      format!("failed to parse sweep all params json: {:?}", e),
+    )
+  })?;
+  let fee_rate = parse_fee_rate(&params.fee_priority, params.fee_response)?;
+  let address = parse_address_for_network(&params.address, network)?;
+  let change = params.change.to_change(&viewpair, network)?;
+  let below_threshold = match &params.below_threshold {
+    Some(threshold) => Some(threshold.parse::<u64>().map_err(|e| {
+      AbiError::invalid_input(
+        "below_threshold",
+        format!("failed to parse below_threshold '{}' : {:?}", threshold, e),
+      )
+    })?),
+    None => None,
+  };
+
+  let shape = TxShape::new(1, 2);
+  let input_fee = fee_rate.calculate_fee_from_weight(shape.input_size() + shape.input_proof_size());
+  let mut inputs: Vec<OutputWithDecoys> = vec![];
+  let mut skipped_inputs = 0;
+  for input in read_inputs(params.inputs)? {
+    let amount = input.commitment().amount;
+    if below_threshold.is_some_and(|threshold| amount >= threshold) {
+      continue;
+    }
+    if amount <= input_fee {
+      skipped_inputs += 1;
+      continue;
+    }
+    inputs.push(input);
+  }
+  if inputs.is_empty() {
+    return Err(AbiError::invalid_input("inputs", "no inputs to sweep"));
+  }
+  // neither the partitions nor the totals of the plan overflow if the sum of every input doesn't
+  input_total(&inputs)?;
+  // largest first, so every transaction gets a share of the big inputs
+  inputs.sort_by(|a, b| b.commitment().amount.cmp(&a.commitment().amount));
+
+  let max_inputs = max_inputs_per_tx(params.max_weight.unwrap_or(MAX_TX_WEIGHT), &fee_rate)?;
+  let partitions = partition(inputs, max_inputs);
+
+  let mut transactions = Vec::with_capacity(partitions.len());
+  for partition in partitions {
+    let total_input = input_total(&partition)?;
+    let input_count = partition.len();
+    let signable_tx = sweep_transaction(
+      random_outgoing_view_key(),
+      partition,
+      address,
+      change.clone(),
+      vec![],
+      fee_rate,
+    )?;
+    let fee = signable_tx.necessary_fee();
+    transactions.push(SweepTransaction {
+      signable_transaction: hex::encode(signable_tx.serialize()),
+      inputs: input_count,
+      amount: amount_after_fee(total_input, fee)?,
+      fee,
+    });
+  }
+  Ok(SweepPlan {
+    total_amount: transactions.iter().map(|tx| tx.amount).sum(),
+    total_fee: transactions.iter().map(|tx| tx.fee).sum(),
+    transactions,
+    skipped_inputs,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::{input, keys};
+  use monero_wallet::rpc::FeeRate;
+
+  fn fee_rate() -> FeeRate {
+    FeeRate::new(20_000, 10_000).unwrap()
+  }
+
+  #[test]
+  fn max_inputs_fill_max_weight() {
+    let max_inputs = max_inputs_per_tx(MAX_TX_WEIGHT, &fee_rate()).unwrap();
+    let shape =
+      |inputs| TxShape { key_offsets_size: Some(RING_LEN * 4), ..TxShape::new(inputs, 2) };
+    assert!(shape(max_inputs).estimate(&fee_rate()).weight <= MAX_TX_WEIGHT);
+    assert!(shape(max_inputs + 1).estimate(&fee_rate()).weight > MAX_TX_WEIGHT);
+  }
+
+  #[test]
+  fn max_weight_below_one_input_is_an_error() {
+    assert!(max_inputs_per_tx(1_000, &fee_rate()).is_err());
+  }
+
+  #[test]
+  fn inputs_are_dealt_round_robin() {
+    let partitions = partition((0..7).collect(), 3);
+    assert_eq!(partitions, vec![vec![0, 3, 6], vec![1, 4], vec![2, 5]]);
+    assert_eq!(partition((0..6).collect(), 3), vec![vec![0, 2, 4], vec![1, 3, 5]]);
+    assert_eq!(partition(vec![0], 3), vec![vec![0]]);
+  }
+
+  #[test]
+  fn inputs_that_do_not_cover_the_fee_are_an_error() {
+    assert_eq!(amount_after_fee(1_000, 400), Ok(600));
+    assert!(amount_after_fee(1_000, 1_000).is_err());
+    assert!(amount_after_fee(999, 1_000).is_err());
+  }
+
+  #[test]
+  fn overflowing_inputs_are_an_error() {
+    let wallet = keys(7);
+    let inputs = [input(&wallet, u64::MAX, 10), input(&wallet, 1, 20)];
+    assert_eq!(input_total(&inputs[..1]), Ok(u64::MAX));
+    let error = input_total(&inputs).unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("inputs"));
+    assert_eq!(error.message, "input total overflows u64");
+  }
+}
@@ -1,10 +1,9 @@
-use curve25519_dalek::Scalar;
 use monero_wallet::{
   OutputWithDecoys, ViewPair,
-  address::{AddressType, MoneroAddress, Network},
+  address::{AddressType, MoneroAddress, Network, SubaddressIndex},
   ringct::RctType,
   rpc::{FeePriority, FeeRate, RpcError},
-  send::{Change, SignableTransaction},
+  send::{Change, SendError, SignableTransaction},
 };
 use serde_json::json;
 use std::{io::Cursor};
@@ -13,32 +12,43 @@ use rand_core::{OsRng, RngCore};
 use serde::Deserialize;
 use zeroize::Zeroizing;
 
-pub fn sign_transaction(tx: String, sender_spend_key: String) -> Result<String, String> {
-  let spend_bytes = <[u8; 32]>::from_hex(sender_spend_key).unwrap();
-  let spend_scalar = Zeroizing::new(Scalar::from_canonical_bytes(spend_bytes).unwrap()); // okay to panic if spend_key is invalid
+use crate::amount::{Unit, parse_amount};
+use crate::error::{AbiError, FAILED, NETWORK_MISMATCH, NOT_ENOUGH_FUNDS};
+use crate::keypairs::parse_secret_key;
 
-  let mut reader = Cursor::new(hex::decode(tx).unwrap());
+pub fn sign_transaction(tx: String, sender_spend_key: String) -> Result<String, AbiError> {
+  let spend_scalar = parse_secret_key("secret_spend_key", &sender_spend_key)?;
+
+  let invalid = |message: String| AbiError::invalid_input("signable_transaction", message);
+  let mut reader = Cursor::new(
+    hex::decode(tx)
+      .map_err(|e| invalid(format!("failed to parse signable transaction hex: {:?}", e)))?,
+  );
   let transaction = SignableTransaction::read(&mut reader)
-    .map_err(|e| format!("failed to read SignableTransaction: {:?}", e))?;
+    .map_err(|e| invalid(format!("failed to read SignableTransaction: {:?}", e)))?;
   Ok(hex::encode(
     transaction
       .sign(&mut OsRng, &spend_scalar)
-      .map_err(|e| format!("failed to sign transaction: {:?}", e))?
+      .map_err(|e| AbiError::new(FAILED, format!("failed to sign transaction: {:?}", e)))?
       .serialize(),
   ))
 }
 
-pub fn read_one_input(input_hex_string: String) -> Result<OutputWithDecoys, String> {
-  let input_bytes: Vec<u8> = hex::decode(input_hex_string).unwrap();
+/// field is the json key of the input, e.g. inputs[3]
+pub fn read_one_input(input_hex_string: String, field: &str) -> Result<OutputWithDecoys, AbiError> {
+  let input_bytes: Vec<u8> = hex::decode(input_hex_string).map_err(|e| {
+    AbiError::invalid_input(field, format!("failed to parse serialized input hex: {:?}", e))
+  })?;
   let mut reader = Cursor::new(input_bytes);
-  OutputWithDecoys::read(&mut reader)
-    .map_err(|e| format!("failed to read  serialized input: {:?}", e))
+  OutputWithDecoys::read(&mut reader).map_err(|e| {
+    AbiError::invalid_input(field, format!("failed to read  serialized input: {:?}", e))
+  })
 }
 
-pub fn read_inputs(vec_inputs: Vec<String>) -> Result<Vec<OutputWithDecoys>, String> {
+pub fn read_inputs(vec_inputs: Vec<String>) -> Result<Vec<OutputWithDecoys>, AbiError> {
   let mut inputs = Vec::with_capacity(vec_inputs.len());
-  for input_bytes in vec_inputs {
-    let input = read_one_input(input_bytes)?;
+  for (i, input_bytes) in vec_inputs.into_iter().enumerate() {
+    let input = read_one_input(input_bytes, &format!("inputs[{}]", i))?;
     inputs.push(input);
   }
   Ok(inputs)
@@ -46,33 +56,49 @@ pub fn read_inputs(vec_inputs: Vec<String>) -> Result<Vec<OutputWithDecoys>, Str
 pub fn make_transaction(
   json_params: &str,
   viewpair: ViewPair,
+  network: Network,
   // inputs: Vec<OutputWithDecoys>,
   // payments: Vec<(MoneroAddress, u64)>,
   // OPTIONAL outgoing_view_key: Zeroizing<[u8; 32]>, default random if None
   // OPTIONAL: data: Vec<Vec<u8>>,
-) -> Result<SignableTransaction, String> {
+) -> Result<SignableTransaction, AbiError> {
   let params = parse_make_transaction_params(json_params)?;
-  let priority = parse_fee_priority(&params.fee_priority)?;
-  let fee_rate = get_fee_rate(priority, params.fee_response).map_err(|e| {
-    format!("failed to get fee rate (from fee priority + rpc get_fee_estimate response: {:?}", e)
-  })?;
+  let fee_rate = parse_fee_rate(&params.fee_priority, params.fee_response)?;
   let inputs = read_inputs(params.inputs)?;
-  let payments = parse_payments(&params.payments)?;
-  let change = Change::new(viewpair.clone(), None);
-  let outgoing_view_key = match &params.outgoing_view_key {
-    Some(s) => {
-      let bytes: [u8; 32] = <[u8; 32]>::from_hex(s)
-        .map_err(|e| format!("failed to parse outgoing_view_key hex: {:?}", e))?;
-      Zeroizing::new(bytes)
-    }
-    None => {
-      let mut outgoing_view = Zeroizing::new([0; 32]);
-      OsRng.fill_bytes(&mut outgoing_view.as_mut()[..]);
-      outgoing_view
-    }
-  };
+  let payments = parse_payments(&params.payments, network)?;
+  let change = params.change.to_change(&viewpair, network)?;
+  let outgoing_view_key = parse_outgoing_view_key(params.outgoing_view_key.as_deref())?;
   let data = params.data.unwrap_or(vec![]);
-  SignableTransaction::new(
+  let subtract_fee_from: Vec<bool> =
+    params.payments.iter().map(|payment| payment.subtract_fee_from_amount).collect();
+  let payments = if subtract_fee_from.contains(&true) {
+    // same trick as the sweep: the fee does not depend on the amounts,
+    // so build once without the flagged amounts to learn the fee
+    let probe_payments = payments
+      .iter()
+      .zip(&subtract_fee_from)
+      .map(|((address, amount), subtract)| (*address, if *subtract { 0 } else { *amount }))
+      .collect();
+    let probe = SignableTransaction::new(
+      RctType::ClsagBulletproofPlus,
+      outgoing_view_key.clone(),
+      inputs.clone(),
+      probe_payments,
+      change.clone(),
+      data.clone(),
+      fee_rate,
+    )
+    .map_err(send_error)?;
+    subtract_fee_from_payments(
+      payments,
+      &subtract_fee_from,
+      probe.necessary_fee(),
+      params.fee_split.as_deref().unwrap_or("even"),
+    )?
+  } else {
+    payments
+  };
+  let tx = SignableTransaction::new(
     RctType::ClsagBulletproofPlus,
     outgoing_view_key,
     inputs,
@@ -81,55 +107,103 @@ pub fn make_transaction(
     data,
     fee_rate,
   )
-  .map_err(|e| json!({"message":"failed to create SignableTransaction","error":e}).to_string())
+  .map_err(send_error)?;
+  Ok(tx)
+}
+/// splits the fee across the flagged payments, evenly or proportional to their amounts.
+/// the remainder of the division goes to the first flagged payments (like wallet2's subtractfeefrom)
+pub(crate) fn subtract_fee_from_payments(
+  mut payments: Vec<(MoneroAddress, u64)>,
+  subtract_fee_from: &[bool],
+  fee: u64,
+  fee_split: &str,
+) -> Result<Vec<(MoneroAddress, u64)>, AbiError> {
+  let flagged: Vec<usize> = (0..payments.len()).filter(|i| subtract_fee_from[*i]).collect();
+  let flagged_total: u128 = flagged.iter().map(|i| u128::from(payments[*i].1)).sum();
+  let mut shares: Vec<u64> = match fee_split {
+    "even" => vec![fee / flagged.len() as u64; flagged.len()],
+    "proportional" => {
+      if flagged_total == 0 {
+        return Err(AbiError::invalid_input(
+          "payments",
+          "payments to subtract the fee from have no amount",
+        ));
+      }
+      flagged
+        .iter()
+        .map(|i| (u128::from(fee) * u128::from(payments[*i].1) / flagged_total) as u64)
+        .collect()
+    }
+    _ => {
+      let message = format!("Invalid fee_split: '{}'. Must be: even, proportional", fee_split);
+      return Err(AbiError::invalid_input("fee_split", message));
+    }
+  };
+  let mut remainder = fee - shares.iter().sum::<u64>();
+  for share in shares.iter_mut() {
+    if remainder == 0 {
+      break;
+    }
+    *share += 1;
+    remainder -= 1;
+  }
+  for (i, share) in flagged.iter().zip(shares) {
+    let (_, amount) = &mut payments[*i];
+    if *amount <= share {
+      let message =
+        format!("payment amount {} does not cover its share of the fee {}", amount, share);
+      return Err(AbiError::invalid_input(&format!("payments[{}].amount", i), message));
+    }
+    *amount -= share;
+  }
+  Ok(payments)
 }
 
 pub fn make_external_sweep_transaction(
   json_params: &str,
   viewpair: ViewPair,
+  network: Network,
   // inputs: Vec<OutputWithDecoys>,
   // payments: Vec<(MoneroAddress, u64)>,
   // OPTIONAL outgoing_view_key: Zeroizing<[u8; 32]>, default random if None
   // OPTIONAL: data: Vec<Vec<u8>>,
-) -> Result<SignableTransaction, String> {
+) -> Result<SignableTransaction, AbiError> {
   let params = parse_make_transaction_params(json_params)?;
-  let priority = parse_fee_priority(&params.fee_priority)?;
-  let fee_rate = get_fee_rate(priority, params.fee_response).map_err(|e| {
-    format!("failed to get fee rate (from fee priority + rpc get_fee_estimate response: {:?}", e)
-  })?;
+  let fee_rate = parse_fee_rate(&params.fee_priority, params.fee_response)?;
   let inputs = read_inputs(params.inputs)?;
-  let payments = parse_payments(&params.payments)?;
+  let payments = parse_payments(&params.payments, network)?;
   if payments.len() != 1 {
-    return Err("external sweep must have exactly one payment".to_string());
+    return Err(AbiError::invalid_input("payments", "external sweep must have exactly one payment"));
   }
 
-  let change = Change::new(viewpair.clone(), None);
-  let outgoing_view_key = match &params.outgoing_view_key {
-    Some(s) => {
-      let bytes: [u8; 32] = <[u8; 32]>::from_hex(s)
-        .map_err(|e| format!("failed to parse outgoing_view_key hex: {:?}", e))?;
-      Zeroizing::new(bytes)
-    }
-    None => {
-      let mut outgoing_view = Zeroizing::new([0; 32]);
-      OsRng.fill_bytes(&mut outgoing_view.as_mut()[..]);
-      outgoing_view
-    }
-  };
+  let change = params.change.to_change(&viewpair, network)?;
+  let outgoing_view_key = parse_outgoing_view_key(params.outgoing_view_key.as_deref())?;
   let data = params.data.unwrap_or(vec![]);
+  sweep_transaction(outgoing_view_key, inputs, payments[0].0, change, data, fee_rate)
+}
+/// sends everything but the fee to the address: the first transaction is only built
+/// to learn the necessary fee, which is then subtracted from the payment
+pub(crate) fn sweep_transaction(
+  outgoing_view_key: Zeroizing<[u8; 32]>,
+  inputs: Vec<OutputWithDecoys>,
+  address: MoneroAddress,
+  change: Change,
+  data: Vec<Vec<u8>>,
+  fee_rate: FeeRate,
+) -> Result<SignableTransaction, AbiError> {
   let tx = SignableTransaction::new(
     RctType::ClsagBulletproofPlus,
     outgoing_view_key.clone(),
     inputs.clone(),
-    payments.clone(),
+    vec![(address, 0)],
     change.clone(),
     data.clone(),
     fee_rate,
   )
-  .map_err(|e| json!({"message":"failed to create SignableTransaction","error":e}).to_string())?;
+  .map_err(send_error)?;
   let change_amount = tx.necessary_fee();
-  let total_input: u64 = inputs.iter().map(|input| input.commitment().amount).sum();
-  let real_payments = vec![(payments[0].0, total_input - change_amount)];
+  let total_input = input_total(&inputs)?;
+  let real_payments = vec![(address, amount_after_fee(total_input, change_amount)?)];
   SignableTransaction::new(
     RctType::ClsagBulletproofPlus,
     outgoing_view_key,
@@ -139,10 +213,50 @@ pub fn make_external_sweep_transaction(
     data,
     fee_rate,
   )
-  .map_err(|e| json!({"message":"failed to create SignableTransaction","error":e}).to_string())
+  .map_err(send_error)
+}
+/// the sum of the input amounts, an error if it overflows
+pub(crate) fn input_total(inputs: &[OutputWithDecoys]) -> Result<u64, AbiError> {
+  inputs
+    .iter()
+    .try_fold(0u64, |total, input| total.checked_add(input.commitment().amount))
+    .ok_or_else(|| AbiError::invalid_input("inputs", "input total overflows u64"))
+}
+/// what a sweep sends after paying the fee, an error if nothing is left
+pub(crate) fn amount_after_fee(total_input: u64, fee: u64) -> Result<u64, AbiError> {
+  if total_input <= fee {
+    let message = format!("inputs of {} do not cover the fee of {}", total_input, fee);
+    return Err(AbiError::new(NOT_ENOUGH_FUNDS, message));
+  }
+  Ok(total_input - fee)
+}
+/// SignableTransaction::new's error, NotEnoughFunds (inputs, outputs and necessary fee in the
+/// message) gets its own code
+pub(crate) fn send_error(error: SendError) -> AbiError {
+  let code = match error {
+    SendError::NotEnoughFunds { .. } => NOT_ENOUGH_FUNDS,
+    _ => FAILED,
+  };
+  AbiError::new(code, format!("failed to create SignableTransaction: {:?}", error))
+}
+pub(crate) fn random_outgoing_view_key() -> Zeroizing<[u8; 32]> {
+  let mut outgoing_view = Zeroizing::new([0; 32]);
+  OsRng.fill_bytes(&mut outgoing_view.as_mut()[..]);
+  outgoing_view
+}
+/// the outgoing_view_key of the params, a random one if there is none
+fn parse_outgoing_view_key(hex: Option<&str>) -> Result<Zeroizing<[u8; 32]>, AbiError> {
+  let Some(hex) = hex else {
+    return Ok(random_outgoing_view_key());
+  };
+  let bytes = <[u8; 32]>::from_hex(hex).map_err(|e| {
+    let message = format!("failed to parse outgoing_view_key hex: {:?}", e);
+    AbiError::invalid_input("outgoing_view_key", message)
+  })?;
+  Ok(Zeroizing::new(bytes))
 }
 #[derive(Debug, Deserialize)]
-struct FeeResponse {
+pub(crate) struct FeeResponse {
   // status: String,
   fees: Option<Vec<u64>>,
   fee: u64,
@@ -150,21 +264,63 @@ struct FeeResponse {
 }
 
 #[derive(Debug, Deserialize)]
-struct PaymentJson {
-  address: String,
-  amount: String,
+pub(crate) struct PaymentJson {
+  pub(crate) address: String,
+  pub(crate) amount: String,
+  /// amount is a decimal in this unit instead of atomic units (piconero)
+  pub(crate) amount_unit: Option<Unit>,
+  /// the recipient pays (a share of) the fee
+  #[serde(default)]
+  pub(crate) subtract_fee_from_amount: bool,
 }
-pub fn parse_address(address: &str) -> Result<MoneroAddress, String> {
-  let address = MoneroAddress::from_str_with_unchecked_network(address)
-    .map_err(|e| format!("failed to parse payment address '{}' : {:?}", address, e))?;
+pub fn parse_address(address: &str) -> Result<MoneroAddress, AbiError> {
+  let address = MoneroAddress::from_str_with_unchecked_network(address).map_err(|e| {
+    let message = format!("failed to parse payment address '{}' : {:?}", address, e);
+    AbiError::invalid_input("address", message)
+  })?;
   Ok(address)
 }
-pub fn address_to_json(address: &MoneroAddress) -> String {
-  let network_string = match address.network() {
+/// rejects an address of another network than the wallet's with the network_mismatch code
+pub fn check_network(address: &MoneroAddress, network: Network) -> Result<(), AbiError> {
+  if address.network() == network {
+    return Ok(());
+  }
+  let message = format!(
+    "{} is a {} address, the wallet is on {}",
+    address,
+    network_name(address.network()),
+    network_name(network)
+  );
+  Err(AbiError::new(NETWORK_MISMATCH, message).with_field("address"))
+}
+/// every address a user passes in goes through here (or parse_address_for_any_network)
+pub fn parse_address_for_network(
+  address: &str,
+  network: Network,
+) -> Result<MoneroAddress, AbiError> {
+  let address = parse_address(address)?;
+  check_network(&address, network)?;
+  Ok(address)
+}
+/// for the exports that work without a wallet: before init_viewpair any network is fine
+pub fn parse_address_for_any_network(
+  address: &str,
+  network: Option<Network>,
+) -> Result<MoneroAddress, AbiError> {
+  match network {
+    Some(network) => parse_address_for_network(address, network),
+    None => parse_address(address),
+  }
+}
+pub(crate) fn network_name(network: Network) -> &'static str {
+  match network {
     Network::Mainnet => "mainnet",
     Network::Stagenet => "stagenet",
     Network::Testnet => "testnet",
-  };
+  }
+}
+pub fn address_to_json(address: &MoneroAddress) -> String {
+  let network_string = network_name(address.network());
   let kind_string = match address.kind() {
     AddressType::LegacyIntegrated(_) => "integrated".to_string(),
     AddressType::Legacy => "primary".to_string(),
@@ -191,25 +347,99 @@ struct MakeTransactionParams {
   fee_priority: String,
   outgoing_view_key: Option<String>,
   data: Option<Vec<Vec<u8>>>,
+  /// how the fee is split across payments with subtract_fee_from_amount: even (default) | proportional
+  fee_split: Option<String>,
+  #[serde(flatten)]
+  change: ChangeJson,
+}
+#[derive(Debug, Deserialize, Default)]
+pub(crate) struct ChangeJson {
+  /// change goes to this subaddress of our wallet instead of the primary address
+  change_subaddress_index: Option<SubaddressIndexJson>,
+  /// change goes to an external address, this is fingerprintable and the change will not be scanned
+  change_address: Option<String>,
+}
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub(crate) struct SubaddressIndexJson {
+  major: u32,
+  minor: u32,
+}
+impl ChangeJson {
+  /// the subaddress the change goes to, None for the primary address or an external address
+  pub(crate) fn subaddress_index(&self) -> Option<SubaddressIndex> {
+    self.change_subaddress_index.and_then(|index| SubaddressIndex::new(index.major, index.minor))
+  }
+  pub(crate) fn to_change(
+    &self,
+    viewpair: &ViewPair,
+    network: Network,
+  ) -> Result<Change, AbiError> {
+    match (&self.change_subaddress_index, &self.change_address) {
+      (Some(_), Some(_)) => Err(AbiError::invalid_input(
+        "change_address",
+        "change_subaddress_index and change_address are mutually exclusive",
+      )),
+      (_, Some(address)) => {
+        let address =
+          parse_address_for_network(address, network).map_err(|e| e.with_field("change_address"))?;
+        Ok(Change::fingerprintable(Some(address)))
+      }
+      (Some(index), None) => match SubaddressIndex::new(index.major, index.minor) {
+        Some(index) => Ok(Change::new(viewpair.clone(), Some(index))),
+        None => Err(AbiError::invalid_input(
+          "change_subaddress_index",
+          "change_subaddress_index 0/0 is the primary address, leave it out",
+        )),
+      },
+      (None, None) => Ok(Change::new(viewpair.clone(), None)),
+    }
+  }
 }
-fn parse_payments(payments_json: &[PaymentJson]) -> Result<Vec<(MoneroAddress, u64)>, String> {
+/// the subaddress make_transaction sends change to, so it can be registered with the scanner
+pub fn change_subaddress_index(json_params: &str) -> Option<SubaddressIndex> {
+  let params: ChangeJson = serde_json::from_str(json_params).ok()?;
+  params.subaddress_index()
+}
+pub(crate) fn parse_payments(
+  payments_json: &[PaymentJson],
+  network: Network,
+) -> Result<Vec<(MoneroAddress, u64)>, AbiError> {
   let mut payments = Vec::with_capacity(payments_json.len());
-  for payment in payments_json {
-    let address = MoneroAddress::from_str_with_unchecked_network(&payment.address)
-      .map_err(|e| format!("failed to parse payment address '{}' : {:?}", payment.address, e))?;
-    let amount = payment
-      .amount
-      .parse::<u64>()
-      .map_err(|e| format!("failed to parse payment amount '{}' : {:?}", payment.amount, e))?;
+  for (i, payment) in payments_json.iter().enumerate() {
+    let address = parse_address_for_network(&payment.address, network)
+      .map_err(|e| e.with_field(&format!("payments[{}].address", i)))?;
+    let amount = match payment.amount_unit {
+      Some(unit) => parse_amount(&payment.amount, unit, None),
+      None => payment
+        .amount
+        .parse::<u64>()
+        .map_err(|e| format!("failed to parse payment amount '{}' : {:?}", payment.amount, e)),
+    }
+    .map_err(|e| AbiError::invalid_input(&format!("payments[{}].amount", i), e))?;
     payments.push((address, amount));
   }
   Ok(payments)
 }
-fn parse_make_transaction_params(json_str: &str) -> Result<MakeTransactionParams, String> {
-  serde_json::from_str(json_str)
-    .map_err(|e| format!("failed to parse make_transaction params json: {:?}", e))
+fn parse_make_transaction_params(json_str: &str) -> Result<MakeTransactionParams, AbiError> {
+  serde_json::from_str(json_str).map_err(|e| {
+    let message = format!("failed to parse make_transaction params json: {:?}", e);
+    AbiError::invalid_input("json_params", message)
+  })
 }
-fn parse_fee_priority(s: &str) -> Result<FeePriority, String> {
+/// the fee rate of the priority, from the rpc get_fee_estimate response
+pub(crate) fn parse_fee_rate(
+  fee_priority: &str,
+  fee_response: FeeResponse,
+) -> Result<FeeRate, AbiError> {
+  let priority =
+    parse_fee_priority(fee_priority).map_err(|e| AbiError::invalid_input("fee_priority", e))?;
+  get_fee_rate(priority, fee_response).map_err(|e| {
+    let message =
+      format!("failed to get fee rate (from fee priority + rpc get_fee_estimate response: {:?}", e);
+    AbiError::invalid_input("fee_response", message)
+  })
+}
+pub(crate) fn parse_fee_priority(s: &str) -> Result<FeePriority, String> {
   match s.to_lowercase().as_str() {
     "unimportant" => Ok(FeePriority::Unimportant),
     "normal" => Ok(FeePriority::Normal),
@@ -221,7 +451,7 @@ fn parse_fee_priority(s: &str) -> Result<FeePriority, String> {
   }
 }
 // adapted from monero_wallet::rpc::get_fee_rate
-fn get_fee_rate(priority: FeePriority, res: FeeResponse) -> Result<FeeRate, RpcError> {
+pub(crate) fn get_fee_rate(priority: FeePriority, res: FeeResponse) -> Result<FeeRate, RpcError> {
   if let Some(fees) = res.fees {
     // https://github.com/monero-project/monero/blob/94e67bf96bbc010241f29ada6abc89f49a81759c/
     // src/wallet/wallet2.cpp#L7615-L7620
@@ -255,3 +485,113 @@ fn get_fee_rate(priority: FeePriority, res: FeeResponse) -> Result<FeeRate, RpcE
     FeeRate::new(res.fee * fee_multiplier, res.quantization_mask)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::error::INVALID_INPUT;
+  use crate::test_util::{XMR, keys, payment};
+
+  fn change(json: serde_json::Value) -> Result<Change, AbiError> {
+    let change: ChangeJson = serde_json::from_value(json).unwrap();
+    change.to_change(&keys(7).viewpair(), Network::Mainnet)
+  }
+
+  #[test]
+  fn change_to_the_primary_address_as_subaddress_is_rejected() {
+    assert!(change(json!({})).is_ok());
+    assert!(change(json!({ "change_subaddress_index": { "major": 0, "minor": 1 } })).is_ok());
+    assert!(change(json!({ "change_subaddress_index": { "major": 0, "minor": 0 } })).is_err());
+    let params = json!({ "change_subaddress_index": { "major": 1, "minor": 0 } }).to_string();
+    assert_eq!(change_subaddress_index(&params), SubaddressIndex::new(1, 0));
+  }
+
+  fn payments(amounts: &[u64]) -> Vec<(MoneroAddress, u64)> {
+    let address = parse_address(&keys(9).primary_address()).unwrap();
+    amounts.iter().map(|amount| (address, *amount)).collect()
+  }
+  fn amounts(payments: &[(MoneroAddress, u64)]) -> Vec<u64> {
+    payments.iter().map(|(_, amount)| *amount).collect()
+  }
+
+  fn payment_json(amount: &str, amount_unit: Option<&str>) -> Vec<PaymentJson> {
+    let address = keys(9).primary_address();
+    let payment = json!({ "address": address, "amount": amount, "amount_unit": amount_unit });
+    serde_json::from_value(json!([payment])).unwrap()
+  }
+
+  #[test]
+  fn payment_amounts_are_piconero_unless_amount_unit_is_set() {
+    let cases = [
+      ("1500", None, 1500),
+      ("1.5", Some("xmr"), XMR + XMR / 2),
+      ("2.5", Some("millinero"), 2_500_000_000),
+      ("0.000001", Some("micronero"), 1),
+      ("7", Some("piconero"), 7),
+    ];
+    for (amount, unit, piconero) in cases {
+      let payments = parse_payments(&payment_json(amount, unit), Network::Mainnet).unwrap();
+      assert_eq!(amounts(&payments), vec![piconero]);
+    }
+    let invalid = [("1.5", None), ("0.0000001", Some("micronero")), ("1,000", Some("xmr"))];
+    for (amount, unit) in invalid {
+      let error = parse_payments(&payment_json(amount, unit), Network::Mainnet).unwrap_err();
+      assert_eq!(error.code, INVALID_INPUT);
+      assert_eq!(error.field.as_deref(), Some("payments[0].amount"));
+    }
+  }
+
+  #[test]
+  fn the_fee_is_split_evenly_with_the_remainder_on_the_first_payments() {
+    let flagged = [true, false, true, true];
+    let split = subtract_fee_from_payments(payments(&[100; 4]), &flagged, 11, "even").unwrap();
+    assert_eq!(amounts(&split), vec![96, 100, 96, 97]);
+  }
+
+  #[test]
+  fn the_fee_is_split_proportionally_with_the_remainder_on_the_first_payments() {
+    let flagged = [true, true, false];
+    let split =
+      subtract_fee_from_payments(payments(&[100, 300, 50]), &flagged, 10, "proportional").unwrap();
+    // 2.5 and 7.5 round down, the remaining 1 goes to the first payment
+    assert_eq!(amounts(&split), vec![97, 293, 50]);
+  }
+
+  #[test]
+  fn a_payment_that_does_not_cover_its_share_is_an_error() {
+    let flagged = [true, true];
+    assert!(subtract_fee_from_payments(payments(&[5, 100]), &flagged, 10, "even").is_err());
+    assert!(subtract_fee_from_payments(payments(&[0, 0]), &flagged, 10, "proportional").is_err());
+    assert!(subtract_fee_from_payments(payments(&[50, 50]), &flagged, 10, "by_size").is_err());
+  }
+
+  #[test]
+  fn bad_spend_keys_and_signable_transactions_are_invalid_input() {
+    let wallet = keys(7);
+    let recipient = keys(9).viewpair().legacy_address(Network::Mainnet);
+    let tx = payment(&wallet, vec![(recipient, XMR)], Change::new(wallet.viewpair(), None));
+    let tx = hex::encode(tx.serialize());
+    // bad hex, too short, a non-canonical scalar
+    for spend_key in ["zz".to_string(), "abcd".to_string(), "ff".repeat(32)] {
+      let error = sign_transaction(tx.clone(), spend_key).unwrap_err();
+      assert_eq!(error.code, INVALID_INPUT);
+      assert_eq!(error.field.as_deref(), Some("secret_spend_key"));
+    }
+    for signable_transaction in ["zz", "00", &tx[..tx.len() / 2]] {
+      let error = sign_transaction(signable_transaction.to_string(), wallet.spend_key_hex());
+      let error = error.unwrap_err();
+      assert_eq!(error.code, INVALID_INPUT);
+      assert_eq!(error.field.as_deref(), Some("signable_transaction"));
+    }
+    assert!(sign_transaction(tx, wallet.spend_key_hex()).is_ok());
+  }
+
+  #[test]
+  fn the_outgoing_view_key_is_parsed_or_random() {
+    let key = parse_outgoing_view_key(Some(&hex::encode([3; 32]))).unwrap();
+    assert_eq!(*key, [3; 32]);
+    let error = parse_outgoing_view_key(Some("0303")).unwrap_err();
+    assert_eq!(error.field.as_deref(), Some("outgoing_view_key"));
+    assert_ne!(*parse_outgoing_view_key(None).unwrap(), *parse_outgoing_view_key(None).unwrap());
+  }
+}
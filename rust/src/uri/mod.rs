@@ -0,0 +1,315 @@
+use monero_wallet::address::{AddressType, MoneroAddress, Network};
+use serde::{Deserialize, Serialize};
+
+use crate::amount::{Unit, format_amount, parse_amount};
+use crate::error::AbiError;
+use crate::transaction_building::transaction::parse_address_for_network;
+
+pub const URI_SCHEME: &str = "monero:";
+
+#[derive(Debug, Serialize)]
+pub struct ParsedUri {
+  /// every recipient of the uri, the amount is null if the uri has none
+  payments: Vec<UriPayment>,
+  /// the standalone tx_payment_id as a decimal u64 like address_ownership reports it,
+  /// already folded into the (integrated) address of the payment
+  payment_id: Option<String>,
+  description: Option<String>,
+  /// parameters this parser does not know, as (key, value) kept in uri order
+  unknown_parameters: Vec<(String, String)>,
+}
+#[derive(Debug, Serialize)]
+struct UriPayment {
+  address: String,
+  amount: Option<String>,
+  recipient_name: Option<String>,
+}
+#[derive(Debug, Deserialize)]
+struct MakeUriParams {
+  recipients: Vec<UriRecipientJson>,
+  /// a decimal u64 like address_ownership reports it, only for a single primary address
+  payment_id: Option<String>,
+  description: Option<String>,
+}
+#[derive(Debug, Deserialize)]
+struct UriRecipientJson {
+  /// e.g. from make_integrated_address or make_subaddress
+  address: String,
+  /// piconero
+  amount: Option<String>,
+  name: Option<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct PaymentUri {
+  uri: String,
+}
+
+/// epee's convert_from_url_format, strict: every % must be followed by two hex digits
+/// and the result must be valid utf-8
+fn percent_decode(value: &str) -> Result<String, String> {
+  let bytes = value.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    if bytes[i] == b'%' {
+      let byte = value
+        .get(i + 1..i + 3)
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| format!("invalid percent encoding in '{}'", value))?;
+      decoded.push(byte);
+      i += 3;
+    } else {
+      decoded.push(bytes[i]);
+      i += 1;
+    }
+  }
+  String::from_utf8(decoded).map_err(|_| format!("'{}' is not valid utf-8", value))
+}
+/// epee's conver_to_url_format plus ';', which separates the values of several recipients
+fn percent_encode(value: &str) -> String {
+  let mut encoded = String::with_capacity(value.len());
+  for byte in value.bytes() {
+    if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+      encoded.push(byte as char);
+    } else {
+      encoded.push_str(&format!("%{:02X}", byte));
+    }
+  }
+  encoded
+}
+
+/// the tx_payment_id of the uri, only short (8 byte) payment ids, long ones are obsolete
+fn parse_payment_id(payment_id: &str) -> Result<[u8; 8], String> {
+  let mut bytes = [0; 8];
+  hex::decode_to_slice(payment_id, &mut bytes)
+    .map_err(|_| format!("invalid payment id '{}', must be 16 hex characters", payment_id))?;
+  Ok(bytes)
+}
+/// the payment id of the json params, the bytes of an integrated address are its little endian
+fn parse_decimal_payment_id(payment_id: &str) -> Result<[u8; 8], String> {
+  let payment_id = payment_id
+    .parse::<u64>()
+    .map_err(|e| format!("failed to parse payment id '{}' : {:?}", payment_id, e))?;
+  Ok(payment_id.to_le_bytes())
+}
+/// a standalone payment id goes into an integrated address, so it only works for one
+/// primary address
+fn with_payment_id(
+  addresses: &[MoneroAddress],
+  payment_id: [u8; 8],
+) -> Result<MoneroAddress, String> {
+  let [address] = addresses else {
+    return Err("a payment id can only be used with a single recipient".to_string());
+  };
+  if !matches!(address.kind(), AddressType::Legacy) {
+    return Err("a payment id can only be used with a primary address".to_string());
+  }
+  Ok(MoneroAddress::new(
+    address.network(),
+    AddressType::LegacyIntegrated(payment_id),
+    address.spend(),
+    address.view(),
+  ))
+}
+
+fn bad_uri(message: String) -> AbiError {
+  AbiError::invalid_input("uri", message)
+}
+
+/// wallet2's parse_uri, extended to several recipients: addresses, amounts and names are
+/// separated by ';'
+pub fn parse_uri(uri: &str, network: Network) -> Result<ParsedUri, AbiError> {
+  let scheme = uri.get(..URI_SCHEME.len()).filter(|scheme| scheme.eq_ignore_ascii_case(URI_SCHEME));
+  let Some(_) = scheme else {
+    return Err(bad_uri(format!("uri does not start with '{}'", URI_SCHEME)));
+  };
+  let rest = &uri[URI_SCHEME.len()..];
+  let (addresses, query) = rest.split_once('?').unwrap_or((rest, ""));
+  if addresses.is_empty() {
+    return Err(bad_uri("uri has no address".to_string()));
+  }
+  let addresses = addresses
+    .split(';')
+    .map(|address| parse_address_for_network(address, network).map_err(|e| e.with_field("uri")))
+    .collect::<Result<Vec<MoneroAddress>, AbiError>>()?;
+
+  let mut amounts = None;
+  let mut payment_id = None;
+  let mut names = None;
+  let mut description = None;
+  let mut unknown_parameters = vec![];
+  for parameter in query.split('&').filter(|_| !query.is_empty()) {
+    let (key, value) =
+      parameter.split_once('=').ok_or_else(|| bad_uri(format!("bad parameter '{}'", parameter)))?;
+    let duplicate = match key {
+      "tx_amount" => amounts.replace(value).is_some(),
+      "tx_payment_id" => payment_id.replace(value).is_some(),
+      "recipient_name" => names.replace(value).is_some(),
+      "tx_description" => description.replace(value).is_some(),
+      "" => return Err(bad_uri(format!("bad parameter '{}'", parameter))),
+      _ => {
+        let key = percent_decode(key).map_err(bad_uri)?;
+        unknown_parameters.push((key, percent_decode(value).map_err(bad_uri)?));
+        false
+      }
+    };
+    if duplicate {
+      return Err(bad_uri(format!("duplicate parameter '{}'", key)));
+    }
+  }
+
+  let per_recipient = |values: Option<&str>, name: &str| -> Result<Vec<Option<String>>, AbiError> {
+    let Some(values) = values else {
+      return Ok(vec![None; addresses.len()]);
+    };
+    let values: Vec<&str> = values.split(';').collect();
+    if values.len() != addresses.len() {
+      return Err(bad_uri(format!(
+        "{} has {} values for {} addresses",
+        name,
+        values.len(),
+        addresses.len()
+      )));
+    }
+    values.into_iter().map(|value| percent_decode(value).map(Some).map_err(bad_uri)).collect()
+  };
+  let amounts = per_recipient(amounts, "tx_amount")?
+    .into_iter()
+    .map(|amount| {
+      amount
+        .map(|amount| parse_amount(&amount, Unit::Xmr, None).map(|amount| amount.to_string()))
+        .transpose()
+        .map_err(bad_uri)
+    })
+    .collect::<Result<Vec<Option<String>>, AbiError>>()?;
+  let names = per_recipient(names, "recipient_name")?;
+
+  let payment_id = payment_id.map(parse_payment_id).transpose().map_err(bad_uri)?;
+  let addresses = match payment_id {
+    Some(payment_id) => vec![with_payment_id(&addresses, payment_id).map_err(bad_uri)?],
+    None => addresses,
+  };
+  let payments = addresses
+    .iter()
+    .zip(amounts)
+    .zip(names)
+    .map(|((address, amount), recipient_name)| UriPayment {
+      address: address.to_string(),
+      amount,
+      recipient_name,
+    })
+    .collect();
+  Ok(ParsedUri {
+    payments,
+    payment_id: payment_id.map(|payment_id| u64::from_le_bytes(payment_id).to_string()),
+    description: description.map(percent_decode).transpose().map_err(bad_uri)?,
+    unknown_parameters,
+  })
+}
+
+/// wallet2's make_uri for one or more recipients
+pub fn make_uri(json_params: &str, network: Network) -> Result<PaymentUri, AbiError> {
+  let params: MakeUriParams = serde_json::from_str(json_params).map_err(|e| {
+    AbiError::invalid_input("json_params", format!("failed to parse make uri params json: {:?}", e))
+  })?;
+  let bad_recipients = |message: &str| AbiError::invalid_input("recipients", message);
+  if params.recipients.is_empty() {
+    return Err(bad_recipients("uri needs at least one recipient"));
+  }
+  let mut addresses = vec![];
+  let mut amounts = vec![];
+  let mut names = vec![];
+  for (i, recipient) in params.recipients.iter().enumerate() {
+    let address = parse_address_for_network(&recipient.address, network)
+      .map_err(|e| e.with_field(&format!("recipients[{}].address", i)))?;
+    addresses.push(address);
+    let amount = match &recipient.amount {
+      Some(amount) => Some(amount.parse::<u64>().map_err(|e| {
+        let message = format!("failed to parse amount '{}' : {:?}", amount, e);
+        AbiError::invalid_input(&format!("recipients[{}].amount", i), message)
+      })?),
+      None => None,
+    };
+    amounts.push(amount);
+    names.push(recipient.name.as_deref());
+  }
+  let bad_payment_id = |message: String| AbiError::invalid_input("payment_id", message);
+  let payment_id = params
+    .payment_id
+    .as_deref()
+    .map(parse_decimal_payment_id)
+    .transpose()
+    .map_err(bad_payment_id)?;
+  if let Some(payment_id) = payment_id {
+    with_payment_id(&addresses, payment_id).map_err(bad_payment_id)?;
+  }
+  if amounts.iter().any(Option::is_some) && amounts.iter().any(Option::is_none) {
+    return Err(bad_recipients("either every recipient or none has an amount"));
+  }
+  if names.iter().any(Option::is_some) && names.iter().any(Option::is_none) {
+    return Err(bad_recipients("either every recipient or none has a name"));
+  }
+
+  let addresses: Vec<String> = addresses.iter().map(|address| address.to_string()).collect();
+  let mut uri = format!("{}{}", URI_SCHEME, addresses.join(";"));
+  let mut parameters = vec![];
+  if let Some(payment_id) = payment_id {
+    parameters.push(format!("tx_payment_id={}", hex::encode(payment_id)));
+  }
+  if amounts.iter().all(Option::is_some) {
+    let amounts: Vec<String> =
+      amounts.into_iter().flatten().map(|amount| format_amount(amount, Unit::Xmr, None)).collect();
+    parameters.push(format!("tx_amount={}", amounts.join(";")));
+  }
+  if names.iter().all(Option::is_some) {
+    let names: Vec<String> = names.into_iter().flatten().map(percent_encode).collect();
+    parameters.push(format!("recipient_name={}", names.join(";")));
+  }
+  if let Some(description) = &params.description {
+    parameters.push(format!("tx_description={}", percent_encode(description)));
+  }
+  if !parameters.is_empty() {
+    uri.push('?');
+    uri.push_str(&parameters.join("&"));
+  }
+  Ok(PaymentUri { uri })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::keys;
+  use serde_json::json;
+
+  #[test]
+  fn payment_ids_are_decimal_like_address_ownership() {
+    let address = keys(9).primary_address();
+    let params = json!({
+      "recipients": [{ "address": address, "amount": "1500000000000" }],
+      "payment_id": "1311768467294899695",
+    });
+    let uri = make_uri(&params.to_string(), Network::Mainnet).unwrap().uri;
+    assert!(uri.ends_with("?tx_payment_id=efcdab9078563412&tx_amount=1.5"), "{}", uri);
+
+    let parsed = parse_uri(&uri, Network::Mainnet).unwrap();
+    assert_eq!(parsed.payment_id.as_deref(), Some("1311768467294899695"));
+    let integrated = parse_address_for_network(&parsed.payments[0].address, Network::Mainnet);
+    assert_eq!(integrated.unwrap().payment_id(), Some(1311768467294899695u64.to_le_bytes()));
+    assert_eq!(parsed.payments[0].amount.as_deref(), Some("1500000000000"));
+  }
+
+  #[test]
+  fn amounts_are_optional() {
+    let uri = format!("monero:{}", keys(9).primary_address());
+    let parsed = parse_uri(&uri, Network::Mainnet).unwrap();
+    assert_eq!(parsed.payments[0].amount, None);
+  }
+
+  #[test]
+  fn addresses_have_to_be_on_the_network_of_the_wallet() {
+    let uri = format!("monero:{}", keys(9).primary_address());
+    assert!(parse_uri(&uri, Network::Stagenet).is_err());
+    let params = json!({ "recipients": [{ "address": keys(9).primary_address() }] });
+    assert!(make_uri(&params.to_string(), Network::Stagenet).is_err());
+  }
+}
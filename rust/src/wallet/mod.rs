@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use cuprate_rpc_types::bin::GetBlocksResponse;
+use curve25519_dalek::{EdwardsPoint, Scalar};
+use monero_wallet::{
+  Scanner, ViewPair,
+  address::{MoneroAddress, Network, SubaddressIndex},
+};
+use serde_json::json;
+use zeroize::Zeroizing;
+
+use crate::error::{AbiError, NO_HANDLES_LEFT, NOT_INITIALIZED, UNKNOWN_HANDLE};
+use crate::keypairs::ownership::{self, SubaddressTable};
+use crate::transaction_building::transaction::{change_subaddress_index, network_name};
+
+/// the wallet of init_viewpair, used by every export that takes no handle
+pub const DEFAULT_WALLET: u32 = 0;
+
+/// the state of one wallet: keys, scanner and the last loaded getblocks.bin response
+pub struct Wallet {
+  /// the public spend key, the viewpair is built from it and view_key when needed so the
+  /// private view key is stored once
+  spend: EdwardsPoint,
+  pub view_key: Zeroizing<Scalar>,
+  pub scanner: Scanner,
+  pub network: Network,
+  pub primary_address: String,
+  /// the subaddresses registered with the scanner, for address_ownership
+  pub subaddresses: SubaddressTable,
+  /// shared, so scanning one of its blocks doesn't hold the lock of every wallet
+  pub get_blocks_bin_response: Option<Arc<GetBlocksResponse>>,
+}
+
+/// every wallet of the process by handle, a mutex instead of a thread_local so a
+/// native host can use a handle from any of its threads
+static WALLETS: Mutex<BTreeMap<u32, Wallet>> = Mutex::new(BTreeMap::new());
+/// handles are never reused, so a freed handle can't reach another wallet
+static NEXT_HANDLE: AtomicU32 = AtomicU32::new(DEFAULT_WALLET + 1);
+
+impl Wallet {
+  /// subaddresses (0, 1..=last_subaddress_index) are registered with the scanner
+  pub fn new(
+    primary_address: &str,
+    secret_view_key: &str,
+    last_subaddress_index: u32,
+  ) -> Result<Wallet, AbiError> {
+    let address = MoneroAddress::from_str_with_unchecked_network(primary_address)
+      .map_err(|_| AbiError::invalid_input("primary_address", "primary-address-not-valid"))?;
+    let view_key = parse_view_key(secret_view_key)?;
+    let viewpair = ViewPair::new(address.spend(), view_key.clone()).map_err(|e| {
+      AbiError::invalid_input("primary_address", format!("invalid viewpair: {}", e))
+    })?;
+    let mut wallet = Wallet {
+      scanner: Scanner::new(viewpair),
+      spend: address.spend(),
+      view_key,
+      network: address.network(),
+      primary_address: primary_address.to_string(),
+      subaddresses: SubaddressTable::new(),
+      get_blocks_bin_response: None,
+    };
+    for minor in 1..=last_subaddress_index {
+      // minor starts at 1 so this is never None
+      wallet.register_subaddress(SubaddressIndex::new(0, minor).unwrap());
+    }
+    Ok(wallet)
+  }
+  /// ViewPair::new succeeded with the same keys in Wallet::new
+  pub fn viewpair(&self) -> ViewPair {
+    ViewPair::new(self.spend, self.view_key.clone()).expect("keys were checked in Wallet::new")
+  }
+  pub fn network_json(&self) -> String {
+    json!({ "network": network_name(self.network) }).to_string()
+  }
+  pub fn register_subaddress(&mut self, index: SubaddressIndex) {
+    self.scanner.register_subaddress(index);
+    let viewpair = self.viewpair();
+    ownership::register(&mut self.subaddresses, &viewpair, index);
+  }
+  /// change sent to a subaddress has to be found by the scanner, returns the registered index
+  pub fn register_change_subaddress(&mut self, json_params: &str) -> Option<SubaddressIndex> {
+    let index = change_subaddress_index(json_params)?;
+    self.register_subaddress(index);
+    Some(index)
+  }
+}
+
+fn parse_view_key(secret_view_key: &str) -> Result<Zeroizing<Scalar>, AbiError> {
+  let not_valid = || AbiError::invalid_input("secret_view_key", "view-key-not-valid");
+  let mut bytes = [0; 32];
+  hex::decode_to_slice(secret_view_key, &mut bytes).map_err(|_| not_valid())?;
+  let view_key =
+    Option::<Scalar>::from(Scalar::from_canonical_bytes(bytes)).ok_or_else(not_valid)?;
+  Ok(Zeroizing::new(view_key))
+}
+
+/// the counter stops at u32::MAX instead of wrapping around to DEFAULT_WALLET and the
+/// handles of live wallets
+fn next_handle(counter: &AtomicU32) -> Result<u32, AbiError> {
+  counter
+    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |handle| handle.checked_add(1))
+    .map_err(|_| AbiError::new(NO_HANDLES_LEFT, "no wallet handles left"))
+}
+/// stores the wallet under a new handle, never DEFAULT_WALLET
+pub fn insert(wallet: Wallet) -> Result<u32, AbiError> {
+  let handle = next_handle(&NEXT_HANDLE)?;
+  WALLETS.lock().unwrap_or_else(|e| e.into_inner()).insert(handle, wallet);
+  Ok(handle)
+}
+/// init_viewpair replaces the default wallet
+pub fn set_default(wallet: Wallet) {
+  WALLETS.lock().unwrap_or_else(|e| e.into_inner()).insert(DEFAULT_WALLET, wallet);
+}
+/// false if there was no wallet under the handle
+pub fn free(handle: u32) -> bool {
+  WALLETS.lock().unwrap_or_else(|e| e.into_inner()).remove(&handle).is_some()
+}
+/// runs f with the wallet of the handle, the lock is held meanwhile so f should not scan
+pub fn with_wallet<T>(handle: u32, f: impl FnOnce(&mut Wallet) -> T) -> Result<T, AbiError> {
+  let mut wallets = WALLETS.lock().unwrap_or_else(|e| e.into_inner());
+  match wallets.get_mut(&handle) {
+    Some(wallet) => Ok(f(wallet)),
+    None if handle == DEFAULT_WALLET => {
+      Err(AbiError::new(NOT_INITIALIZED, "wallet is not initialized, call init_viewpair first"))
+    }
+    None => Err(AbiError::new(UNKNOWN_HANDLE, format!("unknown wallet handle {}", handle))),
+  }
+}
+/// copies of the keys, so the lock is not held while an export works with them
+pub fn keys(handle: u32) -> Result<(ViewPair, Zeroizing<Scalar>, Network), AbiError> {
+  with_wallet(handle, |wallet| (wallet.viewpair(), wallet.view_key.clone(), wallet.network))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn handles_do_not_wrap_around_to_the_default_wallet() {
+    let counter = AtomicU32::new(u32::MAX - 1);
+    assert_eq!(next_handle(&counter).unwrap(), u32::MAX - 1);
+    let error = next_handle(&counter).unwrap_err();
+    assert_eq!(error.code, NO_HANDLES_LEFT);
+    // stays exhausted
+    assert!(next_handle(&counter).is_err());
+  }
+}
@@ -0,0 +1,385 @@
+//! feeds malformed input to every export and checks that it fails through output_error
+//! with the {code, message, field} schema instead of panicking (an abort in the wasm build)
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use monero_wallet_api::*;
+use serde_json::{Value, json};
+
+/// what the host side of the ABI saw during one export call, natively the callbacks are
+/// registered with mwa_set_io_callbacks instead of being linked in
+#[derive(Default)]
+struct Host {
+  inputs: VecDeque<Vec<u8>>,
+  outputs: Vec<Vec<u8>>,
+  errors: Vec<Vec<u8>>,
+}
+
+thread_local! {
+  static HOST: RefCell<Host> = RefCell::new(Host::default());
+}
+
+extern "C" fn input(ptr: *mut u8, length: usize) {
+  // a panic can't unwind out of an extern "C" fn, a wrong length is caught by the outputs
+  let data = HOST.with(|host| host.borrow_mut().inputs.pop_front()).unwrap_or_default();
+  unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len().min(length)) };
+}
+extern "C" fn output(ptr: *const u8, length: usize) {
+  let data = unsafe { std::slice::from_raw_parts(ptr, length) }.to_vec();
+  HOST.with(|host| host.borrow_mut().outputs.push(data));
+}
+extern "C" fn output_error(ptr: *const u8, length: usize) {
+  let data = unsafe { std::slice::from_raw_parts(ptr, length) }.to_vec();
+  HOST.with(|host| host.borrow_mut().errors.push(data));
+}
+
+/// queues the inputs, calls the export with their lengths and returns what it output
+fn call(inputs: &[&[u8]], export: impl FnOnce(&[usize])) -> Host {
+  native::mwa_set_io_callbacks(Some(input), Some(output), Some(output_error));
+  HOST.with(|host| {
+    *host.borrow_mut() =
+      Host { inputs: inputs.iter().map(|input| input.to_vec()).collect(), ..Default::default() }
+  });
+  let lengths: Vec<usize> = inputs.iter().map(|input| input.len()).collect();
+  export(&lengths);
+  HOST.with(|host| std::mem::take(&mut *host.borrow_mut()))
+}
+
+/// the export failed exactly once, with the stable schema, and output nothing else
+fn assert_abi_error(name: &str, host: &Host) -> Value {
+  assert!(host.outputs.is_empty(), "{}: output on error: {:?}", name, host.outputs);
+  assert_eq!(host.errors.len(), 1, "{}: expected one error", name);
+  let error: Value = serde_json::from_slice(&host.errors[0])
+    .unwrap_or_else(|e| panic!("{}: error is not json: {}", name, e));
+  assert!(error["code"].is_string(), "{}: no code in {}", name, error);
+  assert!(error["message"].is_string(), "{}: no message in {}", name, error);
+  assert!(error["field"].is_string() || error["field"].is_null(), "{}: bad field {}", name, error);
+  error
+}
+fn assert_code(name: &str, host: &Host, code: &str) -> Value {
+  let error = assert_abi_error(name, host);
+  assert_eq!(error["code"], code, "{}: {}", name, error);
+  error
+}
+
+/// none of these is json, hex, an address or an epee response
+const MALFORMED: &[&[u8]] =
+  &[b"", b"{", b"zz", b"{\"amount\":", b"[1, 2", &[0xff, 0xfe, 0xfd], &[0; 7], b"\0\0\0"];
+
+/// exports that take one json/text/binary input
+type OneInput = (&'static str, extern "C" fn(usize));
+const ONE_INPUT: &[OneInput] = &[
+  ("make_spendkey_from_seed", make_spendkey_from_seed),
+  ("make_viewkey", make_viewkey),
+  ("sample_decoys", sample_decoys),
+  ("parse_address", parse_address),
+  ("make_transaction", make_transaction),
+  ("make_external_sweep_transaction", make_external_sweep_transaction),
+  ("estimate_fee", estimate_fee),
+  ("select_inputs", select_inputs),
+  ("make_sweep_all_transactions", make_sweep_all_transactions),
+  ("make_batch_payout", make_batch_payout),
+  ("inspect_signable_transaction", inspect_signable_transaction),
+  ("get_tx_key", get_tx_key),
+  ("check_tx_key", check_tx_key),
+  ("get_out_proof", get_out_proof),
+  ("check_out_proof", check_out_proof),
+  ("get_in_proof", get_in_proof),
+  ("check_in_proof", check_in_proof),
+  ("get_reserve_proof", get_reserve_proof),
+  ("parse_reserve_proof", parse_reserve_proof),
+  ("check_reserve_proof", check_reserve_proof),
+  ("sign_message", sign_message),
+  ("verify_message", verify_message),
+  ("parse_payment_uri", parse_payment_uri),
+  ("make_payment_uri", make_payment_uri),
+  ("openalias_query_name", openalias_query_name),
+  ("parse_openalias", parse_openalias),
+  ("address_ownership", address_ownership),
+  ("parse_amount", parse_amount),
+  ("format_amount", format_amount),
+  ("build_getblocksbin_request", build_getblocksbin_request),
+  ("build_getoutsbin_request", build_getoutsbin_request),
+  ("build_send_raw_transaction_request", build_send_raw_transaction_request),
+  ("parse_send_raw_transaction_response", parse_send_raw_transaction_response),
+  ("convert_get_outs_bin_response_to_json", convert_get_outs_bin_response_to_json),
+  ("scan_blocks_with_get_blocks_bin", scan_blocks_with_get_blocks_bin),
+  ("load_get_blocks_bin_response", load_get_blocks_bin_response),
+  ("scan_cache_compact", scan_cache_compact),
+  ("compute_balances", compute_balances),
+];
+type TwoInputs = (&'static str, extern "C" fn(usize, usize));
+const TWO_INPUTS: &[TwoInputs] = &[
+  ("make_input", make_input),
+  ("sign_transaction", sign_transaction),
+  ("decode_transaction", decode_transaction),
+  ("check_spend_proof", check_spend_proof),
+  ("compute_key_image", compute_key_image),
+];
+
+#[test]
+fn one_input_exports_report_malformed_input() {
+  for (name, export) in ONE_INPUT {
+    for malformed in MALFORMED {
+      let host = call(&[*malformed], |lengths| export(lengths[0]));
+      assert_abi_error(name, &host);
+    }
+  }
+}
+
+#[test]
+fn two_input_exports_report_malformed_input() {
+  for (name, export) in TWO_INPUTS {
+    for first in MALFORMED {
+      for second in MALFORMED {
+        let host = call(&[*first, *second], |lengths| export(lengths[0], lengths[1]));
+        assert_abi_error(name, &host);
+      }
+    }
+  }
+}
+
+#[test]
+fn three_input_exports_report_malformed_input() {
+  for malformed in MALFORMED {
+    let host = call(&[*malformed, *malformed, *malformed], |lengths| {
+      get_spend_proof(lengths[0], lengths[1], lengths[2])
+    });
+    assert_abi_error("get_spend_proof", &host);
+    let host = call(&[b"{", *malformed, *malformed], |lengths| {
+      scan_cache_append(lengths[0], lengths[1], lengths[2])
+    });
+    assert_abi_error("scan_cache_append", &host);
+  }
+}
+
+#[test]
+fn bad_hex_keys_are_invalid_input() {
+  let host = call(&[b"not hex"], |lengths| make_spendkey_from_seed(lengths[0]));
+  assert_eq!(assert_code("make_spendkey_from_seed", &host, "invalid_input")["field"], "seed");
+  let host = call(&[b"abcd"], |lengths| make_viewkey(lengths[0]));
+  assert_eq!(assert_code("make_viewkey", &host, "invalid_input")["field"], "spend_key");
+  let host = call(&[b"zz", b"zz"], |lengths| init_viewpair(lengths[0], lengths[1], 0));
+  assert_eq!(assert_code("init_viewpair", &host, "invalid_input")["field"], "primary_address");
+  let host = call(&[b"zz", b"zz"], |lengths| {
+    assert_eq!(wallet_init(lengths[0], lengths[1], 0), 0);
+  });
+  assert_code("wallet_init", &host, "invalid_input");
+}
+
+/// a new mainnet wallet, its primary address and the stagenet address of the same keys
+fn new_wallet() -> (u32, String, String) {
+  let host = call(&[], |_| make_spendkey());
+  let host = call(&[&host.outputs[0]], |lengths| make_viewkey(lengths[0]));
+  let viewpair: Value = serde_json::from_slice(&host.outputs[0]).unwrap();
+  let field = |name: &str| viewpair[name].as_str().unwrap().to_string();
+  let (primary, stagenet_primary, view_key) =
+    (field("mainnet_primary"), field("stagenet_primary"), field("view_key"));
+  let mut handle = 0;
+  let host = call(&[primary.as_bytes(), view_key.as_bytes()], |lengths| {
+    handle = wallet_init(lengths[0], lengths[1], 3);
+  });
+  assert!(host.errors.is_empty());
+  assert_ne!(handle, 0);
+  (handle, primary, stagenet_primary)
+}
+
+#[test]
+fn wallet_exports_report_malformed_input() {
+  let (handle, _, _) = new_wallet();
+  type WalletExport = (&'static str, extern "C" fn(u32, usize));
+  let exports: &[WalletExport] = &[
+    ("wallet_parse_address", wallet_parse_address),
+    ("wallet_make_transaction", wallet_make_transaction),
+    ("wallet_make_external_sweep_transaction", wallet_make_external_sweep_transaction),
+    ("wallet_select_inputs", wallet_select_inputs),
+    ("wallet_make_sweep_all_transactions", wallet_make_sweep_all_transactions),
+    ("wallet_make_batch_payout", wallet_make_batch_payout),
+    ("wallet_inspect_signable_transaction", wallet_inspect_signable_transaction),
+    ("wallet_get_in_proof", wallet_get_in_proof),
+    ("wallet_get_reserve_proof", wallet_get_reserve_proof),
+    ("wallet_sign_message", wallet_sign_message),
+    ("wallet_check_tx_key", wallet_check_tx_key),
+    ("wallet_get_out_proof", wallet_get_out_proof),
+    ("wallet_check_out_proof", wallet_check_out_proof),
+    ("wallet_check_in_proof", wallet_check_in_proof),
+    ("wallet_check_reserve_proof", wallet_check_reserve_proof),
+    ("wallet_verify_message", wallet_verify_message),
+    ("wallet_parse_payment_uri", wallet_parse_payment_uri),
+    ("wallet_make_payment_uri", wallet_make_payment_uri),
+    ("wallet_parse_openalias", wallet_parse_openalias),
+    ("wallet_address_ownership", wallet_address_ownership),
+    ("wallet_scan_blocks_with_get_blocks_bin", wallet_scan_blocks_with_get_blocks_bin),
+    ("wallet_load_get_blocks_bin_response", wallet_load_get_blocks_bin_response),
+  ];
+  for (name, export) in exports {
+    for malformed in MALFORMED {
+      let host = call(&[*malformed], |lengths| export(handle, lengths[0]));
+      assert_abi_error(name, &host);
+    }
+  }
+  let malformed_caches: [&[u8]; 3] = [b"{", b"zz", &[0xff, 0xfe]];
+  for malformed in malformed_caches {
+    let host = call(&[malformed], |lengths| wallet_scan_cache_compact(handle, lengths[0]));
+    assert_code("wallet_scan_cache_compact", &host, "invalid_input");
+    let host = call(&[b"", malformed, b""], |lengths| {
+      wallet_scan_cache_append(handle, lengths[0], lengths[1], lengths[2])
+    });
+    assert_abi_error("wallet_scan_cache_append", &host);
+  }
+
+  let host = call(&[], |_| wallet_make_subaddress(handle, 0, 0));
+  assert_eq!(assert_code("wallet_make_subaddress", &host, "invalid_input")["field"], "minor");
+  let host = call(&[], |_| wallet_get_blocks_bin_scan_one_block(handle, 0));
+  assert_code("wallet_get_blocks_bin_scan_one_block", &host, "failed");
+  assert!(wallet_free(handle));
+}
+
+/// well formed json with one bad hex, length or non canonical scalar names that field
+#[test]
+fn bad_values_in_well_formed_input_name_their_field() {
+  let (handle, primary, _) = new_wallet();
+  let key = format!("01{}", "00".repeat(31));
+  let non_canonical = "ff".repeat(32);
+  let tx_hash = "00".repeat(32);
+  let assert_field = |name: &str, host: &Host, field: &str| {
+    assert_eq!(assert_code(name, host, "invalid_input")["field"], field, "{}", name);
+  };
+
+  for spend_key in ["zz", "abcd", non_canonical.as_str()] {
+    let host =
+      call(&[b"00", spend_key.as_bytes()], |lengths| sign_transaction(lengths[0], lengths[1]));
+    assert_field("sign_transaction", &host, "secret_spend_key");
+  }
+  for tx in ["zz", "00"] {
+    let host =
+      call(&[tx.as_bytes(), key.as_bytes()], |lengths| sign_transaction(lengths[0], lengths[1]));
+    assert_field("sign_transaction", &host, "signable_transaction");
+  }
+  let host = call(&[b"zz", key.as_bytes()], |lengths| compute_key_image(lengths[0], lengths[1]));
+  assert_field("compute_key_image", &host, "output");
+  let host = call(&[non_canonical.as_bytes()], |lengths| make_viewkey(lengths[0]));
+  assert_field("make_viewkey", &host, "spend_key");
+  let host = call(&[primary.as_bytes(), non_canonical.as_bytes()], |lengths| {
+    assert_eq!(wallet_init(lengths[0], lengths[1], 0), 0);
+  });
+  assert_field("wallet_init", &host, "secret_view_key");
+  let host = call(&[br#"{"amount":"1.2.3"}"#], |lengths| parse_amount(lengths[0]));
+  assert_field("parse_amount", &host, "amount");
+
+  let fee_response = json!({ "fee": 20000, "quantization_mask": 10000 });
+  let cases: &[(&str, extern "C" fn(u32, usize), Value, &str)] = &[
+    (
+      "wallet_check_tx_key",
+      wallet_check_tx_key,
+      json!({ "tx_hash": "abcd", "tx_key": key, "address": primary, "transaction": "00" }),
+      "tx_hash",
+    ),
+    (
+      "wallet_check_tx_key",
+      wallet_check_tx_key,
+      json!({ "tx_hash": tx_hash, "tx_key": key, "address": primary, "transaction": "zz" }),
+      "transaction",
+    ),
+    (
+      "wallet_get_out_proof",
+      wallet_get_out_proof,
+      json!({ "tx_hash": "zz", "tx_key": key, "address": primary }),
+      "tx_hash",
+    ),
+    (
+      "wallet_get_out_proof",
+      wallet_get_out_proof,
+      json!({ "tx_hash": tx_hash, "tx_key": non_canonical, "address": primary }),
+      "tx_key",
+    ),
+    (
+      "wallet_make_transaction",
+      wallet_make_transaction,
+      json!({
+        "inputs": [], "payments": [], "fee_response": fee_response, "fee_priority": "whenever",
+      }),
+      "fee_priority",
+    ),
+    (
+      "wallet_make_transaction",
+      wallet_make_transaction,
+      json!({
+        "inputs": ["zz"], "payments": [], "fee_response": fee_response, "fee_priority": "normal",
+      }),
+      "inputs[0]",
+    ),
+    (
+      "wallet_make_transaction",
+      wallet_make_transaction,
+      json!({
+        "inputs": [],
+        "payments": [{ "address": primary, "amount": "1.5" }],
+        "fee_response": fee_response,
+        "fee_priority": "normal",
+      }),
+      "payments[0].amount",
+    ),
+    ("wallet_sign_message", wallet_sign_message, json!({ "message": "hi", "key": "both" }), "key"),
+    (
+      "wallet_sign_message",
+      wallet_sign_message,
+      json!({ "message": "hi", "key": "spend", "spend_key": non_canonical }),
+      "spend_key",
+    ),
+  ];
+  for (name, export, params, field) in cases {
+    let params = params.to_string();
+    let host = call(&[params.as_bytes()], |lengths| export(handle, lengths[0]));
+    assert_field(name, &host, field);
+  }
+  assert!(wallet_free(handle));
+}
+
+#[test]
+fn addresses_of_another_network_are_a_network_mismatch() {
+  let (handle, _, stagenet_primary) = new_wallet();
+  let host =
+    call(&[stagenet_primary.as_bytes()], |lengths| wallet_parse_address(handle, lengths[0]));
+  assert_eq!(assert_code("wallet_parse_address", &host, "network_mismatch")["field"], "address");
+  let host =
+    call(&[stagenet_primary.as_bytes()], |lengths| wallet_address_ownership(handle, lengths[0]));
+  assert_code("wallet_address_ownership", &host, "network_mismatch");
+  wallet_free(handle);
+}
+
+#[test]
+fn freed_and_unknown_handles_are_reported() {
+  let (handle, _, _) = new_wallet();
+  assert!(wallet_free(handle));
+  assert!(!wallet_free(handle));
+  for handle in [handle, u32::MAX] {
+    let host = call(&[], |_| wallet_make_integrated_address(handle, 1));
+    assert_code("wallet_make_integrated_address", &host, "unknown_handle");
+    let host = call(&[], |_| wallet_make_subaddress(handle, 0, 1));
+    assert_code("wallet_make_subaddress", &host, "unknown_handle");
+    let host = call(&[b"{}"], |lengths| wallet_make_transaction(handle, lengths[0]));
+    assert_code("wallet_make_transaction", &host, "unknown_handle");
+    let host = call(&[b"{}"], |lengths| wallet_sign_message(handle, lengths[0]));
+    assert_code("wallet_sign_message", &host, "unknown_handle");
+    let host = call(&[b""], |lengths| wallet_scan_cache_compact(handle, lengths[0]));
+    assert_code("wallet_scan_cache_compact", &host, "unknown_handle");
+    let host = call(&[], |_| wallet_get_blocks_bin_scan_one_block(handle, 0));
+    assert_code("wallet_get_blocks_bin_scan_one_block", &host, "unknown_handle");
+  }
+}
+
+#[test]
+fn default_wallet_exports_before_init_viewpair_are_not_initialized() {
+  // no test initializes the default wallet
+  let host = call(&[], |_| make_integrated_address(1));
+  assert_code("make_integrated_address", &host, "not_initialized");
+  let host = call(&[], |_| get_blocks_bin_scan_one_block(0));
+  assert_code("get_blocks_bin_scan_one_block", &host, "not_initialized");
+  let host = call(&[b"{}"], |lengths| sign_message(lengths[0]));
+  assert_code("sign_message", &host, "not_initialized");
+  // an empty cache is a new one, it only fails for the missing wallet
+  let host = call(&[b""], |lengths| scan_cache_compact(lengths[0]));
+  assert_code("scan_cache_compact", &host, "not_initialized");
+}
@@ -0,0 +1,146 @@
+// runs the native C API of libmonero_wallet_api.so: make -C tests/c
+#include <stdio.h>
+#include <string.h>
+
+#include "monero_wallet_api.h"
+
+static int failures = 0;
+
+#define CHECK(condition)                                          \
+  do {                                                            \
+    if (!(condition)) {                                           \
+      fprintf(stderr, "%s:%d: %s\n", __FILE__, __LINE__, #condition); \
+      failures++;                                                 \
+    }                                                             \
+  } while (0)
+
+#define STR(s) (const uint8_t *)(s), strlen(s)
+
+// the first output of a call that has to succeed, NUL terminated
+static const char *ok_output(const MwaResult *result) {
+  if (!mwa_result_ok(result)) {
+    fprintf(stderr, "unexpected error: %s\n", (const char *)mwa_result_error(result, NULL));
+    failures++;
+    return "";
+  }
+  return (const char *)mwa_result_output(result, 0, NULL);
+}
+
+// the error json of a call that has to fail
+static const char *error_json(const MwaResult *result) {
+  const char *error = (const char *)mwa_result_error(result, NULL);
+  if (mwa_result_ok(result) || error == NULL) {
+    fprintf(stderr, "expected an error\n");
+    failures++;
+    return "";
+  }
+  return error;
+}
+
+// copies the string value of "key":"..." out of a flat json object
+static void json_string(const char *json, const char *key, char *value, size_t capacity) {
+  char pattern[64];
+  snprintf(pattern, sizeof(pattern), "\"%s\":\"", key);
+  const char *start = strstr(json, pattern);
+  value[0] = '\0';
+  if (start == NULL) return;
+  start += strlen(pattern);
+  const char *end = strchr(start, '"');
+  if (end == NULL || (size_t)(end - start) >= capacity) return;
+  memcpy(value, start, end - start);
+  value[end - start] = '\0';
+}
+
+static void test_keys_and_wallet(void) {
+  MwaResult *spend_key = mwa_make_spendkey();
+  CHECK(strlen(ok_output(spend_key)) == 64);
+
+  MwaResult *viewpair = mwa_make_viewkey(STR(ok_output(spend_key)));
+  char primary[128], view_key[65];
+  json_string(ok_output(viewpair), "mainnet_primary", primary, sizeof(primary));
+  json_string(ok_output(viewpair), "view_key", view_key, sizeof(view_key));
+  CHECK(primary[0] == '4');
+  CHECK(strlen(view_key) == 64);
+
+  uint32_t handle = 0;
+  MwaResult *wallet = mwa_wallet_init(STR(primary), STR(view_key), 2, &handle);
+  CHECK(handle != 0);
+  CHECK(strcmp(ok_output(wallet), "{\"network\":\"mainnet\"}") == 0);
+
+  MwaResult *subaddress = mwa_wallet_make_subaddress(handle, 0, 1);
+  CHECK(ok_output(subaddress)[0] == '8');
+  MwaResult *ownership = mwa_wallet_address_ownership(handle, STR(ok_output(subaddress)));
+  CHECK(strstr(ok_output(ownership), "\"status\":\"subaddress\"") != NULL);
+
+  MwaResult *primary_index = mwa_wallet_make_subaddress(handle, 0, 0);
+  CHECK(strstr(error_json(primary_index), "\"code\":\"invalid_input\"") != NULL);
+  CHECK(strstr(error_json(primary_index), "\"field\":\"minor\"") != NULL);
+
+  CHECK(mwa_wallet_free(handle));
+  CHECK(!mwa_wallet_free(handle));
+  MwaResult *freed = mwa_wallet_make_integrated_address(handle, 1);
+  CHECK(strstr(error_json(freed), "\"code\":\"unknown_handle\"") != NULL);
+
+  MwaResult *results[] = {spend_key, viewpair, wallet, subaddress, ownership, primary_index, freed};
+  for (size_t i = 0; i < sizeof(results) / sizeof(results[0]); i++) mwa_result_free(results[i]);
+}
+
+static void test_caller_buffers(void) {
+  MwaResult *amount = mwa_parse_amount(STR("{\"amount\":\"1.5\"}"));
+  const char *expected = "{\"piconero\":\"1500000000000\"}";
+  CHECK(strcmp(ok_output(amount), expected) == 0);
+  CHECK(mwa_result_output_count(amount) == 1);
+
+  char small[4] = "abc";
+  size_t length = mwa_result_copy_output(amount, 0, (uint8_t *)small, sizeof(small));
+  CHECK(length == strlen(expected));
+  CHECK(strcmp(small, "abc") == 0);  // too small, nothing copied
+
+  char buffer[64] = {0};
+  CHECK(mwa_result_copy_output(amount, 0, (uint8_t *)buffer, sizeof(buffer)) == length);
+  CHECK(strcmp(buffer, expected) == 0);
+  CHECK(mwa_result_output(amount, 1, &length) == NULL && length == 0);
+  CHECK(mwa_result_error(amount, NULL) == NULL);
+  mwa_result_free(amount);
+
+  MwaResult *request = mwa_build_getblocksbin_request(STR("{\"start_height\":100}"));
+  mwa_result_output(request, 0, &length);
+  CHECK(mwa_result_ok(request) && length > 0);
+  mwa_result_free(request);
+}
+
+static void test_malformed_input(void) {
+  MwaResult *bad_hex = mwa_make_viewkey(STR("not hex"));
+  CHECK(mwa_result_output_count(bad_hex) == 0);
+  CHECK(strstr(error_json(bad_hex), "\"field\":\"spend_key\"") != NULL);
+
+  MwaResult *null_input = mwa_parse_address(NULL, 10);
+  CHECK(strstr(error_json(null_input), "\"code\":\"invalid_input\"") != NULL);
+
+  MwaResult *empty = mwa_parse_payment_uri(NULL, 0);
+  error_json(empty);
+
+  const uint8_t garbage[] = {0xff, 0xfe, 0x00, 0x7b};
+  MwaResult *binary = mwa_convert_get_outs_bin_response_to_json(garbage, sizeof(garbage));
+  CHECK(strstr(error_json(binary), "\"field\":\"getouts_response\"") != NULL);
+
+  MwaResult *entropy = mwa_vk_from_entropy(garbage, sizeof(garbage));
+  CHECK(strstr(error_json(entropy), "\"field\":\"entropy\"") != NULL);
+
+  MwaResult *results[] = {bad_hex, null_input, empty, binary, entropy};
+  for (size_t i = 0; i < sizeof(results) / sizeof(results[0]); i++) mwa_result_free(results[i]);
+  mwa_result_free(NULL);
+  CHECK(!mwa_result_ok(NULL));
+}
+
+int main(void) {
+  test_keys_and_wallet();
+  test_caller_buffers();
+  test_malformed_input();
+  if (failures > 0) {
+    fprintf(stderr, "%d check(s) failed\n", failures);
+    return 1;
+  }
+  printf("native api: all checks passed\n");
+  return 0;
+}
@@ -0,0 +1,124 @@
+#!/usr/bin/env bun
+import { join } from "node:path";
+import { NodeUrl } from "../wallet-api/api";
+
+// writes the wallet-cli fixtures of rust/tests/fixtures (see the README there)
+
+// needs a synced stagenet node and a monero-wallet-rpc with the wallet open:
+// ./monero-wallet-rpc --stagenet --daemon-address 127.0.0.1:38081 --rpc-bind-port 38083 --disable-rpc-login --wallet-file <wallet>
+
+// in_txid=<txid the wallet received> in_address=<address it went to> spend_txid=<txid the wallet sent> bun run scripts/stagenet_fixtures.ts
+// optional: node=<node url> wallet_rpc=<wallet rpc url>
+const node = Bun.env["node"] || "http://127.0.0.1:38081";
+const wallet_rpc = Bun.env["wallet_rpc"] || "http://127.0.0.1:38083";
+const fixtures_dir = join(import.meta.dir, "../../rust/tests/fixtures");
+const message = "monero-wallet-api fixture";
+
+function env(name: string): string {
+  const value = Bun.env[name];
+  if (!value) {
+    console.error(`missing ${name}=...`);
+    process.exit(1);
+  }
+  return value;
+}
+async function post(url: string, body: BodyInit, content_type = "application/json") {
+  const response = await fetch(url, {
+    method: "POST",
+    headers: { "Content-Type": content_type },
+    body,
+  });
+  if (!response.ok) throw new Error(`${url}: ${response.status}`);
+  return response;
+}
+async function json_rpc(method: string, params: object) {
+  const response = await post(
+    wallet_rpc + "/json_rpc",
+    JSON.stringify({ jsonrpc: "2.0", id: "0", method, params })
+  );
+  const json = await response.json();
+  if (json.error) throw new Error(`${method}: ${JSON.stringify(json.error)}`);
+  return json.result;
+}
+/** the as_hex of get_transactions and the decoded transaction */
+async function transaction(txid: string) {
+  const response = await post(
+    node + "/get_transactions",
+    JSON.stringify({ txs_hashes: [txid], decode_as_json: true })
+  );
+  const tx = (await response.json()).txs[0];
+  return { as_hex: tx.as_hex as string, decoded: JSON.parse(tx.as_json) };
+}
+
+async function in_proof() {
+  const txid = env("in_txid");
+  const address = env("in_address");
+  const { as_hex } = await transaction(txid);
+  const params = { txid, address, message };
+  const { signature } = await json_rpc("get_tx_proof", params);
+  const { received } = await json_rpc("check_tx_proof", { ...params, signature });
+  return {
+    tx_hash: txid,
+    address,
+    message,
+    signature,
+    transaction: as_hex,
+    received: String(received),
+  };
+}
+
+async function reserve_proof() {
+  const { address } = await json_rpc("get_address", { account_index: 0 });
+  const { signature } = await json_rpc("get_reserve_proof", { all: true, message });
+  const { total } = await json_rpc("check_reserve_proof", { address, message, signature });
+  // "all" proves every unspent output
+  const { transfers = [] } = await json_rpc("incoming_transfers", {
+    transfer_type: "available",
+  });
+  const transactions: Record<string, string> = {};
+  for (const { tx_hash } of transfers) {
+    transactions[tx_hash] ??= (await transaction(tx_hash)).as_hex;
+  }
+  return { address, message, signature, transactions, total: String(total) };
+}
+
+async function spend_proof() {
+  const txid = env("spend_txid");
+  const { as_hex, decoded } = await transaction(txid);
+  const { signature } = await json_rpc("get_spend_proof", { txid, message });
+  // the ring members input after input, the key offsets are relative to the previous one
+  const indices: number[] = [];
+  for (const input of decoded.vin) {
+    let index = 0;
+    for (const offset of input.key.key_offsets) {
+      index += offset;
+      indices.push(index);
+    }
+  }
+  const ring_members = await (await NodeUrl.create(node)).getOutsBin(indices);
+  return {
+    tx_hash: txid,
+    transaction: as_hex,
+    message,
+    signature,
+    ring_members: Buffer.from(ring_members).toString("hex"),
+  };
+}
+
+async function message_signature() {
+  const { address } = await json_rpc("get_address", { account_index: 0 });
+  const key = "spend";
+  const { signature } = await json_rpc("sign", { data: message, signature_type: key });
+  return { message, address, signature, key };
+}
+
+const fixtures: Record<string, () => Promise<object>> = {
+  "in_proof.json": in_proof,
+  "reserve_proof.json": reserve_proof,
+  "spend_proof.json": spend_proof,
+  "message.json": message_signature,
+};
+for (const [name, make] of Object.entries(fixtures)) {
+  await Bun.write(join(fixtures_dir, name), JSON.stringify(await make(), null, 2) + "\n");
+  console.log("wrote", name);
+}
@@ -70,6 +70,12 @@ export type GetBlocksResultMeta = {
   daemon_height: number;
   status: Status;
   block_infos: BlockInfo[];
+  scan_stats?: ScanStats; // only present when the response was scanned
+};
+export type ScanStats = {
+  txs_seen: number;
+  view_tag_matches: number; // outputs whose view tag matched, these txs were fully parsed
+  found_outputs: number;
 };
 export type BlockInfo = {
   block_timestamp: number;
@@ -99,7 +105,18 @@ export type Output = {
 
 export type ErrorResponse = {
   error: string;
+  code?: string; // see rust/src/error/mod.rs
+  field?: string | null;
 };
+/** the rust side reports failures as { code, message, field } through output_error */
+export function readErrorResponse<T extends WasmProcessor>(
+  processor: T,
+  ptr: number,
+  len: number,
+): ErrorResponse {
+  const { code, message, field } = JSON.parse(processor.readString(ptr, len));
+  return { error: message, code, field };
+}
 
 interface HasNodeUrl {
   node_url: string;
@@ -195,6 +212,9 @@ export async function getBlocksBinScanResponse<T extends WasmProcessor>(
       }
     };
   };
+  processor.readErrorFromWasmMemory = (ptr, len) => {
+    result = readErrorResponse(processor, ptr, len);
+  };
   //@ts-ignore
   processor.tinywasi.instance.exports.scan_blocks_with_get_blocks_bin(
     getBlocksBinResponseBuffer.length,
@@ -274,12 +294,16 @@ export async function getOutsBinJson<T extends WasmProcessor & HasNodeUrl>(
   processor.readFromWasmMemory = (ptr, len) => {
     result = JSON.parse(processor.readString(ptr, len));
   };
+  let error: ErrorResponse | undefined;
+  processor.readErrorFromWasmMemory = (ptr, len) => {
+    error = readErrorResponse(processor, ptr, len);
+  };
   //@ts-ignore
   processor.tinywasi.instance.exports.convert_get_outs_bin_response_to_json(
     getOutsBinResponseBuffer.length,
   );
   if (!result) {
-    throw new Error("Failed to parse get_outs.bin response");
+    throw new Error(error?.error || "Failed to parse get_outs.bin response");
   }
   return result as GetOutsBinResponse;
 }
@@ -307,10 +331,15 @@ export async function loadGetBlocksBinResponse<T extends WasmProcessor>(
       processor.readString(ptr, len),
     ) as GetBlocksResultMeta;
   };
+  let error: ErrorResponse | undefined;
+  processor.readErrorFromWasmMemory = (ptr, len) => {
+    error = readErrorResponse(processor, ptr, len);
+  };
   //@ts-ignore
   processor.tinywasi.instance.exports.load_get_blocks_bin_response(
     getBlocksBinResponseBuffer.length,
   );
+  if (error) throw new Error(error.error);
   return resultMeta!;
 }
 
@@ -323,15 +352,15 @@ export async function loadGetBlocksBinResponse<T extends WasmProcessor>(
  *
  * ErrorResponse type:
  * the error messages come from the rust side in rust/src/:
- * - "Block index {} out of bounds (total blocks: {})"
+ * - "block index {} out of bounds (total blocks: {})", code invalid_input, field block_index
  *   in lib.rs get_blocks_bin_scan_one_block, guards blockIndex against response.blocks.len
- * - "No getBlocks.bin response loaded. Call loadGetBlocksBinResponse first."
- *   in lib.rs get_blocks_bin_scan_one_block, when GLOBAL_GET_BLOCKS_BIN_RESPONSE is None
- * - "Error scanning miner transaction: {}"
- *   in block_parsing/mod.rs scan_block, scanner.scan_transaction failed when parsing miner tx of block
- * - "Error scanning block: {}"
- *   in block_parsing/mod.rs scan_block, scanner.scan(scan_block) failed
- * all errors are returned as { error: string } (ErrorResponse type)
+ * - "no getblocks.bin response loaded, call load_get_blocks_bin_response first", code failed
+ *   in lib.rs get_blocks_bin_scan_one_block, when the wallet has no response loaded
+ * - "failed to parse block at height {}: {}" / "failed to parse pruned transaction {}: {}"
+ *   in block_parsing/mod.rs scan_block, code invalid_input
+ * - "error scanning miner transaction: {}" / "error scanning block: {}"
+ *   in block_parsing/mod.rs scan_block, scanner.scan_transaction failed, code failed
+ * all errors are returned as { error: message, code, field } (ErrorResponse type)
  */
 export async function getBlocksBinScanOneBlock<T extends WasmProcessor>(
   processor: T,
@@ -344,6 +373,9 @@ export async function getBlocksBinScanOneBlock<T extends WasmProcessor>(
       return value;
     }) as ScanResult | ErrorResponse;
   };
+  processor.readErrorFromWasmMemory = (ptr, len) => {
+    result = readErrorResponse(processor, ptr, len);
+  };
   //@ts-ignore
   processor.tinywasi.instance.exports.get_blocks_bin_scan_one_block(blockIndex);
   return result!;
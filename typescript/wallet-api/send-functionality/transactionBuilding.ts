@@ -70,11 +70,15 @@ export type MakeTransactionParams = {
   payments: {
     address: string;
     amount: string;
+    subtract_fee_from_amount?: boolean; // the recipient pays (a share of) the fee
   }[];
   fee_response: GetFeeEstimateResult;
   fee_priority: string;
   outgoing_view_key?: string;
   data?: number[][];
+  fee_split?: "even" | "proportional"; // how the fee is split across subtract_fee_from_amount payments
+  change_subaddress_index?: { major: number; minor: number }; // default: primary address
+  change_address?: string; // fingerprintable, change to an external address
 };
 export type NotEnoughFundsError = {
   inputs: number;
@@ -11,6 +11,7 @@ import {
   MAINNET_GENESIS_BLOCK_HASH,
   STAGENET_GENESIS_BLOCK_HASH,
   type ErrorResponse,
+  readErrorResponse,
 } from "../node-interaction/binaryEndpoints";
 import {
   lastRange,
@@ -87,7 +88,7 @@ export class ViewPair extends WasmProcessor {
       init_viewpair_result = JSON.parse(viewPair.readString(ptr, len));
     };
     viewPair.readErrorFromWasmMemory = (ptr, len) => {
-      init_viewpair_error = JSON.parse(viewPair.readString(ptr, len));
+      init_viewpair_error = readErrorResponse(viewPair, ptr, len);
     };
     //@ts-ignore
     tinywasi.instance.exports.init_viewpair(
@@ -96,7 +97,7 @@ export class ViewPair extends WasmProcessor {
       subaddress_index,
     );
 
-    if (init_viewpair_error && "error" in init_viewpair_error) {
+    if (init_viewpair_error) {
       throw new Error(
         `primary-address-not-valid (${primary_address.slice(0, 8)}...${primary_address.slice(-8)})`,
       );